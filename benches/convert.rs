@@ -0,0 +1,52 @@
+//! Benchmarks of the conversion pipeline.
+//!
+//! Run with `cargo bench`. Stages are measured through the public API calls closest to them, on a
+//! synthetic 2000x3000 page which is downscaled to fit 1920x1920.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use image::imageops::{resize, FilterType};
+use image::{GrayImage, Luma};
+use maco::{compute_bounds_batch, convert, convert_with_bounds, ConvertParams};
+
+/// Generates a page with smooth gradients, sharp edges and some noise.
+fn page(width: u32, height: u32) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| {
+        let gradient = (x + y) / 24 % 200 + 20;
+        let line = if (x / 40 + y / 60) % 7 == 0 {
+            0
+        } else {
+            gradient
+        };
+        let noise = (x.wrapping_mul(31) ^ y.wrapping_mul(17)) % 9;
+        Luma([(line + noise).min(255) as u8])
+    })
+}
+
+fn bench_convert(c: &mut Criterion) {
+    let source = page(2000, 3000);
+    let resized = resize(&source, 1280, 1920, FilterType::CatmullRom);
+    let defaults = ConvertParams::default();
+    let no_gamma = ConvertParams::builder().gamma(1.0).build();
+
+    c.bench_function("convert", |b| {
+        b.iter(|| convert(black_box(source.clone()), &defaults))
+    });
+    c.bench_function("convert gamma 1", |b| {
+        b.iter(|| convert(black_box(source.clone()), &no_gamma))
+    });
+    c.bench_function("resize", |b| {
+        b.iter(|| resize(black_box(&source), 1280, 1920, FilterType::CatmullRom))
+    });
+    c.bench_function("percentile", |b| {
+        b.iter(|| compute_bounds_batch(black_box(std::slice::from_ref(&resized)), 1))
+    });
+    c.bench_function("contrast", |b| {
+        b.iter(|| convert_with_bounds(black_box(resized.clone()), &no_gamma, (20, 230)))
+    });
+    c.bench_function("gamma", |b| {
+        b.iter(|| convert_with_bounds(black_box(resized.clone()), &defaults, (0, 255)))
+    });
+}
+
+criterion_group!(benches, bench_convert);
+criterion_main!(benches);
@@ -0,0 +1,478 @@
+use image::imageops::{resize, FilterType};
+use image::{GrayImage, Luma};
+use imageproc::contrast::otsu_level;
+use imageproc::filter::filter3x3;
+
+use crate::convert::histogram;
+
+/// Share of all pixels a single value needs to exceed to be considered part of the image content
+/// rather than noise. Corresponds to the density of a uniform distribution.
+const NOISE_DENSITY: f64 = 1_f64 / 256_f64;
+
+/// Largest cutoff returned by [`estimate_cutoff()`].
+const MAX_CUTOFF: u8 = 10;
+
+/// Estimates a cutoff for [`ConvertParamsBuilder::cutoff()`](crate::ConvertParamsBuilder::cutoff)
+/// which trims noise from the darkest and brightest ends of the histogram.
+///
+/// Starting from each end of the histogram, values are treated as noise until one is found which
+/// is more common than in a uniform distribution. The returned cutoff covers the larger of the two
+/// noise tails, capped at 10%.
+///
+/// When converting a batch of related pages, consider using the median of per-page estimates.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::estimate_cutoff;
+///
+/// let clean = GrayImage::from_fn(100, 100, |x, _| Luma([x as u8 + 50]));
+///
+/// assert_eq!(estimate_cutoff(&clean), 0);
+/// ```
+pub fn estimate_cutoff(image: &GrayImage) -> u8 {
+    let hist = histogram(image);
+    let total: u64 = hist.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+
+    let threshold = total as f64 * NOISE_DENSITY;
+    let tail = |bins: &mut dyn Iterator<Item = &u64>| -> u64 {
+        bins.take_while(|&&count| (count as f64) < threshold).sum()
+    };
+    let tail = tail(&mut hist.iter()).max(tail(&mut hist.iter().rev()));
+
+    // Round up, so that the whole tail gets trimmed.
+    let cutoff = (100 * tail).div_ceil(total);
+    cutoff.min(u64::from(MAX_CUTOFF)) as u8
+}
+
+/// 3x3 Laplacian kernel, responding to rapid changes in intensity.
+const LAPLACIAN: [i32; 9] = [0, 1, 0, 1, -4, 1, 0, 1, 0];
+
+/// Estimates how sharp an image is, computed as the variance of its Laplacian.
+///
+/// Higher values indicate more fine detail and crisper edges. The score depends on image content,
+/// so it's most useful for comparing similar images, e.g. pages of the same series, and for
+/// spotting soft scans which could use extra sharpening.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::estimate_sharpness;
+///
+/// let flat = GrayImage::from_pixel(10, 10, Luma([128]));
+///
+/// assert_eq!(estimate_sharpness(&flat), 0.0);
+/// ```
+pub fn estimate_sharpness(image: &GrayImage) -> f64 {
+    let laplacian = filter3x3::<Luma<u8>, i32, i32>(image, &LAPLACIAN);
+    let n = laplacian.len() as f64;
+    if n == 0_f64 {
+        return 0_f64;
+    }
+    let mean = laplacian.iter().map(|&v| f64::from(v)).sum::<f64>() / n;
+    laplacian
+        .iter()
+        .map(|&v| (f64::from(v) - mean).powi(2))
+        .sum::<f64>()
+        / n
+}
+
+/// Range of gamma values searched by [`match_gamma()`].
+const MATCH_GAMMA_RANGE: (f64, f64) = (0.1, 10.0);
+
+/// Number of golden section search steps taken by [`match_gamma()`], narrowing the range well
+/// below the precision of 8-bit values.
+const MATCH_GAMMA_STEPS: usize = 60;
+
+/// Finds the gamma modifier for
+/// [`ConvertParamsBuilder::gamma()`](crate::ConvertParamsBuilder::gamma) which makes the
+/// histogram of an image most similar to a reference histogram, e.g. of a well exposed page from
+/// the same batch.
+///
+/// Histograms are compared by the area between their cumulative distributions, so only the shape
+/// of the reference matters, not its pixel count. Returns 1 if either histogram is empty.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::match_gamma;
+///
+/// let image = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+/// let mut reference = [0; 256];
+/// for x in 0..256 {
+///     reference[x] = 1;
+/// }
+///
+/// assert!((match_gamma(&image, &reference) - 1.0).abs() < 0.01);
+/// ```
+pub fn match_gamma(image: &GrayImage, reference: &[u32; 256]) -> f64 {
+    let hist = histogram(image);
+    let reference = cumulative(reference.iter().map(|&count| u64::from(count)));
+    let total: u64 = hist.iter().sum();
+    if total == 0 || reference[255] == 0.0 {
+        return 1.0;
+    }
+
+    let distance = |gamma: f64| {
+        let mut transformed = [0_u64; 256];
+        for (value, &count) in hist.iter().enumerate() {
+            let mapped = (value as f64 / 255.0).powf(1.0 / gamma) * 255.0;
+            transformed[mapped.round() as usize] += count;
+        }
+        let transformed = cumulative(transformed.iter().copied());
+        transformed
+            .iter()
+            .zip(&reference)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>()
+    };
+
+    // Golden section search on the logarithm of gamma, which treats darkening and brightening
+    // symmetrically.
+    let ratio = (5_f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (MATCH_GAMMA_RANGE.0.ln(), MATCH_GAMMA_RANGE.1.ln());
+    for _ in 0..MATCH_GAMMA_STEPS {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if distance(a.exp()) <= distance(b.exp()) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    ((low + high) / 2.0).exp()
+}
+
+/// Range of the share of pixels classified as ink by [`is_text_page()`]. Text is sparse, so
+/// pages with more ink are likely art.
+const TEXT_INK_SHARE: (f64, f64) = (0.01, 0.35);
+
+/// Smallest difference between the mean ink and the mean background value of a text page.
+const TEXT_MIN_CONTRAST: f64 = 96.0;
+
+/// Largest share of pixels a text page may have away from both the ink and the background, i.e.
+/// in the middle half between their means.
+const TEXT_MAX_MIDTONES: f64 = 0.15;
+
+/// Smallest share of pixels on an ink edge for a text page. Thin strokes have many edges for
+/// their area, solid shapes few.
+const TEXT_MIN_EDGES: f64 = 0.02;
+
+/// Guesses whether an image is a page of mostly text, e.g. prose or 4-koma, rather than art.
+///
+/// Text pages have a bimodal histogram with sparse dark ink on a bright background, few midtones
+/// and a high density of edges between the two. They usually look best binarized with
+/// [`ConvertParamsBuilder::threshold()`](crate::ConvertParamsBuilder::threshold), while art pages
+/// should keep their grays.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::is_text_page;
+///
+/// let gradient = GrayImage::from_fn(100, 100, |x, _| Luma([x as u8 * 2 + 20]));
+///
+/// assert!(!is_text_page(&gradient));
+/// ```
+pub fn is_text_page(image: &GrayImage) -> bool {
+    let (width, height) = image.dimensions();
+    let total = f64::from(width) * f64::from(height);
+    if total == 0.0 {
+        return false;
+    }
+    let threshold = otsu_level(image);
+    let hist = histogram(image);
+
+    let (mut ink, mut ink_sum, mut background_sum) = (0_u64, 0_u64, 0_u64);
+    for (value, &count) in hist.iter().enumerate() {
+        if value <= usize::from(threshold) {
+            ink += count;
+            ink_sum += value as u64 * count;
+        } else {
+            background_sum += value as u64 * count;
+        }
+    }
+    let ink_share = ink as f64 / total;
+    if ink == 0 || ink_share < TEXT_INK_SHARE.0 || ink_share > TEXT_INK_SHARE.1 {
+        return false;
+    }
+    let ink_mean = ink_sum as f64 / ink as f64;
+    let background_mean = background_sum as f64 / (total - ink as f64);
+    let contrast = background_mean - ink_mean;
+    if contrast < TEXT_MIN_CONTRAST {
+        return false;
+    }
+
+    let (low, high) = (ink_mean + contrast / 4.0, background_mean - contrast / 4.0);
+    let midtones: u64 = hist
+        .iter()
+        .enumerate()
+        .filter(|&(value, _)| (low..=high).contains(&(value as f64)))
+        .map(|(_, &count)| count)
+        .sum();
+    if midtones as f64 / total > TEXT_MAX_MIDTONES {
+        return false;
+    }
+
+    let is_ink = |x: u32, y: u32| image[(x, y)][0] <= threshold;
+    let edges = image
+        .enumerate_pixels()
+        .filter(|&(x, y, _)| {
+            (x + 1 < width && is_ink(x, y) != is_ink(x + 1, y))
+                || (y + 1 < height && is_ink(x, y) != is_ink(x, y + 1))
+        })
+        .count();
+    edges as f64 / total >= TEXT_MIN_EDGES
+}
+
+/// Largest mean brightness of the borders of a negative scan.
+const NEGATIVE_MAX_BORDER: f64 = 64.0;
+
+/// Width of the borders checked by [`is_negative()`], as a share of the image dimensions.
+const NEGATIVE_BORDER_SHARE: f64 = 0.02;
+
+/// Returns whether an image looks like a negative scan, i.e. its inverse is a text page and its
+/// borders are dark.
+pub(crate) fn is_negative(image: &GrayImage) -> bool {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return false;
+    }
+    let border_x = ((f64::from(width) * NEGATIVE_BORDER_SHARE).ceil() as u32).max(1);
+    let border_y = ((f64::from(height) * NEGATIVE_BORDER_SHARE).ceil() as u32).max(1);
+    let (sum, count) = image
+        .enumerate_pixels()
+        .filter(|&(x, y, _)| {
+            x < border_x || y < border_y || x >= width - border_x || y >= height - border_y
+        })
+        .fold((0_u64, 0_u64), |(sum, count), (_, _, p)| {
+            (sum + u64::from(p[0]), count + 1)
+        });
+    if sum as f64 / count as f64 > NEGATIVE_MAX_BORDER {
+        return false;
+    }
+    let mut inverted = image.clone();
+    image::imageops::invert(&mut inverted);
+    is_text_page(&inverted)
+}
+
+/// Weight of sharpness retention in [`quality_score()`].
+const SHARPNESS_WEIGHT: f64 = 0.4;
+
+/// Weight of structural similarity in [`quality_score()`].
+const STRUCTURE_WEIGHT: f64 = 0.6;
+
+/// Side length of the blocks structural similarity is computed over.
+const STRUCTURE_BLOCK: u32 = 8;
+
+/// Scores how well a conversion kept the detail of the original image, from 0 (worst) to 1.
+///
+/// The score combines two measures, weighted 0.4 and 0.6 respectively:
+///
+/// - Sharpness retention, the ratio of [`estimate_sharpness()`] of the converted image to that of
+///   the original, inverted if above 1 so that over-sharpening is penalized like blurring.
+/// - Structural similarity, the average correlation between 8x8 blocks of both images. Tone
+///   adjustments which keep detail barely affect it, while crushed shadows or highlights, where
+///   detail is clipped to flat black or white, lower it.
+///
+/// The original is resized to the dimensions of the converted image for comparison. Batch drivers
+/// can use the score to flag conversions for review.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::quality_score;
+///
+/// let image = GrayImage::from_fn(64, 64, |x, y| Luma([(x * 3 + y) as u8]));
+///
+/// assert!(quality_score(&image, &image) > 0.99);
+/// ```
+pub fn quality_score(original: &GrayImage, converted: &GrayImage) -> f64 {
+    let (width, height) = converted.dimensions();
+    if width == 0 || height == 0 {
+        return 0.0;
+    }
+    let resized;
+    let original = if original.dimensions() == converted.dimensions() {
+        original
+    } else {
+        resized = resize(original, width, height, FilterType::CatmullRom);
+        &resized
+    };
+
+    let (before, after) = (estimate_sharpness(original), estimate_sharpness(converted));
+    let retention = if before == 0.0 && after == 0.0 {
+        1.0
+    } else if after <= before {
+        after / before
+    } else {
+        before / after
+    };
+
+    let mut similarity = 0.0;
+    let mut blocks = 0;
+    for y in (0..height).step_by(STRUCTURE_BLOCK as usize) {
+        for x in (0..width).step_by(STRUCTURE_BLOCK as usize) {
+            let (w, h) = (
+                STRUCTURE_BLOCK.min(width - x),
+                STRUCTURE_BLOCK.min(height - y),
+            );
+            similarity += block_correlation(original, converted, x, y, w, h);
+            blocks += 1;
+        }
+    }
+    let similarity = similarity / f64::from(blocks);
+
+    SHARPNESS_WEIGHT * retention + STRUCTURE_WEIGHT * similarity
+}
+
+/// Returns the correlation between the given block of two images, clamped to `0..=1`. Blocks flat
+/// in both images are identical, blocks flat in only one share no structure.
+fn block_correlation(a: &GrayImage, b: &GrayImage, x: u32, y: u32, w: u32, h: u32) -> f64 {
+    let pixels = || {
+        (y..y + h).flat_map(move |py| {
+            (x..x + w).map(move |px| (f64::from(a[(px, py)][0]), f64::from(b[(px, py)][0])))
+        })
+    };
+    let n = f64::from(w * h);
+    let (mean_a, mean_b) = pixels().fold((0.0, 0.0), |(sa, sb), (pa, pb)| (sa + pa, sb + pb));
+    let (mean_a, mean_b) = (mean_a / n, mean_b / n);
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (pa, pb) in pixels() {
+        cov += (pa - mean_a) * (pb - mean_b);
+        var_a += (pa - mean_a).powi(2);
+        var_b += (pb - mean_b).powi(2);
+    }
+    match (var_a == 0.0, var_b == 0.0) {
+        (true, true) => 1.0,
+        (true, false) | (false, true) => 0.0,
+        (false, false) => (cov / (var_a * var_b).sqrt()).clamp(0.0, 1.0),
+    }
+}
+
+/// Returns the cumulative distribution of a histogram, normalized to end at 1.
+pub(crate) fn cumulative(hist: impl Iterator<Item = u64>) -> [f64; 256] {
+    let mut sums = [0_f64; 256];
+    let mut sum = 0;
+    for (s, count) in sums.iter_mut().zip(hist) {
+        sum += count;
+        *s = sum as f64;
+    }
+    if sum > 0 {
+        sums.iter_mut().for_each(|s| *s /= sum as f64);
+    }
+    sums
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::filter::gaussian_blur_f32;
+    use imageproc::stats::percentile;
+
+    use super::*;
+
+    #[test]
+    fn estimate_cutoff_trims_noise() {
+        // 300 pixels of sparse noise on each end and 9400 pixels of content in 100..=150.
+        let values: Vec<u8> = (0..10000)
+            .map(|i| match i {
+                0..=299 => (i / 4) as u8,
+                300..=599 => (180 + (i - 300) / 4) as u8,
+                _ => (100 + i % 51) as u8,
+            })
+            .collect();
+        let image = GrayImage::from_fn(100, 100, |x, y| Luma([values[(y * 100 + x) as usize]]));
+
+        let cutoff = estimate_cutoff(&image);
+
+        assert_eq!(cutoff, 3);
+        // Noise spans 0..=74 and 180..=254, so all of it is clipped by the contrast stretch.
+        assert!(percentile(&image, cutoff) >= 74);
+        assert!(percentile(&image, 100 - cutoff) <= 180);
+    }
+
+    #[test]
+    fn estimate_cutoff_is_capped() {
+        let image = GrayImage::from_fn(100, 100, |x, y| {
+            Luma([if y < 50 { (x + y * 100) as u8 } else { 128 }])
+        });
+
+        assert_eq!(estimate_cutoff(&image), MAX_CUTOFF);
+    }
+
+    #[test]
+    fn match_gamma_finds_exponent() {
+        let image = GrayImage::from_fn(64, 64, |x, y| Luma([(x * 3 + y) as u8 / 2 + 20]));
+        let own = histogram(&image).map(|count| count as u32);
+
+        assert!((match_gamma(&image, &own) - 1.0).abs() < 0.02);
+
+        // Darkened with x^2, which gamma 2 undoes.
+        let dark = GrayImage::from_fn(64, 64, |x, y| {
+            let v = f64::from(image[(x, y)][0]) / 255.0;
+            Luma([(v * v * 255.0).round() as u8])
+        });
+        let gamma = match_gamma(&dark, &own);
+        assert!((gamma - 2.0).abs() < 0.1, "gamma was {}", gamma);
+
+        assert_eq!(match_gamma(&GrayImage::new(0, 0), &own), 1.0);
+        assert_eq!(match_gamma(&image, &[0; 256]), 1.0);
+    }
+
+    #[test]
+    fn is_text_page_detects_text() {
+        // Rows of small glyphs made of vertical and horizontal strokes.
+        let text = GrayImage::from_fn(200, 200, |x, y| {
+            let (gx, gy) = (x % 8, y % 14);
+            let stroke = gy < 9 && (gx < 2 || (gy == 4 && gx < 6));
+            Luma([if stroke { 25 } else { 235 }])
+        });
+        let tone = gaussian_blur_f32(
+            &GrayImage::from_fn(200, 200, |x, y| Luma([((x + y) / 2) as u8 + 10])),
+            3.0,
+        );
+        let solid = GrayImage::from_fn(200, 200, |x, _| Luma([if x < 100 { 10 } else { 240 }]));
+
+        assert!(is_text_page(&text));
+        assert!(!is_text_page(&tone));
+        assert!(!is_text_page(&solid));
+        assert!(!is_text_page(&GrayImage::new(0, 0)));
+    }
+
+    #[test]
+    fn quality_score_penalizes_crushing() {
+        let original = GrayImage::from_fn(128, 128, |x, y| {
+            let texture = if (x / 2 + y / 2) % 2 == 0 { 12 } else { 0 };
+            Luma([(x + y / 2) as u8 + texture + 30])
+        });
+        let sensible = crate::convert(original.clone(), &crate::ConvertParams::default());
+        let crushed = crate::convert(
+            original.clone(),
+            &crate::ConvertParams::builder().cutoff(45).build(),
+        );
+
+        let good = quality_score(&original, &sensible);
+        let bad = quality_score(&original, &crushed);
+
+        assert!(good > bad, "{} <= {}", good, bad);
+        assert!((0.0..=1.0).contains(&good) && (0.0..=1.0).contains(&bad));
+    }
+
+    #[test]
+    fn estimate_sharpness_blurred_is_lower() {
+        let sharp = GrayImage::from_fn(64, 64, |x, y| {
+            Luma([if (x / 4 + y / 4) % 2 == 0 { 20 } else { 230 }])
+        });
+        let blurred = gaussian_blur_f32(&sharp, 2.0);
+
+        assert!(estimate_sharpness(&blurred) < estimate_sharpness(&sharp));
+    }
+}
@@ -0,0 +1,492 @@
+use std::path::{Path, PathBuf};
+
+use image::GrayImage;
+
+use crate::io::{load, save, LoadParams, OutputFormat, SaveParams};
+use crate::{convert_with_stats, estimate_sharpness, ConvertParams, ConvertStats, Error};
+
+/// Summarizes the conversion of a batch of images, e.g. all pages of a chapter.
+///
+/// See also: [`convert_batch()`], [`BatchSummary::from_stats()`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BatchSummary {
+    /// Number of converted pages.
+    pub pages: usize,
+    /// Total size of input pixel buffers in bytes.
+    pub input_bytes: u64,
+    /// Total size of output pixel buffers in bytes.
+    pub output_bytes: u64,
+    /// Average ratio of output to input bytes per page. 0 for an empty batch.
+    pub compression_ratio: f64,
+    /// Number of pages on which the contrast stretch was skipped.
+    pub contrast_skipped: usize,
+    /// Number of pages on which the gamma modifier was skipped.
+    pub gamma_skipped: usize,
+    /// Smallest output width and height, or `None` for an empty batch.
+    pub min_dimensions: Option<(u32, u32)>,
+    /// Largest output width and height, or `None` for an empty batch.
+    pub max_dimensions: Option<(u32, u32)>,
+    /// Number of files which failed to convert and were skipped.
+    pub skipped: usize,
+    /// Files which failed to convert, if errors are collected.
+    ///
+    /// See also: [`OnError::Collect`].
+    pub errors: Vec<BatchError>,
+    /// Indices of input pages which are less sharp than [`BatchParamsBuilder::min_sharpness()`],
+    /// e.g. blurry scans worth replacing.
+    pub low_quality: Vec<usize>,
+}
+
+/// Describes a file which failed to convert as part of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    /// Path of the input file.
+    pub path: PathBuf,
+    /// Description of the error.
+    pub message: String,
+}
+
+/// Defines what a batch conversion does when a file fails to convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    /// Stops the batch, returning the error.
+    Abort,
+    /// Skips the file, only counting it in [`BatchSummary::skipped`].
+    Skip,
+    /// Skips the file, recording the error in [`BatchSummary::errors`].
+    Collect,
+}
+
+impl BatchSummary {
+    /// Aggregates per-page [`ConvertStats`] into a summary.
+    pub fn from_stats(stats: &[ConvertStats]) -> Self {
+        let mut summary = BatchSummary::default();
+        let mut ratio_sum = 0_f64;
+        for s in stats {
+            summary.pages += 1;
+            summary.input_bytes += s.input_bytes as u64;
+            summary.output_bytes += s.output_bytes as u64;
+            if s.input_bytes > 0 {
+                ratio_sum += s.output_bytes as f64 / s.input_bytes as f64;
+            }
+            summary.contrast_skipped += s.contrast_skipped as usize;
+            summary.gamma_skipped += s.gamma_skipped as usize;
+
+            let (width, height) = s.output_dimensions;
+            summary.min_dimensions = Some(match summary.min_dimensions {
+                Some((w, h)) => (w.min(width), h.min(height)),
+                None => (width, height),
+            });
+            summary.max_dimensions = Some(match summary.max_dimensions {
+                Some((w, h)) => (w.max(width), h.max(height)),
+                None => (width, height),
+            });
+        }
+        if summary.pages > 0 {
+            summary.compression_ratio = ratio_sum / summary.pages as f64;
+        }
+        summary
+    }
+}
+
+/// Converts a batch of images according to given params, returning the converted images along
+/// with a [`BatchSummary`].
+///
+/// See also: [`convert_with_stats()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_batch, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(100).height(100).build();
+/// let images = vec![GrayImage::new(200, 200), GrayImage::new(50, 50)];
+/// let (_, summary) = convert_batch(images, &params);
+///
+/// assert_eq!(summary.pages, 2);
+/// assert_eq!(summary.max_dimensions, Some((100, 100)));
+/// ```
+pub fn convert_batch(
+    images: Vec<GrayImage>,
+    params: &ConvertParams,
+) -> (Vec<GrayImage>, BatchSummary) {
+    let (images, stats): (Vec<_>, Vec<_>) = images
+        .into_iter()
+        .map(|image| convert_with_stats(image, params))
+        .unzip();
+    (images, BatchSummary::from_stats(&stats))
+}
+
+/// Converts a batch of images in parallel, returning the converted images in input order along
+/// with a [`BatchSummary`].
+///
+/// Runs on a dedicated pool of [`BatchParamsBuilder::threads()`] threads, or on the global rayon
+/// thread pool if that is 0. Results are identical to [`convert_batch()`], except that pages are
+/// also checked against [`BatchParamsBuilder::min_sharpness()`].
+///
+/// # Errors
+///
+/// Fails if the thread pool cannot be created.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_batch_parallel, BatchParams, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(100).height(100).build();
+/// let batch_params = BatchParams::builder().threads(2).build();
+/// let images = vec![GrayImage::new(200, 200), GrayImage::new(50, 50)];
+/// let (_, summary) = convert_batch_parallel(images, &params, &batch_params)?;
+///
+/// assert_eq!(summary.pages, 2);
+/// # Ok::<(), maco::Error>(())
+/// ```
+#[cfg(feature = "rayon")]
+pub fn convert_batch_parallel(
+    images: Vec<GrayImage>,
+    params: &ConvertParams,
+    batch_params: &BatchParams,
+) -> Result<(Vec<GrayImage>, BatchSummary), Error> {
+    use rayon::prelude::*;
+
+    let run = || {
+        let results: Vec<_> = images
+            .into_par_iter()
+            .map(|image| {
+                let low_quality = batch_params.is_low_quality(&image);
+                let (image, stats) = convert_with_stats(image, params);
+                (image, stats, low_quality)
+            })
+            .collect();
+        let mut images = Vec::with_capacity(results.len());
+        let mut stats = Vec::with_capacity(results.len());
+        let mut low_quality = Vec::new();
+        for (index, (image, page_stats, low)) in results.into_iter().enumerate() {
+            images.push(image);
+            stats.push(page_stats);
+            if low {
+                low_quality.push(index);
+            }
+        }
+        let mut summary = BatchSummary::from_stats(&stats);
+        summary.low_quality = low_quality;
+        (images, summary)
+    };
+    if batch_params.threads == 0 {
+        return Ok(run());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(batch_params.threads)
+        .build()?;
+    Ok(pool.install(run))
+}
+
+/// Defines parameters for the [`convert_dir()`] function.
+///
+/// See also: [`BatchParamsBuilder`], [`BatchParamsBuilder::default()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::{BatchParams, OnError};
+///
+/// BatchParams::builder().on_error(OnError::Collect).build();
+/// ```
+#[derive(Debug)]
+pub struct BatchParams {
+    on_error: OnError,
+    format: OutputFormat,
+    min_sharpness: Option<f64>,
+    #[cfg(feature = "rayon")]
+    threads: usize,
+}
+
+impl Default for BatchParams {
+    /// Convenience method wrapping [`BatchParamsBuilder::default()`].
+    fn default() -> Self {
+        BatchParamsBuilder::default().build()
+    }
+}
+
+impl BatchParams {
+    /// Returns a new [`BatchParamsBuilder`] with default settings.
+    pub fn builder() -> BatchParamsBuilder {
+        BatchParamsBuilder::default()
+    }
+
+    /// Returns whether an input page is too blurry according to
+    /// [`BatchParamsBuilder::min_sharpness()`].
+    fn is_low_quality(&self, image: &GrayImage) -> bool {
+        match self.min_sharpness {
+            Some(min) => estimate_sharpness(image) < min,
+            None => false,
+        }
+    }
+}
+
+/// Builds [`BatchParams`].
+pub struct BatchParamsBuilder {
+    on_error: OnError,
+    format: OutputFormat,
+    min_sharpness: Option<f64>,
+    #[cfg(feature = "rayon")]
+    threads: usize,
+}
+
+impl Default for BatchParamsBuilder {
+    /// Default values are equal to the following:
+    ///
+    /// ```ignore
+    /// BatchParamsBuilder {
+    ///     on_error: OnError::Abort,
+    ///     format: OutputFormat::Png,
+    ///     min_sharpness: None,
+    ///     threads: 0,
+    /// }
+    /// ```
+    fn default() -> Self {
+        BatchParamsBuilder {
+            on_error: OnError::Abort,
+            format: OutputFormat::Png,
+            min_sharpness: None,
+            #[cfg(feature = "rayon")]
+            threads: 0,
+        }
+    }
+}
+
+impl BatchParamsBuilder {
+    /// Sets what happens when a file fails to load, convert or save.
+    pub fn on_error(&mut self, on_error: OnError) -> &mut Self {
+        self.on_error = on_error;
+        self
+    }
+
+    /// Sets the format of output images.
+    pub fn format(&mut self, format: OutputFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the sharpness below which input pages are reported in [`BatchSummary::low_quality`].
+    /// Sharpness is measured on input pages, before conversion.
+    ///
+    /// See also: [`estimate_sharpness()`].
+    pub fn min_sharpness(&mut self, sharpness: f64) -> &mut Self {
+        self.min_sharpness = Some(sharpness);
+        self
+    }
+
+    /// Sets the number of threads used by [`convert_batch_parallel()`]. 0 uses the global rayon
+    /// thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Builds and returns a [`BatchParams`] instance.
+    pub fn build(&self) -> BatchParams {
+        BatchParams {
+            on_error: self.on_error,
+            format: self.format,
+            min_sharpness: self.min_sharpness,
+            #[cfg(feature = "rayon")]
+            threads: self.threads,
+        }
+    }
+}
+
+/// Converts every file in a directory, saving the results into another directory.
+///
+/// Files are processed in the order of their names. Outputs keep the name of their input, with
+/// the extension of the output format. Subdirectories are ignored. Failures to read the input
+/// directory always abort, failures of individual files are handled according to
+/// [`BatchParamsBuilder::on_error()`].
+pub fn convert_dir(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    params: &ConvertParams,
+    batch_params: &BatchParams,
+) -> Result<BatchSummary, Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(input)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+
+    let save_params = SaveParams::builder().format(batch_params.format).build();
+    let mut stats = Vec::with_capacity(paths.len());
+    let mut failures = Vec::new();
+    let mut low_quality = Vec::new();
+    for (index, path) in paths.into_iter().enumerate() {
+        let name = path
+            .with_extension(batch_params.format.extension())
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let result = load(&path, &LoadParams::default()).and_then(|image| {
+            if batch_params.is_low_quality(&image) {
+                low_quality.push(index);
+            }
+            let (image, page_stats) = convert_with_stats(image, params);
+            save(&image, output.as_ref().join(name), &save_params)?;
+            Ok(page_stats)
+        });
+        match result {
+            Ok(page_stats) => stats.push(page_stats),
+            Err(e) if batch_params.on_error == OnError::Abort => return Err(e),
+            Err(e) => {
+                log::warn!("skipping {}: {}", path.display(), e);
+                failures.push(BatchError {
+                    path,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    let mut summary = BatchSummary::from_stats(&stats);
+    summary.skipped = failures.len();
+    summary.low_quality = low_quality;
+    if batch_params.on_error == OnError::Collect {
+        summary.errors = failures;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+    use imageproc::filter::gaussian_blur_f32;
+
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let gradient = GrayImage::from_fn(200, 100, |x, _| Luma([(x / 2) as u8 + 50]));
+        let full_range = GrayImage::from_fn(50, 80, |x, y| Luma([if x > y { 255 } else { 0 }]));
+        let params = ConvertParams::builder()
+            .width(100)
+            .height(100)
+            .gamma(1.0)
+            .build();
+
+        let (images, summary) = convert_batch(vec![gradient, full_range], &params);
+
+        assert_eq!(images.len(), 2);
+        assert_eq!(
+            summary,
+            BatchSummary {
+                pages: 2,
+                input_bytes: 200 * 100 + 50 * 80,
+                output_bytes: 100 * 50 + 50 * 80,
+                compression_ratio: (0.25 + 1.0) / 2.0,
+                contrast_skipped: 1,
+                gamma_skipped: 2,
+                min_dimensions: Some((50, 50)),
+                max_dimensions: Some((100, 80)),
+                skipped: 0,
+                errors: Vec::new(),
+                low_quality: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn convert_dir_flags_blurry_pages() {
+        let input = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let sharp = GrayImage::from_fn(60, 80, |x, y| {
+            Luma([if (x / 2 + y / 3) % 2 == 0 { 0 } else { 255 }])
+        });
+        let blurry = gaussian_blur_f32(&sharp, 3.0);
+        for (name, page) in [("01.png", &sharp), ("02.png", &blurry), ("03.png", &sharp)] {
+            page.save(input.path().join(name)).unwrap();
+        }
+        let threshold = estimate_sharpness(&sharp) / 10.0;
+        assert!(estimate_sharpness(&blurry) < threshold);
+        let params = ConvertParams::default();
+
+        let batch_params = BatchParams::builder().min_sharpness(threshold).build();
+        let summary = convert_dir(input.path(), output.path(), &params, &batch_params).unwrap();
+        assert_eq!(summary.low_quality, vec![1]);
+
+        let summary = convert_dir(
+            input.path(),
+            output.path(),
+            &params,
+            &BatchParams::default(),
+        )
+        .unwrap();
+        assert!(summary.low_quality.is_empty());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn convert_batch_parallel_matches_serial() {
+        let images: Vec<_> = (0..6)
+            .map(|i| GrayImage::from_fn(40 + i * 10, 60, |x, y| Luma([(x * y + i) as u8])))
+            .collect();
+        let params = ConvertParams::builder().width(30).height(30).build();
+
+        let serial = convert_batch(images.clone(), &params);
+        for threads in [0, 1, 3] {
+            let batch_params = BatchParams::builder().threads(threads).build();
+            let parallel = convert_batch_parallel(images.clone(), &params, &batch_params).unwrap();
+            assert_eq!(parallel, serial);
+        }
+    }
+
+    #[test]
+    fn summary_empty() {
+        assert_eq!(BatchSummary::from_stats(&[]), BatchSummary::default());
+    }
+
+    /// Creates a directory with two valid pages around a garbage file.
+    fn pages_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let page = GrayImage::from_fn(20, 30, |x, y| Luma([(x + y) as u8 * 4]));
+        page.save(dir.path().join("01.png")).unwrap();
+        std::fs::write(dir.path().join("02.png"), b"not an image").unwrap();
+        page.save(dir.path().join("03.png")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn convert_dir_on_error() {
+        let input = pages_dir();
+        let params = ConvertParams::default();
+        let convert = |on_error| {
+            let output = tempfile::tempdir().unwrap();
+            let batch_params = BatchParams::builder().on_error(on_error).build();
+            let result = convert_dir(input.path(), output.path(), &params, &batch_params);
+            let mut names: Vec<_> = std::fs::read_dir(output.path())
+                .unwrap()
+                .map(|e| e.unwrap().file_name())
+                .collect();
+            names.sort();
+            (result, names)
+        };
+
+        let (result, names) = convert(OnError::Skip);
+        let summary = result.unwrap();
+        assert_eq!((summary.pages, summary.skipped), (2, 1));
+        assert!(summary.errors.is_empty());
+        assert_eq!(names, vec!["01.png", "03.png"]);
+
+        let (result, _) = convert(OnError::Collect);
+        let summary = result.unwrap();
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(summary.errors[0].path, input.path().join("02.png"));
+
+        let (result, names) = convert(OnError::Abort);
+        assert!(matches!(result, Err(Error::Image(_))));
+        assert_eq!(names, vec!["01.png"]);
+    }
+}
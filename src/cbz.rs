@@ -0,0 +1,707 @@
+//! Converting CBZ archives.
+
+use std::io::{Read, Seek, Write};
+
+use image::codecs::png::PngEncoder;
+use image::{imageops, ColorType, DynamicImage, GrayImage};
+use zip::read::ZipFile;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::convert::resize_color;
+use crate::io::{encode, SaveParams};
+use crate::layout::blank_page;
+use crate::{
+    convert, is_color, split_spread, to_gray, ConvertParams, Error, LumaWeights, ReadingDirection,
+};
+
+/// Name of the metadata file in a CBZ archive.
+const COMIC_INFO: &str = "ComicInfo.xml";
+
+/// Extensions of archive entries treated as pages.
+const PAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "bmp", "webp"];
+
+/// Metadata stored in the ComicInfo.xml file of a CBZ archive.
+///
+/// Only the fields used during conversion are parsed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComicInfo {
+    /// Reading direction declared by the `Manga` element, if any.
+    pub reading_direction: Option<ReadingDirection>,
+}
+
+impl ComicInfo {
+    /// Parses the contents of a ComicInfo.xml file. Unknown or malformed fields are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use maco::cbz::ComicInfo;
+    /// use maco::ReadingDirection;
+    ///
+    /// let info = ComicInfo::parse("<ComicInfo><Manga>YesAndRightToLeft</Manga></ComicInfo>");
+    ///
+    /// assert_eq!(info.reading_direction, Some(ReadingDirection::RightToLeft));
+    /// ```
+    pub fn parse(xml: &str) -> ComicInfo {
+        let reading_direction = match element(xml, "Manga") {
+            Some("Yes") | Some("YesAndRightToLeft") => Some(ReadingDirection::RightToLeft),
+            Some("No") => Some(ReadingDirection::LeftToRight),
+            _ => None,
+        };
+        ComicInfo { reading_direction }
+    }
+}
+
+/// Defines how the pages of an archive are grouped into chapters by [`convert_cbz_chapters()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterRule {
+    /// Pages whose names share the part before the first occurrence of the separator belong to the
+    /// same chapter, e.g. `ch1_p1.png` and `ch1_p2.png` with `'_'`. Use `'/'` for archives storing
+    /// chapters in directories. Pages without the separator are grouped into an unnamed chapter.
+    Prefix(char),
+    /// A chapter starts at every page with a `Bookmark` attribute in the `Pages` element of the
+    /// ComicInfo.xml, named after the bookmark. Pages before the first bookmark and archives
+    /// without bookmarks form an unnamed chapter.
+    Bookmarks,
+}
+
+/// Returns the indices of pages with a bookmark declared in a ComicInfo.xml, along with the
+/// bookmark text.
+fn bookmarks(xml: &str) -> Vec<(usize, &str)> {
+    let mut bookmarks: Vec<(usize, &str)> = xml
+        .split("<Page ")
+        .skip(1)
+        .filter_map(|tag| {
+            let tag = &tag[..tag.find('>')?];
+            let image = attribute(tag, "Image")?.parse().ok()?;
+            Some((image, attribute(tag, "Bookmark")?))
+        })
+        .collect();
+    bookmarks.sort_by_key(|&(image, _)| image);
+    bookmarks
+}
+
+/// Returns the value of an attribute in the contents of an XML tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!(" {}=\"", name);
+    let start = format!(" {}", tag).find(&key)? + key.len() - 1;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+/// Returns the trimmed text content of the first element with the given name.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let len = xml[start..].find(&close)?;
+    Some(xml[start..start + len].trim())
+}
+
+/// Defines parameters for the [`convert_cbz()`] function.
+///
+/// See also: [`CbzParamsBuilder`], [`CbzParamsBuilder::default()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::cbz::CbzParams;
+/// use maco::ReadingDirection;
+///
+/// CbzParams::builder()
+///     .reading_direction(ReadingDirection::RightToLeft)
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct CbzParams {
+    split_spreads: bool,
+    even_pages: bool,
+    reading_direction: ReadingDirection,
+    luma_weights: LumaWeights,
+    color_cover: bool,
+}
+
+impl Default for CbzParams {
+    /// Convenience method wrapping [`CbzParamsBuilder::default()`].
+    fn default() -> Self {
+        CbzParamsBuilder::default().build()
+    }
+}
+
+impl CbzParams {
+    /// Returns a new [`CbzParamsBuilder`] with default settings.
+    pub fn builder() -> CbzParamsBuilder {
+        CbzParamsBuilder::default()
+    }
+}
+
+/// Builds [`CbzParams`].
+pub struct CbzParamsBuilder {
+    split_spreads: bool,
+    even_pages: bool,
+    reading_direction: ReadingDirection,
+    luma_weights: LumaWeights,
+    color_cover: bool,
+}
+
+impl Default for CbzParamsBuilder {
+    /// Default values are equal to the following:
+    ///
+    /// ```ignore
+    /// CbzParamsBuilder {
+    ///     split_spreads: true,
+    ///     even_pages: false,
+    ///     reading_direction: ReadingDirection::LeftToRight,
+    ///     luma_weights: LumaWeights::Rec709,
+    ///     color_cover: false,
+    /// }
+    /// ```
+    fn default() -> Self {
+        CbzParamsBuilder {
+            split_spreads: true,
+            even_pages: false,
+            reading_direction: ReadingDirection::LeftToRight,
+            luma_weights: LumaWeights::Rec709,
+            color_cover: false,
+        }
+    }
+}
+
+impl CbzParamsBuilder {
+    /// Sets whether two-page spreads should be split into separate pages.
+    ///
+    /// See also: [`split_spread()`].
+    pub fn split_spreads(&mut self, split: bool) -> &mut Self {
+        self.split_spreads = split;
+        self
+    }
+
+    /// Sets whether a blank page should be appended if the output would have an odd number of
+    /// pages, so that spreads line up in readers showing two pages at a time.
+    ///
+    /// Pages are only ever added: blank separators in the input are kept, as telling them apart
+    /// from intentionally blank pages isn't reliable.
+    ///
+    /// See also: [`pad_to_even()`](crate::pad_to_even).
+    pub fn even_pages(&mut self, even: bool) -> &mut Self {
+        self.even_pages = even;
+        self
+    }
+
+    /// Sets the reading direction used to order split pages if the archive doesn't declare one in
+    /// its ComicInfo.xml.
+    pub fn reading_direction(&mut self, direction: ReadingDirection) -> &mut Self {
+        self.reading_direction = direction;
+        self
+    }
+
+    /// Sets the weights used to convert color pages to grayscale.
+    pub fn luma_weights(&mut self, weights: LumaWeights) -> &mut Self {
+        self.luma_weights = weights;
+        self
+    }
+
+    /// Sets whether the first page should be kept in color if it is a color image. Such a cover
+    /// is only resized, without adjusting its tones.
+    ///
+    /// See also: [`is_color()`].
+    pub fn color_cover(&mut self, keep: bool) -> &mut Self {
+        self.color_cover = keep;
+        self
+    }
+
+    /// Builds and returns a [`CbzParams`] instance.
+    pub fn build(&self) -> CbzParams {
+        CbzParams {
+            split_spreads: self.split_spreads,
+            even_pages: self.even_pages,
+            reading_direction: self.reading_direction,
+            luma_weights: self.luma_weights,
+            color_cover: self.color_cover,
+        }
+    }
+}
+
+/// Converts every page of a CBZ archive, writing the results as PNG files into a new archive.
+///
+/// Pages are processed in the order of their names and renamed to consecutive numbers. Color
+/// pages are converted to grayscale, except for a color cover if requested in `cbz_params`. If the
+/// archive contains a ComicInfo.xml declaring a reading direction, it overrides the one in
+/// `cbz_params`. The ComicInfo.xml is copied to the output unchanged, other files are dropped.
+pub fn convert_cbz<R, W>(
+    reader: R,
+    writer: W,
+    params: &ConvertParams,
+    cbz_params: &CbzParams,
+) -> Result<(), Error>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let mut archive = ZipArchive::new(reader)?;
+    let (pages, comic_info) = contents(&mut archive)?;
+    write_cbz(
+        &mut archive,
+        &pages,
+        comic_info.as_deref(),
+        writer,
+        params,
+        cbz_params,
+    )
+}
+
+/// Converts a multi-chapter CBZ archive like [`convert_cbz()`], writing one archive per chapter.
+///
+/// Chapters are detected according to `rule` and written in order to writers returned by
+/// `create`, which receives the index and name of each chapter. Every output archive is treated
+/// as a separate book: it starts with its own cover if [`CbzParamsBuilder::color_cover()`] is set,
+/// is padded on its own if [`CbzParamsBuilder::even_pages()`] is set and receives a copy of the
+/// ComicInfo.xml.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+///
+/// use maco::cbz::{convert_cbz_chapters, CbzParams, ChapterRule};
+/// use maco::ConvertParams;
+///
+/// convert_cbz_chapters(
+///     File::open("volume.cbz")?,
+///     &ConvertParams::default(),
+///     &CbzParams::default(),
+///     ChapterRule::Prefix('/'),
+///     |index, _| File::create(format!("chapter-{:02}.cbz", index + 1)),
+/// )?;
+/// # Ok::<(), maco::Error>(())
+/// ```
+pub fn convert_cbz_chapters<R, W, F>(
+    reader: R,
+    params: &ConvertParams,
+    cbz_params: &CbzParams,
+    rule: ChapterRule,
+    mut create: F,
+) -> Result<(), Error>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+    F: FnMut(usize, &str) -> std::io::Result<W>,
+{
+    let mut archive = ZipArchive::new(reader)?;
+    let (pages, comic_info) = contents(&mut archive)?;
+    let mut chapters: Vec<(String, Vec<String>)> = Vec::new();
+    match rule {
+        ChapterRule::Prefix(separator) => {
+            for page in &pages {
+                let name = page.split_once(separator).map_or("", |(prefix, _)| prefix);
+                match chapters.iter_mut().find(|(chapter, _)| *chapter == name) {
+                    Some((_, pages)) => pages.push(page.clone()),
+                    None => chapters.push((name.to_string(), vec![page.clone()])),
+                }
+            }
+        }
+        ChapterRule::Bookmarks => {
+            let xml = comic_info.as_deref().map(String::from_utf8_lossy);
+            let marks = xml.as_deref().map(bookmarks).unwrap_or_default();
+            for (index, page) in pages.iter().enumerate() {
+                match marks.iter().find(|&&(image, _)| image == index) {
+                    Some(&(_, name)) => chapters.push((name.to_string(), Vec::new())),
+                    None if chapters.is_empty() => chapters.push((String::new(), Vec::new())),
+                    None => {}
+                }
+                chapters
+                    .last_mut()
+                    .expect("chapter exists")
+                    .1
+                    .push(page.clone());
+            }
+        }
+    }
+    for (index, (name, chapter)) in chapters.iter().enumerate() {
+        write_cbz(
+            &mut archive,
+            chapter,
+            comic_info.as_deref(),
+            create(index, name)?,
+            params,
+            cbz_params,
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the sorted names of the pages in an archive and the contents of its ComicInfo.xml.
+fn contents<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<(Vec<String>, Option<Vec<u8>>), Error> {
+    let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+    names.sort();
+    let comic_info = match names.iter().find(|name| name.as_str() == COMIC_INFO) {
+        Some(name) => Some(read_entry(archive, name)?),
+        None => None,
+    };
+    names.retain(|name| is_page(name));
+    Ok((names, comic_info))
+}
+
+/// Converts a CBZ archive like [`convert_cbz()`], reading and converting one entry at a time.
+///
+/// Unlike [`convert_cbz()`], the input doesn't need to be seekable and only a single page is held
+/// in memory at once, so arbitrarily large archives can be converted, e.g. straight from a network
+/// stream. In exchange, pages are processed in the order they are stored in the archive rather
+/// than by name, and the ComicInfo.xml only sets the reading direction of pages stored after it.
+/// Entries whose sizes are only recorded after their data, as written by some streaming zip
+/// tools, can't be read this way.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use maco::cbz::{convert_cbz_streaming, CbzParams};
+/// use maco::ConvertParams;
+///
+/// convert_cbz_streaming(
+///     BufReader::new(File::open("volume.cbz")?),
+///     File::create("volume-converted.cbz")?,
+///     &ConvertParams::default(),
+///     &CbzParams::default(),
+/// )?;
+/// # Ok::<(), maco::Error>(())
+/// ```
+pub fn convert_cbz_streaming<R, W>(
+    mut reader: R,
+    writer: W,
+    params: &ConvertParams,
+    cbz_params: &CbzParams,
+) -> Result<(), Error>
+where
+    R: Read,
+    W: Write + Seek,
+{
+    let mut out = PageWriter::new(writer);
+    let mut comic_info = None;
+    while let Some(mut entry) = zip::read::read_zipfile_from_stream(&mut reader)? {
+        if entry.name() == COMIC_INFO {
+            comic_info = Some(read_file(&mut entry)?);
+        } else if is_page(entry.name()) {
+            let bytes = read_file(&mut entry)?;
+            drop(entry);
+            let direction = reading_direction(comic_info.as_deref(), cbz_params);
+            out.add(
+                image::load_from_memory(&bytes)?,
+                direction,
+                params,
+                cbz_params,
+            )?;
+        }
+    }
+    out.finish(comic_info.as_deref(), cbz_params)
+}
+
+/// Returns the reading direction declared in a ComicInfo.xml, falling back to the configured one.
+fn reading_direction(comic_info: Option<&[u8]>, cbz_params: &CbzParams) -> ReadingDirection {
+    comic_info
+        .and_then(|xml| ComicInfo::parse(&String::from_utf8_lossy(xml)).reading_direction)
+        .unwrap_or(cbz_params.reading_direction)
+}
+
+/// Converts the given pages of an archive into a new archive, as described in [`convert_cbz()`].
+fn write_cbz<R, W>(
+    archive: &mut ZipArchive<R>,
+    pages: &[String],
+    comic_info: Option<&[u8]>,
+    writer: W,
+    params: &ConvertParams,
+    cbz_params: &CbzParams,
+) -> Result<(), Error>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let direction = reading_direction(comic_info, cbz_params);
+    let mut out = PageWriter::new(writer);
+    for name in pages {
+        let image = image::load_from_memory(&read_entry(archive, name)?)?;
+        out.add(image, direction, params, cbz_params)?;
+    }
+    out.finish(comic_info, cbz_params)
+}
+
+/// Writes converted pages into an archive, numbering them consecutively.
+struct PageWriter<W: Write + Seek> {
+    out: ZipWriter<W>,
+    options: FileOptions,
+    save_params: SaveParams,
+    index: usize,
+    last_page: Option<GrayImage>,
+}
+
+impl<W: Write + Seek> PageWriter<W> {
+    fn new(writer: W) -> Self {
+        PageWriter {
+            out: ZipWriter::new(writer),
+            // Pages are already compressed, so only store them.
+            options: FileOptions::default().compression_method(CompressionMethod::Stored),
+            save_params: SaveParams::default(),
+            index: 0,
+            last_page: None,
+        }
+    }
+
+    /// Converts a page of the input and adds the resulting pages to the archive.
+    fn add(
+        &mut self,
+        image: DynamicImage,
+        direction: ReadingDirection,
+        params: &ConvertParams,
+        cbz_params: &CbzParams,
+    ) -> Result<(), Error> {
+        if self.index == 0 && cbz_params.color_cover && is_color(&image) {
+            let cover = resize_color(image.into_rgb8(), params);
+            self.start_page()?;
+            PngEncoder::new(&mut self.out).encode(
+                &cover,
+                cover.width(),
+                cover.height(),
+                ColorType::Rgb8,
+            )?;
+            self.last_page = Some(imageops::grayscale(&cover));
+            return Ok(());
+        }
+        let image = to_gray(image, cbz_params.luma_weights);
+        let pages = if cbz_params.split_spreads {
+            split_spread(&image, direction)
+        } else {
+            vec![image]
+        };
+        for page in pages {
+            let page = convert(page, params);
+            self.start_page()?;
+            encode(&page, &mut self.out, &self.save_params)?;
+            self.last_page = Some(page);
+        }
+        Ok(())
+    }
+
+    fn start_page(&mut self) -> Result<(), Error> {
+        self.index += 1;
+        self.out
+            .start_file(format!("{:04}.png", self.index), self.options)?;
+        Ok(())
+    }
+
+    /// Pads the archive to an even number of pages if configured, adds the ComicInfo.xml and
+    /// finishes the archive.
+    fn finish(mut self, comic_info: Option<&[u8]>, cbz_params: &CbzParams) -> Result<(), Error> {
+        if cbz_params.even_pages && !self.index.is_multiple_of(2) {
+            if let Some(page) = self.last_page.take() {
+                self.start_page()?;
+                encode(&blank_page(&page), &mut self.out, &self.save_params)?;
+            }
+        }
+        if let Some(xml) = comic_info {
+            self.out.start_file(COMIC_INFO, self.options)?;
+            self.out.write_all(xml)?;
+        }
+        self.out.finish()?;
+        Ok(())
+    }
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>, Error> {
+    read_file(&mut archive.by_name(name)?)
+}
+
+/// Largest number of bytes preallocated for an archive entry, as sizes in its headers can't be
+/// trusted.
+const MAX_PREALLOCATION: u64 = 64 << 20;
+
+/// Reads the contents of an archive entry, preallocating at most [`MAX_PREALLOCATION`] bytes.
+fn read_file(entry: &mut ZipFile<'_>) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(entry.size().min(MAX_PREALLOCATION) as usize);
+    entry.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Returns whether an archive entry is a page, judging by its extension.
+fn is_page(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((_, ext)) => PAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    /// Builds a CBZ archive containing the given files.
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn png(image: &GrayImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encode(image, &mut bytes, &SaveParams::default()).unwrap();
+        bytes
+    }
+
+    /// Returns the width and value of the top left pixel of each page in a converted archive.
+    fn pages(cbz: Vec<u8>) -> Vec<(u32, u8)> {
+        let mut archive = ZipArchive::new(Cursor::new(cbz)).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+        names.sort();
+        names
+            .iter()
+            .filter(|name| is_page(name))
+            .map(|name| {
+                let bytes = read_entry(&mut archive, name).unwrap();
+                let page = image::load_from_memory(&bytes).unwrap().into_luma8();
+                (page.width(), page[(0, 0)][0])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn comic_info_parse() {
+        let info = |manga: &str| {
+            let xml = format!(
+                "<?xml version=\"1.0\"?>\n<ComicInfo>\n  <Manga>{}</Manga>\n</ComicInfo>",
+                manga
+            );
+            ComicInfo::parse(&xml).reading_direction
+        };
+
+        assert_eq!(
+            info("YesAndRightToLeft"),
+            Some(ReadingDirection::RightToLeft)
+        );
+        assert_eq!(info(" Yes "), Some(ReadingDirection::RightToLeft));
+        assert_eq!(info("No"), Some(ReadingDirection::LeftToRight));
+        assert_eq!(info("Unknown"), None);
+        assert_eq!(ComicInfo::parse("<ComicInfo/>").reading_direction, None);
+    }
+
+    #[test]
+    fn convert_cbz_orders_spreads() {
+        // Black left half, white right half.
+        let spread = GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 0 } else { 255 }]));
+        let spread = png(&spread);
+        let cover = png(&GrayImage::from_pixel(6, 8, Luma([255])));
+        let rtl = b"<ComicInfo><Manga>YesAndRightToLeft</Manga></ComicInfo>";
+        let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+
+        let convert = |files: &[(&str, &[u8])], cbz_params: &CbzParams| {
+            let mut out = Cursor::new(Vec::new());
+            convert_cbz(Cursor::new(archive(files)), &mut out, &params, cbz_params).unwrap();
+            pages(out.into_inner())
+        };
+
+        let files: [(&str, &[u8]); 3] = [
+            ("02.png", &spread),
+            ("01.png", &cover),
+            ("ComicInfo.xml", rtl),
+        ];
+        assert_eq!(
+            convert(&files, &CbzParams::default()),
+            vec![(6, 255), (4, 255), (4, 0)]
+        );
+
+        // Without ComicInfo.xml, the configured direction is used.
+        let files: [(&str, &[u8]); 2] = [("01.png", &cover), ("02.png", &spread)];
+        assert_eq!(
+            convert(&files, &CbzParams::default()),
+            vec![(6, 255), (4, 0), (4, 255)]
+        );
+        let cbz_params = CbzParams::builder().split_spreads(false).build();
+        assert_eq!(convert(&files, &cbz_params), vec![(6, 255), (8, 0)]);
+
+        // Three pages gain a blank fourth one, matching the last page.
+        let cbz_params = CbzParams::builder().even_pages(true).build();
+        assert_eq!(
+            convert(&files, &cbz_params),
+            vec![(6, 255), (4, 0), (4, 255), (4, 255)]
+        );
+        let cbz_params = CbzParams::builder()
+            .split_spreads(false)
+            .even_pages(true)
+            .build();
+        assert_eq!(convert(&files, &cbz_params), vec![(6, 255), (8, 0)]);
+    }
+
+    #[test]
+    fn convert_cbz_chapters_splits_by_prefix() {
+        let page = |width| png(&GrayImage::from_pixel(width, 8, Luma([255])));
+        let (first, second, third) = (page(2), page(4), page(6));
+        let files: [(&str, &[u8]); 3] = [
+            ("ch2_p1.png", &third),
+            ("ch1_p1.png", &first),
+            ("ch1_p2.png", &second),
+        ];
+        let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+        let cbz_params = CbzParams::builder().split_spreads(false).build();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |index: usize, name: &str| dir.path().join(format!("{}-{}.cbz", index, name));
+        convert_cbz_chapters(
+            Cursor::new(archive(&files)),
+            &params,
+            &cbz_params,
+            ChapterRule::Prefix('_'),
+            |index, name| std::fs::File::create(path(index, name)),
+        )
+        .unwrap();
+
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        let chapter = |index, name| pages(std::fs::read(path(index, name)).unwrap());
+        assert_eq!(chapter(0, "ch1"), vec![(2, 255), (4, 255)]);
+        assert_eq!(chapter(1, "ch2"), vec![(6, 255)]);
+    }
+
+    #[test]
+    fn convert_cbz_streaming_matches_convert_cbz() {
+        let spread = GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 0 } else { 255 }]));
+        let rtl = b"<ComicInfo><Manga>YesAndRightToLeft</Manga></ComicInfo>";
+        let files: [(&str, &[u8]); 3] = [
+            ("ComicInfo.xml", rtl),
+            ("01.png", &png(&GrayImage::from_pixel(6, 8, Luma([255])))),
+            ("02.png", &png(&spread)),
+        ];
+        let input = archive(&files);
+        let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+        let cbz_params = CbzParams::builder().even_pages(true).build();
+
+        let mut seeking = Cursor::new(Vec::new());
+        convert_cbz(Cursor::new(&input), &mut seeking, &params, &cbz_params).unwrap();
+        let mut streaming = Cursor::new(Vec::new());
+        convert_cbz_streaming(input.as_slice(), &mut streaming, &params, &cbz_params).unwrap();
+
+        let expected = vec![(6, 255), (4, 255), (4, 0), (4, 0)];
+        assert_eq!(pages(seeking.into_inner()), expected);
+        assert_eq!(pages(streaming.into_inner()), expected);
+    }
+
+    #[test]
+    fn comic_info_bookmarks() {
+        let xml = r#"<ComicInfo><Pages>
+            <Page Image="0" Type="FrontCover"/>
+            <Page Image="3" Bookmark="Chapter 2"/>
+            <Page Image="1" Type="Story" Bookmark="Chapter 1" />
+        </Pages></ComicInfo>"#;
+
+        assert_eq!(bookmarks(xml), vec![(1, "Chapter 1"), (3, "Chapter 2")]);
+        assert_eq!(bookmarks("<ComicInfo/>"), vec![]);
+    }
+}
@@ -0,0 +1,128 @@
+use image::{DynamicImage, GrayImage, Luma};
+
+/// Defines how color images are converted to grayscale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LumaWeights {
+    /// ITU-R BT.709 weights, as used by [`DynamicImage::into_luma8()`]. Suited to images
+    /// produced digitally.
+    Rec709,
+    /// ITU-R BT.601 weights, as used by e.g. Pillow and most JPEG decoders.
+    Rec601,
+    /// Plain average of the red, green and blue channels.
+    Average,
+}
+
+/// Minimum difference between the largest and smallest channel of a pixel to count as colored.
+const COLOR_CHROMA: u8 = 32;
+
+/// Share of colored pixels above which an image is considered to be in color.
+const COLOR_SHARE: f32 = 0.02;
+
+/// Converts an image to grayscale using the given weights. Alpha is dropped and grayscale images
+/// are returned unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, Rgb, RgbImage};
+/// use maco::{to_gray, LumaWeights};
+///
+/// let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([255, 0, 0])));
+///
+/// assert_eq!(to_gray(red.clone(), LumaWeights::Rec709)[(0, 0)][0], 54);
+/// assert_eq!(to_gray(red.clone(), LumaWeights::Rec601)[(0, 0)][0], 76);
+/// assert_eq!(to_gray(red, LumaWeights::Average)[(0, 0)][0], 85);
+/// ```
+pub fn to_gray(image: DynamicImage, weights: LumaWeights) -> GrayImage {
+    let image = match image {
+        DynamicImage::ImageLuma8(image) => return image,
+        DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_) => return image.into_luma8(),
+        image => image,
+    };
+    let weights = match weights {
+        LumaWeights::Rec709 => return image.into_luma8(),
+        // Fixed point weights matching Pillow's conversion.
+        LumaWeights::Rec601 => [19595, 38470, 7471],
+        LumaWeights::Average => [21845, 21846, 21845],
+    };
+    let image = image.into_rgb8();
+    GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        let l = weights[0] * u32::from(r) + weights[1] * u32::from(g) + weights[2] * u32::from(b);
+        Luma([((l + 0x8000) >> 16) as u8])
+    })
+}
+
+/// Returns whether an image contains enough saturated pixels to be considered in color, e.g. a
+/// color cover among grayscale pages.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, Rgb, RgbImage};
+/// use maco::is_color;
+///
+/// let sepia = RgbImage::from_pixel(10, 10, Rgb([112, 100, 90]));
+/// let red = RgbImage::from_pixel(10, 10, Rgb([200, 30, 30]));
+///
+/// assert!(!is_color(&DynamicImage::ImageRgb8(sepia)));
+/// assert!(is_color(&DynamicImage::ImageRgb8(red)));
+/// ```
+pub fn is_color(image: &DynamicImage) -> bool {
+    let image = match image {
+        DynamicImage::ImageLuma8(_)
+        | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_) => return false,
+        image => image.to_rgb8(),
+    };
+    let colored = image
+        .pixels()
+        .filter(|p| {
+            let (min, max) = (p.0.iter().min().unwrap(), p.0.iter().max().unwrap());
+            max - min > COLOR_CHROMA
+        })
+        .count();
+    colored as f32 > (image.width() * image.height()) as f32 * COLOR_SHARE
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn to_gray_weights() {
+        let image = RgbImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgb([0, 255, 0]),
+            1 => Rgb([0, 0, 255]),
+            _ => Rgb([255, 255, 255]),
+        });
+        let gray = |weights| to_gray(DynamicImage::ImageRgb8(image.clone()), weights).into_raw();
+
+        assert_eq!(gray(LumaWeights::Rec709), vec![182, 18, 255]);
+        assert_eq!(gray(LumaWeights::Rec601), vec![150, 29, 255]);
+        assert_eq!(gray(LumaWeights::Average), vec![85, 85, 255]);
+    }
+
+    #[test]
+    fn is_color_threshold() {
+        // A few colored pixels, e.g. a stamp, don't make a page colored.
+        let page = |colored: u32| {
+            DynamicImage::ImageRgb8(RgbImage::from_fn(100, 10, |x, _| {
+                Rgb(if x < colored {
+                    [255, 0, 0]
+                } else {
+                    [128, 128, 128]
+                })
+            }))
+        };
+
+        assert!(!is_color(&page(1)));
+        assert!(is_color(&page(3)));
+        assert!(!is_color(&DynamicImage::ImageLuma8(GrayImage::new(10, 10))));
+    }
+}
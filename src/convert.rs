@@ -1,7 +1,20 @@
-use image::imageops::{resize, FilterType};
-use image::{GrayImage, Luma};
-use imageproc::contrast::stretch_contrast_mut;
-use imageproc::stats::percentile;
+use std::borrow::Cow;
+
+use image::imageops::{self, resize, FilterType};
+use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+use imageproc::seam_carving;
+use log::{debug, trace};
+
+use crate::analysis::{self, cumulative};
+use crate::dither::{self, DitherMethod};
+use crate::error::{BuildError, Error};
+use crate::geometry::Rect;
+use crate::integral::IntegralImage;
+use crate::layout::{split_vertical, SplitStrategy};
+use crate::local;
+use crate::resample::{self, CustomFilter, EdgeMode, UpscaleMethod};
+use crate::scan_lines;
+use crate::speckle;
 
 /// Converts an image according to given params.
 ///
@@ -28,218 +41,2972 @@ use imageproc::stats::percentile;
 /// );
 /// ```
 pub fn convert(image: GrayImage, params: &ConvertParams) -> GrayImage {
-    let (width, height) =
-        resize_dimensions(image.width(), image.height(), params.width, params.height);
-    // If width didn't change, height didn't change either.
-    // If width increased but we don't want to upscale, we can skip.
-    let mut image = if width == image.width() || (width > image.width() && !params.upscale) {
-        image
-    } else {
-        resize(&image, width, height, params.filter)
-    };
+    convert_with_stats(image, params).0
+}
 
-    let lower = percentile(&image, params.cutoff);
-    let upper = percentile(&image, 100_u8 - params.cutoff);
-    // If lower is 0 and upper 255, the histogram won't change, making computation redundant.
-    if upper > lower && !(lower == 0 && upper == 255) {
-        stretch_contrast_mut(&mut image, lower, upper);
-    }
+/// Converts an image according to given params, reporting progress to `on_stage`.
+///
+/// `on_stage` is called as each [`Step`] of the conversion starts and finishes, in the order
+/// resize, contrast and gamma, letting a UI show progress while converting a single large image.
+/// Steps which turn out to have nothing to do are still reported.
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_with_progress, ConvertParams, Stage, Step};
+///
+/// let mut stages = Vec::new();
+/// convert_with_progress(GrayImage::new(8, 8), &ConvertParams::default(), |stage| {
+///     stages.push(stage)
+/// });
+///
+/// assert_eq!(stages[0], Stage::Started(Step::Resize));
+/// assert_eq!(stages.last(), Some(&Stage::Finished(Step::Gamma)));
+/// ```
+pub fn convert_with_progress(
+    image: GrayImage,
+    params: &ConvertParams,
+    mut on_stage: impl FnMut(Stage),
+) -> GrayImage {
+    on_stage(Stage::Started(Step::Resize));
+    let mut image = resize_stage(image, params);
+    on_stage(Stage::Finished(Step::Resize));
+    adjust_tone_with(&mut image, params, &mut on_stage);
+    image
+}
 
-    // If gamma == 1 the image doesn't change.
-    if (params.gamma - 1_f64).abs() > 0.001 {
-        apply_lut(&mut image, &params.gamma_lut);
-    }
+/// A step of [`convert()`], as reported by [`convert_with_progress()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Resizing the image, including the stages before and right after it, e.g. sharpening.
+    Resize,
+    /// Stretching contrast and thresholding.
+    Contrast,
+    /// Applying the tone curve.
+    Gamma,
+}
 
-    image
+/// Progress of [`convert_with_progress()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The given step started.
+    Started(Step),
+    /// The given step finished.
+    Finished(Step),
 }
 
-/// Defines parameters for the [`convert()`] function.
+/// Converts an image according to given params, additionally returning whether the output differs
+/// from the input.
 ///
-/// See also: [`ConvertParamsBuilder`], [`ConvertParamsBuilder::default()`].
+/// This lets callers skip rewriting files which conversion leaves untouched, e.g. pages which
+/// were already converted with the same params. The comparison is exact, so any stage modifying
+/// any pixel counts as a change.
 ///
-/// Examples
+/// See also: [`convert()`], [`looks_converted()`].
+///
+/// # Examples
 ///
 /// ```
-/// use maco::ConvertParams;
+/// use maco::{convert_if_changed, ConvertParams};
+/// use imageproc::gray_image;
 ///
-/// ConvertParams::builder().gamma(0.8).cutoff(10_u8).build();
+/// let params = ConvertParams::builder().gamma(0.5).build();
+///
+/// assert!(!convert_if_changed(gray_image!(0, 255), &params).1);
+/// assert!(convert_if_changed(gray_image!(0, 128, 255), &params).1);
 /// ```
-#[derive(Debug)]
-pub struct ConvertParams {
-    width: u32,
-    height: u32,
-    upscale: bool,
-    cutoff: u8,
-    filter: FilterType,
-    gamma: f64,
-    gamma_lut: [u8; 256],
+pub fn convert_if_changed(image: GrayImage, params: &ConvertParams) -> (GrayImage, bool) {
+    let output = convert(image.clone(), params);
+    let changed = output != image;
+    if !changed {
+        debug!("conversion left the image unchanged");
+    }
+    (output, changed)
 }
 
-impl Default for ConvertParams {
-    /// Convenience method wrapping [`ConvertParamsBuilder::default()`].
-    fn default() -> Self {
-        ConvertParamsBuilder::default().build()
-    }
+/// An input image for [`convert_source()`], stating whether it still needs to be resized.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// An image which was already resized, e.g. a downscaled preview. Only its tones are
+    /// adjusted.
+    Sized(GrayImage),
+    /// A full resolution image, which is resized according to the params before adjusting tones.
+    Full(GrayImage),
 }
 
-impl ConvertParams {
-    /// Returns a new [`ConvertParamsBuilder`] with default settings.
-    pub fn builder() -> ConvertParamsBuilder {
-        ConvertParamsBuilder::default()
+/// Converts an image according to given params, resizing it only if `source` says so.
+///
+/// Unlike [`convert()`], which decides whether to resize based on the image dimensions, this
+/// makes the caller's intent explicit.
+///
+/// See also: [`resize_only()`], [`tone_only()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_source, ConvertParams, Source};
+///
+/// let params = ConvertParams::builder().width(100).height(100).build();
+///
+/// let full = convert_source(Source::Full(GrayImage::new(200, 200)), &params);
+/// let sized = convert_source(Source::Sized(GrayImage::new(200, 200)), &params);
+///
+/// assert_eq!(full.dimensions(), (100, 100));
+/// assert_eq!(sized.dimensions(), (200, 200));
+/// ```
+pub fn convert_source(source: Source, params: &ConvertParams) -> GrayImage {
+    match source {
+        Source::Sized(image) => tone_only(image, params),
+        Source::Full(image) => convert(image, params),
     }
 }
 
-/// Builds [`ConvertParams`].
-pub struct ConvertParamsBuilder {
+/// Converts a raw grayscale buffer of `width * height` bytes, one per pixel in row-major order,
+/// according to given params.
+///
+/// This avoids wrapping pixels in a [`GrayImage`], e.g. for bindings to other languages. Returns
+/// the converted pixels along with their width and height, which may differ from the input due
+/// to resizing.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferSize`] if the length of `pixels` doesn't match the dimensions.
+///
+/// # Examples
+///
+/// ```
+/// use maco::{convert_raw, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(2).height(2).build();
+///
+/// let (pixels, width, height) = convert_raw(&[0; 16], 4, 4, &params).unwrap();
+/// assert_eq!((pixels.len(), width, height), (4, 2, 2));
+/// assert!(convert_raw(&[0; 15], 4, 4, &params).is_err());
+/// ```
+pub fn convert_raw(
+    pixels: &[u8],
     width: u32,
     height: u32,
-    upscale: bool,
-    cutoff: u8,
-    filter: FilterType,
-    gamma: f64,
+    params: &ConvertParams,
+) -> Result<(Vec<u8>, u32, u32), Error> {
+    let expected = u64::from(width) * u64::from(height);
+    if pixels.len() as u64 != expected {
+        return Err(Error::BufferSize {
+            len: pixels.len(),
+            expected,
+        });
+    }
+    let image = GrayImage::from_raw(width, height, pixels.to_vec()).expect("length was checked");
+    let image = convert(image, params);
+    let (width, height) = image.dimensions();
+    Ok((image.into_raw(), width, height))
 }
 
-impl Default for ConvertParamsBuilder {
-    /// Default values are equal to the following:
-    ///
-    /// ```ignore
-    /// ConvertParamsBuilder {
-    ///     width: 1920,
-    ///     height: 1920,
-    ///     upscale: false,
-    ///     cutoff: 1,
-    ///     filter: FilterType::CatmullRom,
-    ///     gamma: 0.75,
-    /// }
-    /// ```
-    fn default() -> Self {
-        ConvertParamsBuilder {
-            width: 1920,
-            height: 1920,
-            upscale: false,
-            cutoff: 1,
-            filter: FilterType::CatmullRom,
-            gamma: 0.75,
-        }
-    }
+/// Resizes an image according to given params, without adjusting its tones.
+///
+/// This runs only the first stage of [`convert()`], including skipping images which already fit
+/// and, unless [`ConvertParamsBuilder::upscale()`] is set, images which are smaller than the target
+/// dimensions.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{resize_only, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(100).height(100).build();
+///
+/// assert_eq!(resize_only(GrayImage::new(200, 400), &params).dimensions(), (50, 100));
+/// assert_eq!(resize_only(GrayImage::new(20, 40), &params).dimensions(), (20, 40));
+/// ```
+pub fn resize_only(image: GrayImage, params: &ConvertParams) -> GrayImage {
+    resize_stage(image, params)
 }
 
-impl ConvertParamsBuilder {
-    /// Sets maximum width for the output image. Aspect ratio will be preserved.
-    pub fn width(&mut self, width: u32) -> &mut Self {
-        self.width = width;
-        self
-    }
+/// Adjusts the tones of an image according to given params, without resizing it.
+///
+/// This runs only the contrast stretch and gamma stages of [`convert()`] on the image at its
+/// current size, e.g. for images which were already resized elsewhere.
+///
+/// # Examples
+///
+/// ```
+/// use maco::{tone_only, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let params = ConvertParams::builder().width(1).height(1).cutoff(0).gamma(1.0).build();
+///
+/// assert_eq!(tone_only(gray_image!(50, 100), &params), gray_image!(127, 255));
+/// ```
+pub fn tone_only(mut image: GrayImage, params: &ConvertParams) -> GrayImage {
+    adjust_tone(&mut image, params);
+    image
+}
 
-    /// Sets maximum height for the output image. Aspect ratio will be preserved.
-    pub fn height(&mut self, height: u32) -> &mut Self {
-        self.height = height;
-        self
+/// Converts an image according to given params, splitting it into several pages first if it's
+/// taller than allowed by [`ConvertParamsBuilder::max_aspect()`].
+///
+/// Images within the allowed aspect ratio, or all images if no maximum is set, are converted as a
+/// single page.
+///
+/// See also: [`convert()`], [`split_vertical()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{process, ConvertParams};
+///
+/// let params = ConvertParams::builder().max_aspect(3.0).build();
+///
+/// assert_eq!(process(GrayImage::new(100, 1000), &params).len(), 4);
+/// assert_eq!(process(GrayImage::new(600, 800), &params).len(), 1);
+/// ```
+pub fn process(image: GrayImage, params: &ConvertParams) -> Vec<GrayImage> {
+    match params.max_aspect {
+        Some(max_aspect) if image.height() as f32 > image.width() as f32 * max_aspect => {
+            split_vertical(&image, max_aspect, params.split_strategy)
+                .into_iter()
+                .map(|strip| convert(strip, params))
+                .collect()
+        }
+        _ => vec![convert(image, params)],
     }
+}
 
-    /// Sets whether or not an image smaller than the desired width x height should be upscaled.
-    pub fn upscale(&mut self, upscale: bool) -> &mut Self {
-        self.upscale = upscale;
-        self
-    }
+/// Describes what [`convert_with_stats()`] did to an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertStats {
+    /// Width and height of the input image.
+    pub input_dimensions: (u32, u32),
+    /// Width and height of the output image.
+    pub output_dimensions: (u32, u32),
+    /// Size of the input pixel buffer in bytes.
+    pub input_bytes: usize,
+    /// Size of the output pixel buffer in bytes.
+    pub output_bytes: usize,
+    /// Whether the contrast stretch was skipped because it wouldn't change the image.
+    pub contrast_skipped: bool,
+    /// Whether the gamma modifier was skipped because it wouldn't change the image.
+    pub gamma_skipped: bool,
+}
 
-    /// Sets % of the brightest and darkest pixels to ignore when equalizing the histogram.
-    pub fn cutoff(&mut self, upscale_percentile: u8) -> &mut Self {
-        self.cutoff = upscale_percentile;
-        self
-    }
+/// Converts an image according to given params, additionally returning [`ConvertStats`].
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_with_stats, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(100).height(100).build();
+/// let (_, stats) = convert_with_stats(GrayImage::new(200, 100), &params);
+///
+/// assert_eq!(stats.output_dimensions, (100, 50));
+/// assert_eq!(stats.output_bytes, 5000);
+/// ```
+pub fn convert_with_stats(image: GrayImage, params: &ConvertParams) -> (GrayImage, ConvertStats) {
+    let (image, report) = convert_with_report(image, params);
+    let (w, h) = report.input_dimensions;
 
-    /// Sets resampling filter used when resizing the image.
-    pub fn filter(&mut self, filter: FilterType) -> &mut Self {
-        self.filter = filter;
-        self
-    }
+    let stats = ConvertStats {
+        input_dimensions: report.input_dimensions,
+        output_dimensions: report.output_dimensions,
+        input_bytes: w as usize * h as usize,
+        output_bytes: image.len(),
+        contrast_skipped: report.stretch_bounds.is_none() && report.local_stretch.is_none(),
+        gamma_skipped: report.tone_curve.is_none(),
+    };
+    (image, stats)
+}
 
-    /// Sets a gamma modifier. Values < 1 darken the image, values > 1 brighten it.
-    pub fn gamma(&mut self, gamma: f64) -> &mut Self {
-        self.gamma = gamma;
-        self
-    }
+/// Records the operations [`convert_with_report()`] applied to an image.
+///
+/// This is a provenance record meant to be stored alongside the output, not enough information
+/// to restore the original image. With the `serde` feature enabled it can be serialized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConvertReport {
+    /// Width and height of the input image.
+    pub input_dimensions: (u32, u32),
+    /// Width and height of the output image.
+    pub output_dimensions: (u32, u32),
+    /// Lower and upper bounds of the contrast stretch, or `None` if it was skipped.
+    pub stretch_bounds: Option<(u8, u8)>,
+    /// Tiles contrast was stretched in, if it was stretched per tile.
+    ///
+    /// See also: [`ConvertParamsBuilder::local_stretch()`].
+    pub local_stretch: Option<(u32, u32)>,
+    /// Whether the contrast stretch used a soft clip.
+    pub soft_clip: bool,
+    /// Tone curve applied by the gamma stage, or `None` if it was skipped.
+    pub tone_curve: Option<ToneCurve>,
+    /// Strength of the ink boost applied along with the tone curve.
+    ///
+    /// See also: [`ConvertParamsBuilder::ink_boost()`].
+    pub ink_boost: f32,
+    /// Range of input values the tone curve was limited to, if any.
+    pub gamma_protect: Option<(u8, u8)>,
+    /// Whether the tone curve was applied with error diffusion dithering.
+    pub dithered: bool,
+    /// Threshold the image was binarized with, if any.
+    ///
+    /// See also: [`ConvertParamsBuilder::threshold()`].
+    pub threshold: Option<u8>,
+}
 
-    /// Builds and returns a [`ConvertParams`] instance.
-    pub fn build(&self) -> ConvertParams {
-        ConvertParams {
-            width: self.width,
-            height: self.height,
-            upscale: self.upscale,
-            cutoff: self.cutoff,
-            filter: self.filter,
-            gamma: self.gamma,
-            gamma_lut: generate_gamma_lut(self.gamma),
+/// Converts an image according to given params, additionally returning a [`ConvertReport`].
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_with_report, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(100).height(100).build();
+/// let (_, report) = convert_with_report(GrayImage::new(200, 100), &params);
+///
+/// assert_eq!(report.output_dimensions, (100, 50));
+/// assert_eq!(report.stretch_bounds, None);
+/// ```
+pub fn convert_with_report(image: GrayImage, params: &ConvertParams) -> (GrayImage, ConvertReport) {
+    let input_dimensions = image.dimensions();
+
+    let image = prepare_stage(image, params);
+    let (image, stages) = match fused_linear_stage(&image, params) {
+        Some(fused) => fused,
+        None => {
+            let mut image = resize_prepared(image, params);
+            let stages = adjust_tone(&mut image, params);
+            (image, stages)
         }
-    }
-}
+    };
 
-/// Generates a lookup table with gamma modifications applied.
-fn generate_gamma_lut(gamma: f64) -> [u8; 256] {
-    let mut lut = [0; 256];
-    for (i, x) in lut.iter_mut().enumerate() {
-        *x = clamp((i as f64 / 255_f64).powf(1_f64 / gamma) * 255_f64)
-    }
-    lut
+    let report = ConvertReport {
+        input_dimensions,
+        output_dimensions: image.dimensions(),
+        stretch_bounds: stages.bounds,
+        local_stretch: if stages.local {
+            params.local_stretch
+        } else {
+            None
+        },
+        soft_clip: (stages.bounds.is_some() || stages.local) && params.soft_clip,
+        tone_curve: if stages.gamma {
+            Some(params.tone_curve)
+        } else {
+            None
+        },
+        ink_boost: if stages.gamma { params.ink_boost } else { 0.0 },
+        gamma_protect: if stages.gamma {
+            params.gamma_protect
+        } else {
+            None
+        },
+        dithered: stages.gamma && params.dither_gamma,
+        threshold: params.threshold,
+    };
+    (image, report)
 }
 
-fn clamp(i: f64) -> u8 {
-    if i > 255_f64 {
-        return 255;
-    }
-    if i > 0_f64 {
-        return i as u8;
-    }
-    0
-}
+/// Converts a sequence of frames, e.g. of an animation, according to given params.
+///
+/// Unlike converting each frame separately, contrast stretch bounds are computed once from the
+/// combined histogram of all frames, so that brightness doesn't flicker between frames.
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::{convert_frames, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let frames = vec![gray_image!(50, 100), gray_image!(100, 150)];
+/// let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+/// let frames = convert_frames(frames, &params);
+///
+/// assert_eq!(frames[0], gray_image!(85, 170));
+/// assert_eq!(frames[1], gray_image!(170, 255));
+/// ```
+pub fn convert_frames(frames: Vec<GrayImage>, params: &ConvertParams) -> Vec<GrayImage> {
+    let mut frames: Vec<GrayImage> = frames
+        .into_iter()
+        .map(|frame| resize_stage(frame, params))
+        .collect();
 
-/// Applies a lookup table to a grayscale image, i.e. for each pixel, given pixel value x, replaces
-/// said pixel with lut[x].
-fn apply_lut(image: &mut GrayImage, lut: &[u8; 256]) {
-    for p in image.pixels_mut() {
-        *p = Luma([lut[p[0] as usize]]);
+    let bounds = contrast_bounds(&combined_histogram(&frames), params);
+
+    for frame in &mut frames {
+        apply_tone(frame, params, bounds);
     }
+    frames
 }
 
-/// Calculates the width and height an image should be resized to.
-/// Preserves aspect ratio so that both dimensions are contained within the given `nx` and `ny`.
-/// If `nx` or `ny` are 0, their value will by replaced by `x` or `y` respectively, allowing for
-/// easier downscaling to desired size in one dimension.
-fn resize_dimensions(x: u32, y: u32, nx: u32, ny: u32) -> (u32, u32) {
-    let nx = if nx > 0 { nx } else { x };
-    let ny = if ny > 0 { ny } else { y };
-
-    let ratio = u64::from(x) * u64::from(ny);
-    let nratio = u64::from(nx) * u64::from(y);
-
-    let use_y = nratio <= ratio;
+/// Computes contrast stretch bounds shared by a batch of images, e.g. pages of a chapter.
+///
+/// Bounds are computed from the combined histogram of all images, ignoring `cutoff` % of the
+/// brightest and darkest pixels. Converting each image with [`convert_with_bounds()`] using the
+/// returned bounds keeps tone consistent across the batch. Returns `(0, 255)` if contrast doesn't
+/// need to be stretched.
+///
+/// The bounds are computed from the images as given, while [`convert_with_bounds()`] stretches
+/// contrast after resizing. Resampling can shift the extremes of the histogram, so pass images
+/// resized with [`resize_only()`] to get exactly the bounds [`convert()`] would see.
+///
+/// # Examples
+///
+/// ```
+/// use maco::compute_bounds_batch;
+/// use imageproc::gray_image;
+///
+/// let pages = vec![gray_image!(50, 100), gray_image!(100, 150)];
+///
+/// assert_eq!(compute_bounds_batch(&pages, 0), (0, 150));
+/// ```
+pub fn compute_bounds_batch(images: &[GrayImage], cutoff: u8) -> (u8, u8) {
+    histogram_bounds(&combined_histogram(images), cutoff).unwrap_or((0, 255))
+}
 
-    let intermediate = if use_y {
-        u64::from(y) * u64::from(nx) / u64::from(x)
+/// Converts an image according to given params, stretching contrast using `bounds` instead of
+/// bounds computed from the image itself.
+///
+/// Invalid bounds, where `upper <= lower`, skip the contrast stretch.
+///
+/// See also: [`compute_bounds_batch()`], [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::{convert_with_bounds, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let params = ConvertParams::builder().gamma(1.0).build();
+///
+/// assert_eq!(
+///     convert_with_bounds(gray_image!(50, 100), &params, (0, 100)),
+///     gray_image!(127, 255)
+/// );
+/// ```
+pub fn convert_with_bounds(
+    image: GrayImage,
+    params: &ConvertParams,
+    bounds: (u8, u8),
+) -> GrayImage {
+    let mut image = resize_stage(image, params);
+    let (lower, upper) = bounds;
+    let bounds = if upper > lower && bounds != (0, 255) {
+        Some(bounds)
     } else {
-        u64::from(x) * u64::from(ny) / u64::from(y)
+        None
     };
-    let intermediate = std::cmp::max(1, intermediate);
-
-    if use_y {
-        (nx, intermediate as u32)
-    } else {
-        (intermediate as u32, ny)
-    }
+    apply_tone(&mut image, params, bounds);
+    image
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    macro_rules! dimensions_tests {
-        ($($name:ident: $value:expr,)*) => {
-        $(
-            #[test]
-            fn $name() {
+/// Converts an image according to given params, computing contrast stretch bounds only from pixels
+/// selected by `mask`.
+///
+/// Pixels where `mask` is non-zero participate in the percentile computation, e.g. to ignore a
+/// scanner bed around the page. The stretch is still applied to the whole image. A mask of
+/// different dimensions than the image is resized to match. If the mask selects no pixels, the
+/// contrast stretch is skipped.
+///
+/// See also: [`convert()`], [`convert_with_bounds()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::{convert_with_mask, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let params = ConvertParams::builder().cutoff(1).gamma(1.0).build();
+/// let image = gray_image!(0, 100, 150, 200);
+/// let mask = gray_image!(0, 255, 255, 255);
+///
+/// assert_eq!(convert_with_mask(image, &mask, &params), gray_image!(0, 0, 127, 255));
+/// ```
+pub fn convert_with_mask(image: GrayImage, mask: &GrayImage, params: &ConvertParams) -> GrayImage {
+    let mut image = resize_stage(image, params);
+    let (width, height) = image.dimensions();
+    let resized;
+    let mask = if mask.dimensions() == (width, height) {
+        mask
+    } else {
+        resized = resize(mask, width, height, FilterType::Nearest);
+        &resized
+    };
+
+    let mut hist = [0_u64; 256];
+    for (p, m) in image.pixels().zip(mask.pixels()) {
+        if m[0] != 0 {
+            hist[p[0] as usize] += 1;
+        }
+    }
+    let bounds = if hist.iter().any(|&count| count > 0) {
+        contrast_bounds(&hist, params)
+    } else {
+        debug!("skipping contrast stretch, mask is empty");
+        None
+    };
+    apply_tone(&mut image, params, bounds);
+    image
+}
+
+/// Converts a region of an image according to given params.
+///
+/// The region is cropped from the image and converted as if it was a standalone image. Note that
+/// contrast stretch bounds computed from a small region can differ a lot from those of the whole
+/// image. To make the region match the rest of the image, pass `bounds` precomputed from the whole
+/// image, e.g. using [`compute_bounds_batch()`]. Regions extending beyond the image are clipped to
+/// its edges.
+///
+/// Returns an empty image if the clipped region is empty, e.g. if it doesn't overlap the image.
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::{convert_region, ConvertParams, Rect};
+/// use imageproc::gray_image;
+///
+/// let img = gray_image!(
+///     10, 20, 30;
+///     40, 50, 60);
+/// let region = Rect::new(1, 0, 2, 2);
+/// let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+///
+/// assert_eq!(
+///     convert_region(&img, region, &params, Some((10, 60))),
+///     gray_image!(
+///         51,  102;
+///         204, 255)
+/// );
+/// ```
+pub fn convert_region(
+    image: &GrayImage,
+    region: Rect,
+    params: &ConvertParams,
+    bounds: Option<(u8, u8)>,
+) -> GrayImage {
+    let region = region.clamp_to(image.width(), image.height());
+    if region.is_empty() {
+        debug!("skipping conversion, region {:?} is empty", region);
+        return GrayImage::new(0, 0);
+    }
+    let image = imageops::crop_imm(image, region.x, region.y, region.width, region.height);
+    let mut image = resize_stage(image.to_image(), params);
+    match bounds {
+        Some((lower, upper)) if upper > lower => {
+            apply_tone(&mut image, params, Some((lower, upper)))
+        }
+        Some(_) => apply_tone(&mut image, params, None),
+        None => adjust_tone(&mut image, params),
+    };
+    image
+}
+
+/// Converts an image according to given params, additionally returning a difference image.
+///
+/// Each pixel of the difference image is the absolute difference between the converted image and
+/// the source resized to the same dimensions, showing exactly what the contrast and gamma stages
+/// changed.
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::{convert_and_diff, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+/// let (_, diff) = convert_and_diff(gray_image!(50, 100), &params);
+///
+/// assert_eq!(diff, gray_image!(77, 155));
+/// ```
+pub fn convert_and_diff(image: GrayImage, params: &ConvertParams) -> (GrayImage, GrayImage) {
+    let source = resize_stage(image, params);
+    let mut image = source.clone();
+    adjust_tone(&mut image, params);
+
+    let mut diff = source;
+    for (d, p) in diff.pixels_mut().zip(image.pixels()) {
+        d[0] = d[0].abs_diff(p[0]);
+    }
+    (image, diff)
+}
+
+/// Converts an image according to given params, then colors it with a duotone ramp.
+///
+/// Black stays black and white stays white, while mid gray maps to `tint`, with tones in between
+/// interpolated linearly. This is meant for stylistic covers, e.g. a sepia look.
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::Rgb;
+/// use maco::{convert_tinted, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+/// let image = convert_tinted(gray_image!(0, 255), &params, Rgb([112, 66, 20]));
+///
+/// assert_eq!(image.get_pixel(0, 0), &Rgb([0, 0, 0]));
+/// assert_eq!(image.get_pixel(1, 0), &Rgb([255, 255, 255]));
+/// ```
+pub fn convert_tinted(image: GrayImage, params: &ConvertParams, tint: Rgb<u8>) -> RgbImage {
+    let image = convert(image, params);
+    let ramp = tint_ramp(tint);
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        ramp[usize::from(image.get_pixel(x, y)[0])]
+    })
+}
+
+/// Creates a lookup table mapping luminance to a ramp from black through `tint` to white.
+fn tint_ramp(tint: Rgb<u8>) -> [Rgb<u8>; 256] {
+    let mut ramp = [Rgb([0, 0, 0]); 256];
+    for (l, color) in ramp.iter_mut().enumerate() {
+        let l = l as f32 / 255.0;
+        *color = Rgb(tint.0.map(|c| {
+            let c = f32::from(c);
+            let value = if l <= 0.5 {
+                c * l * 2.0
+            } else {
+                c + (255.0 - c) * (l - 0.5) * 2.0
+            };
+            value.round() as u8
+        }));
+    }
+    ramp
+}
+
+/// Converts `src` according to given params, writing the result into `dst`.
+///
+/// Contents of `dst` are overwritten. Its buffer is reused when its dimensions match the output
+/// and reallocated otherwise, which makes this function suitable for converting many images in a
+/// loop. Note that resampling still allocates temporary buffers, as does resizing an image which
+/// was modified first, e.g. by [`ConvertParamsBuilder::auto_invert()`].
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert_into, ConvertParams};
+///
+/// let params = ConvertParams::default();
+/// let mut dst = GrayImage::new(0, 0);
+/// for src in vec![GrayImage::new(3, 2), GrayImage::new(2, 3)] {
+///     convert_into(&src, &mut dst, &params);
+///     assert_eq!(dst.dimensions(), src.dimensions());
+/// }
+/// ```
+pub fn convert_into(src: &GrayImage, dst: &mut GrayImage, params: &ConvertParams) {
+    if prepare_is_noop(params) {
+        resize_prepared_into(Cow::Borrowed(src), dst, params);
+    } else {
+        // Prepare a copy in the buffer of dst, which only has to be replaced if it's resized.
+        copy_into(src, dst);
+        let prepared = prepare_stage(std::mem::take(dst), params);
+        resize_prepared_into(Cow::Owned(prepared), dst, params);
+    }
+    adjust_tone(dst, params);
+}
+
+/// Returns the approximate peak number of bytes [`convert()`] needs for an image of given size.
+///
+/// This counts the source buffer, the resized buffer and temporary buffers of the stages enabled
+/// in `params`, letting batch drivers pick a concurrency that fits in memory. When the image
+/// isn't resized, no second buffer is counted.
+///
+/// # Examples
+///
+/// ```
+/// use maco::{estimate_memory, ConvertParams};
+///
+/// let params = ConvertParams::default();
+/// assert_eq!(estimate_memory(800, 600, &params), 800 * 600);
+/// ```
+pub fn estimate_memory(width: u32, height: u32, params: &ConvertParams) -> usize {
+    let pixels = |w: u32, h: u32| w as usize * h as usize;
+    let source = pixels(width, height);
+    let mut resize_peak = source;
+    if params.seam_carve {
+        // The carved copy is at most as large as the source.
+        resize_peak += source;
+    }
+    let (out_width, out_height) = match target_dimensions(width, height, params) {
+        Some((w, h)) => {
+            let output = pixels(w, h);
+            let temps = if w > width && params.upscale_method == UpscaleMethod::EdgeDirected {
+                output
+            } else if params.custom_filter.is_some() {
+                // f32 copies of the source and the horizontally resampled image.
+                4 * source + 4 * pixels(w, height)
+            } else if params.linear_resize {
+                // u16 copies of the source, the intermediate and the output.
+                2 * source + 2 * pixels(width, h) + 2 * output
+            } else {
+                pixels(width, h)
+            };
+            let temps = if params.two_pass && width > w.saturating_mul(TWO_PASS_FACTOR) {
+                temps + source / 4
+            } else {
+                temps
+            };
+            let temps = if params.resize_edge == EdgeMode::Truncate {
+                temps
+            } else {
+                // The padded copy of the source.
+                let border = edge_border(width, height, w, h, params);
+                temps + pixels(width + 2 * border, height + 2 * border)
+            };
+            resize_peak += temps + output;
+            (w, h)
+        }
+        None => (width, height),
+    };
+
+    let output = pixels(out_width, out_height);
+    let mut tone_temps = 0;
+    if params.sharpen > 0.0 {
+        // The f32 blur intermediate and the blurred image.
+        tone_temps = tone_temps.max(5 * output);
+    }
+    if params.despeckle > 1 {
+        // Visited flags, one per pixel.
+        tone_temps = tone_temps.max(output);
+    }
+    if params.adaptive_gamma > 0.0 && params.threshold.is_none() {
+        tone_temps = tone_temps.max(8 * pixels(out_width + 1, out_height + 1));
+    }
+    resize_peak.max(output + tone_temps)
+}
+
+/// Millimeters in an inch, for [`ConvertParamsBuilder::target_physical()`].
+const MM_PER_INCH: f64 = 25.4;
+
+/// Returns the dimensions an image of given size should be resized to, or `None` if it should be
+/// left as is.
+fn target_dimensions(width: u32, height: u32, params: &ConvertParams) -> Option<(u32, u32)> {
+    let fitted = fitted_dimensions(width, height, params);
+    if !params.power_of_two {
+        return fitted;
+    }
+    let (fitted_width, fitted_height) = fitted.unwrap_or((width, height));
+    let rounded = (
+        previous_power_of_two(fitted_width),
+        previous_power_of_two(fitted_height),
+    );
+    if rounded == (width, height) {
+        None
+    } else {
+        debug!(
+            "rounding {}x{} down to {}x{}",
+            fitted_width, fitted_height, rounded.0, rounded.1
+        );
+        Some(rounded)
+    }
+}
+
+/// Returns the largest power of two not greater than `n`, or 1 if `n` is 0.
+fn previous_power_of_two(n: u32) -> u32 {
+    1 << (31 - n.max(1).leading_zeros())
+}
+
+/// Does the work for [`target_dimensions()`], fitting the image into the configured dimensions.
+fn fitted_dimensions(width: u32, height: u32, params: &ConvertParams) -> Option<(u32, u32)> {
+    let (nwidth, nheight) = resize_dimensions(width, height, params.width, params.height);
+    // If width didn't change, height didn't change either.
+    // If width increased but we don't want to upscale, we can skip.
+    if nwidth == width {
+        debug!("skipping resize, {}x{} already fits", width, height);
+        None
+    } else if nwidth > width && !params.upscale {
+        debug!(
+            "skipping resize, upscaling {}x{} is disabled",
+            width, height
+        );
+        None
+    } else {
+        debug!("resizing {}x{} to {}x{}", width, height, nwidth, nheight);
+        Some((nwidth, nheight))
+    }
+}
+
+/// Applies the resize stage of [`convert()`].
+fn resize_stage(image: GrayImage, params: &ConvertParams) -> GrayImage {
+    resize_prepared(prepare_stage(image, params), params)
+}
+
+/// Resizes a color image to the dimensions [`convert()`] would produce, without adjusting tones.
+///
+/// Each channel is resized the same way as grayscale images, so
+/// [`ConvertParamsBuilder::ringing_fallback()`] is decided per channel.
+#[cfg(feature = "cbz")]
+pub(crate) fn resize_color(image: RgbImage, params: &ConvertParams) -> RgbImage {
+    let (width, height) = match target_dimensions(image.width(), image.height(), params) {
+        Some(dimensions) => dimensions,
+        None => return image,
+    };
+    let channels: Vec<GrayImage> = (0..3)
+        .map(|c| {
+            let channel = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                Luma([image[(x, y)][c]])
+            });
+            let mut resized = GrayImage::new(width, height);
+            resize_image(&channel, &mut resized, params);
+            resized
+        })
+        .collect();
+    RgbImage::from_fn(width, height, |x, y| {
+        Rgb([
+            channels[0][(x, y)][0],
+            channels[1][(x, y)][0],
+            channels[2][(x, y)][0],
+        ])
+    })
+}
+
+/// Applies the stages of [`convert()`] which run on the source image, before resizing.
+fn prepare_stage(mut image: GrayImage, params: &ConvertParams) -> GrayImage {
+    invert_stage(&mut image, params);
+    scan_line_stage(&mut image, params);
+    carve_stage(&image, params).unwrap_or(image)
+}
+
+/// Returns whether [`prepare_stage()`] leaves every image unchanged with the given params.
+fn prepare_is_noop(params: &ConvertParams) -> bool {
+    !params.auto_invert && !params.remove_scan_lines && !params.seam_carve
+}
+
+/// Applies the rest of the resize stage to an image returned by [`prepare_stage()`].
+fn resize_prepared(image: GrayImage, params: &ConvertParams) -> GrayImage {
+    let mut out = GrayImage::default();
+    resize_prepared_into(Cow::Owned(image), &mut out, params);
+    out
+}
+
+/// Like [`resize_prepared()`], but writes the result into `dst`, reusing its buffer if its
+/// dimensions match. An image which isn't resized replaces `dst` if owned and is copied otherwise.
+fn resize_prepared_into(image: Cow<'_, GrayImage>, dst: &mut GrayImage, params: &ConvertParams) {
+    let downscaled = match target_dimensions(image.width(), image.height(), params) {
+        Some((width, height)) => {
+            if dst.dimensions() != (width, height) {
+                *dst = GrayImage::new(width, height);
+            }
+            resize_image(&image, dst, params);
+            width < image.width()
+        }
+        None => {
+            match image {
+                Cow::Owned(image) => *dst = image,
+                Cow::Borrowed(image) => copy_into(image, dst),
+            }
+            false
+        }
+    };
+    sharpen_stage(dst, params, downscaled);
+    speckle::despeckle(dst, params.despeckle);
+}
+
+/// Copies `src` into `dst`, reusing the buffer of `dst` if the dimensions match.
+fn copy_into(src: &GrayImage, dst: &mut GrayImage) {
+    if dst.dimensions() == src.dimensions() {
+        dst.copy_from_slice(src);
+    } else {
+        *dst = src.clone();
+    }
+}
+
+/// Inverts negative scans, if configured.
+fn invert_stage(image: &mut GrayImage, params: &ConvertParams) {
+    if params.auto_invert && analysis::is_negative(image) {
+        debug!("inverting negative scan");
+        imageops::invert(image);
+    }
+}
+
+/// Removes stuck scanner lines, if configured.
+fn scan_line_stage(image: &mut GrayImage, params: &ConvertParams) {
+    if params.remove_scan_lines {
+        let removed = scan_lines::remove_scan_lines(image);
+        debug!("removed {} scan lines", removed);
+    }
+}
+
+/// Standard deviation of the blur subtracted by [`ConvertParamsBuilder::sharpen()`].
+const SHARPEN_SIGMA: f32 = 1.0;
+
+/// Sharpens a resized image with an unsharp mask, if configured.
+fn sharpen_stage(image: &mut GrayImage, params: &ConvertParams, downscaled: bool) {
+    if params.sharpen <= 0.0 || image.width() == 0 || image.height() == 0 {
+        return;
+    }
+    if params.sharpen_downscale_only && !downscaled {
+        debug!("skipping sharpening, image wasn't downscaled");
+        return;
+    }
+    let blurred = imageproc::filter::gaussian_blur_f32(image, SHARPEN_SIGMA);
+    for (p, b) in image.pixels_mut().zip(blurred.pixels()) {
+        let (value, blur) = (f32::from(p[0]), f32::from(b[0]));
+        p[0] = (value + params.sharpen * (value - blur))
+            .round()
+            .clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Largest share of the width seam carving may remove.
+const MAX_SEAM_SHARE: f64 = 0.25;
+
+/// Narrows an image to the aspect ratio of the target dimensions using seam carving, if enabled.
+/// Returns `None` if the image should be left as is.
+fn carve_stage(image: &GrayImage, params: &ConvertParams) -> Option<GrayImage> {
+    if !params.seam_carve {
+        return None;
+    }
+    let (width, height) = image.dimensions();
+    let target_width = params.width.limit(width)?;
+    let target_height = params.height.limit(height)?;
+    // Width at which the image would have the aspect ratio of the target.
+    let fitting = (u64::from(height) * u64::from(target_width)).div_ceil(u64::from(target_height));
+    let min_width = (f64::from(width) * (1_f64 - MAX_SEAM_SHARE)).ceil() as u64;
+    let carved = fitting.max(min_width).max(2) as u32;
+    if carved >= width {
+        return None;
+    }
+    debug!("seam carving {}x{} to {}x{}", width, height, carved, height);
+    Some(seam_carving::shrink_width(image, carved))
+}
+
+/// Reduction factor beyond which [`ConvertParamsBuilder::two_pass()`] takes effect.
+const TWO_PASS_FACTOR: u32 = 3;
+
+/// Resizes an image into `out`, whose dimensions are the target size, using the method configured
+/// in `params`.
+fn resize_image(image: &GrayImage, out: &mut GrayImage, params: &ConvertParams) {
+    let width = out.width();
+    if params.two_pass && image.width() > width.saturating_mul(TWO_PASS_FACTOR) {
+        // Keep the intermediate at least twice the final size for the filter to work with.
+        let factor = image.width() / (width * 2);
+        if factor >= 2 {
+            debug!("downscaling by {} before resizing", factor);
+            let intermediate = if params.linear_resize {
+                resample::box_downscale_linear(image, factor)
+            } else {
+                resample::box_downscale(image, factor)
+            };
+            return resize_single(&intermediate, out, params);
+        }
+    }
+    resize_single(image, out, params)
+}
+
+/// Does the work for [`resize_image()`] in a single pass, padding the edges of the image first if
+/// configured.
+fn resize_single(image: &GrayImage, out: &mut GrayImage, params: &ConvertParams) {
+    if params.resize_edge == EdgeMode::Truncate {
+        return resize_kernel(image, out, params);
+    }
+    let (width, height) = out.dimensions();
+    let border = edge_border(image.width(), image.height(), width, height, params);
+    let padded = resample::pad_edges(image, border, params.resize_edge);
+    let scale_x = f64::from(width) / f64::from(image.width());
+    let scale_y = f64::from(height) / f64::from(image.height());
+    let padded_width = (f64::from(padded.width()) * scale_x).round() as u32;
+    let padded_height = (f64::from(padded.height()) * scale_y).round() as u32;
+    let mut resized = GrayImage::new(padded_width, padded_height);
+    resize_kernel(&padded, &mut resized, params);
+    let left = ((f64::from(border) * scale_x).round() as u32).min(padded_width - width);
+    let top = ((f64::from(border) * scale_y).round() as u32).min(padded_height - height);
+    for (x, y, p) in out.enumerate_pixels_mut() {
+        *p = *resized.get_pixel(left + x, top + y);
+    }
+}
+
+/// Returns the number of source pixels to pad each edge with so that the resampling kernel never
+/// reaches past the padding when resizing from `src_width` x `src_height` to `width` x `height`.
+fn edge_border(
+    src_width: u32,
+    src_height: u32,
+    width: u32,
+    height: u32,
+    params: &ConvertParams,
+) -> u32 {
+    let support = if let Some(filter) = params.custom_filter {
+        filter.support()
+    } else {
+        match params.filter {
+            FilterType::Nearest | FilterType::Triangle => 1.0,
+            FilterType::CatmullRom => 2.0,
+            FilterType::Gaussian | FilterType::Lanczos3 => 3.0,
+        }
+    };
+    let ratio = (f64::from(src_width) / f64::from(width))
+        .max(f64::from(src_height) / f64::from(height))
+        .max(1.0);
+    (f64::from(support) * ratio).ceil() as u32 + 1
+}
+
+/// Resizes into `out` without any padding, dispatching to the configured resampling method.
+fn resize_kernel(image: &GrayImage, out: &mut GrayImage, params: &ConvertParams) {
+    if out.width() > image.width() && params.upscale_method == UpscaleMethod::EdgeDirected {
+        resample::edge_directed(image, out)
+    } else if let Some(filter) = params.custom_filter {
+        resample::resize_custom(image, out, filter)
+    } else if params.linear_resize {
+        resample::resize_linear(image, out, resize_filter(image, params))
+    } else {
+        resample::resize_fixed(image, out, resize_filter(image, params))
+    }
+}
+
+/// Smallest difference between neighboring pixels considered a hard edge by
+/// [`ConvertParamsBuilder::ringing_fallback()`].
+const HARD_EDGE_CONTRAST: u8 = 128;
+
+/// Share of pixels on hard edges above which [`ConvertParamsBuilder::ringing_fallback()`] takes
+/// effect.
+const HARD_EDGE_SHARE: f64 = 0.001;
+
+/// Returns the filter to resize `image` with, falling back from Lanczos to CatmullRom if enabled
+/// and the image has hard edges.
+fn resize_filter(image: &GrayImage, params: &ConvertParams) -> FilterType {
+    if !params.ringing_fallback || params.filter != FilterType::Lanczos3 {
+        return params.filter;
+    }
+    let (width, height) = image.dimensions();
+    let hard = |a: u8, b: u8| a.abs_diff(b) >= HARD_EDGE_CONTRAST;
+    let edges = image
+        .enumerate_pixels()
+        .filter(|&(x, y, p)| {
+            (x + 1 < width && hard(p[0], image[(x + 1, y)][0]))
+                || (y + 1 < height && hard(p[0], image[(x, y + 1)][0]))
+        })
+        .count();
+    if edges as f64 > HARD_EDGE_SHARE * f64::from(width) * f64::from(height) {
+        debug!("resizing with CatmullRom, image has hard edges");
+        FilterType::CatmullRom
+    } else {
+        params.filter
+    }
+}
+
+/// Marks which pixels of an image are clipped by the contrast stretch of [`convert()`].
+///
+/// Pixels mapped to black are set to 0, pixels mapped to white to 255 and all others to 128. If
+/// the stretch is skipped for the given image, every pixel is set to 128. The image is analyzed at
+/// its current size, so resize it first to match what [`convert()`] sees.
+///
+/// Useful for checking whether a given cutoff destroys detail in the shadows or highlights.
+///
+/// # Examples
+///
+/// ```
+/// use maco::{clip_map, ConvertParams};
+/// use imageproc::gray_image;
+///
+/// let img = gray_image!(
+///     10, 50, 50, 50, 90);
+///
+/// let params = ConvertParams::builder().cutoff(10).build();
+///
+/// assert_eq!(clip_map(&img, &params), gray_image!(0, 128, 128, 128, 255));
+/// ```
+pub fn clip_map(image: &GrayImage, params: &ConvertParams) -> GrayImage {
+    let mut map = GrayImage::from_pixel(image.width(), image.height(), Luma([128]));
+    if let Some((lower, upper)) = stretch_bounds(image, params) {
+        for (p, m) in image.pixels().zip(map.pixels_mut()) {
+            if p[0] <= lower {
+                *m = Luma([0]);
+            } else if p[0] >= upper {
+                *m = Luma([255]);
+            }
+        }
+    }
+    map
+}
+
+/// Darkest value a converted image's stretch bounds may start at, and its distance from white.
+const CONVERTED_MARGIN: u8 = 8;
+
+/// Fraction of pixels that must be possible outputs of the gamma stage for an image to be
+/// considered converted.
+const CONVERTED_GAMMA_SHARE: f64 = 0.99;
+
+/// Guesses whether an image was already converted using the given params.
+///
+/// Converting an image twice stretches contrast and applies gamma twice, which degrades it. Batch
+/// tools can use this to skip pages that were already processed. The image is considered
+/// converted if it fits the target dimensions, its contrast already spans from near black to near
+/// white, and nearly all of its values are ones the gamma stage can produce. The last check is
+/// skipped for identity tone curves and dithered gamma, which can produce any value.
+///
+/// This is a best-effort heuristic. It may flag naturally high-contrast images which weren't
+/// converted, or miss converted images which were edited afterwards.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::{convert, looks_converted, ConvertParams};
+///
+/// let params = ConvertParams::default();
+/// let scan = GrayImage::from_fn(64, 64, |x, y| Luma([(60 + x + y) as u8]));
+///
+/// assert!(!looks_converted(&scan, &params));
+/// assert!(looks_converted(&convert(scan, &params), &params));
+/// ```
+pub fn looks_converted(image: &GrayImage, params: &ConvertParams) -> bool {
+    if target_dimensions(image.width(), image.height(), params).is_some() {
+        return false;
+    }
+    let hist = histogram(image);
+    let lower = histogram_percentile(&hist, params.cutoff);
+    let upper = histogram_percentile(&hist, 100_u8.saturating_sub(params.cutoff));
+    if lower > CONVERTED_MARGIN || upper < 255 - CONVERTED_MARGIN {
+        return false;
+    }
+    if gamma_is_identity(params) || params.dither_gamma {
+        return true;
+    }
+
+    let mut outputs = [false; 256];
+    for &v in params.gamma_lut.iter() {
+        outputs[v as usize] = true;
+    }
+    let total: u64 = hist.iter().sum();
+    let matching: u64 = hist
+        .iter()
+        .zip(outputs.iter())
+        .filter(|(_, &output)| output)
+        .map(|(count, _)| count)
+        .sum();
+    matching as f64 >= total as f64 * CONVERTED_GAMMA_SHARE
+}
+
+/// Plots the tone curve applied by the gamma stage of [`convert()`], including the effects of
+/// [`ConvertParamsBuilder::gamma_protect()`] and [`ConvertParamsBuilder::ink_boost()`].
+///
+/// Returns a 256x256 white image with the curve drawn in black, input values along the x axis and
+/// output values along the y axis, with black at the bottom. Consecutive points are connected, so
+/// steep sections of the curve show up as vertical lines.
+///
+/// # Examples
+///
+/// ```
+/// use maco::{export_lut_image, ConvertParams};
+///
+/// let plot = export_lut_image(&ConvertParams::builder().gamma(1.0).build());
+///
+/// assert_eq!(plot.get_pixel(0, 255)[0], 0);
+/// assert_eq!(plot.get_pixel(255, 0)[0], 0);
+/// ```
+pub fn export_lut_image(params: &ConvertParams) -> GrayImage {
+    let mut plot = GrayImage::from_pixel(256, 256, Luma([255]));
+    let mut prev = params.gamma_lut[0];
+    for (x, &value) in params.gamma_lut.iter().enumerate() {
+        let from = if value > prev { prev + 1 } else { value };
+        for y in from..=value {
+            plot.put_pixel(x as u32, 255 - u32::from(y), Luma([0]));
+        }
+        prev = value;
+    }
+    plot
+}
+
+/// Returns the bounds used to stretch the contrast of an image, or `None` if the stretch should
+/// be skipped.
+fn stretch_bounds(image: &GrayImage, params: &ConvertParams) -> Option<(u8, u8)> {
+    contrast_bounds(&histogram(image), params)
+}
+
+/// Like [`histogram_bounds()`], but falls back to the darkest and brightest values of the
+/// histogram if the bounds are closer than [`ConvertParamsBuilder::min_contrast()`].
+fn contrast_bounds(hist: &[u64; 256], params: &ConvertParams) -> Option<(u8, u8)> {
+    let (lower, upper) = match params.contrast_bounds {
+        ContrastBounds::Percentile => percentile_bounds(hist, params.cutoff),
+        ContrastBounds::MeanStd { k } => mean_std_bounds(hist, k),
+    };
+    if upper.saturating_sub(lower) < params.min_contrast {
+        let darkest = hist.iter().position(|&count| count > 0);
+        let brightest = hist.iter().rposition(|&count| count > 0);
+        if let (Some(darkest), Some(brightest)) = (darkest, brightest) {
+            if brightest > darkest && !(darkest == 0 && brightest == 255) {
+                debug!(
+                    "forcing contrast stretch from {}..={}, bounds {}..={} are too close",
+                    darkest, brightest, lower, upper
+                );
+                return Some((darkest as u8, brightest as u8));
+            }
+        }
+    }
+    checked_bounds(lower, upper)
+}
+
+/// Returns the mean of a histogram minus and plus `k` standard deviations, clamped to `0..=255`.
+fn mean_std_bounds(hist: &[u64; 256], k: f32) -> (u8, u8) {
+    let total: u64 = hist.iter().sum();
+    if total == 0 {
+        return (0, 0);
+    }
+    let mean = hist
+        .iter()
+        .enumerate()
+        .map(|(v, &count)| v as f64 * count as f64)
+        .sum::<f64>()
+        / total as f64;
+    let variance = hist
+        .iter()
+        .enumerate()
+        .map(|(v, &count)| (v as f64 - mean).powi(2) * count as f64)
+        .sum::<f64>()
+        / total as f64;
+    let spread = f64::from(k) * variance.sqrt();
+    trace!(
+        "mean {:.1}, standard deviation {:.1}",
+        mean,
+        variance.sqrt()
+    );
+    let bound = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+    (bound(mean - spread), bound(mean + spread))
+}
+
+/// Counts occurrences of each value in a grayscale image.
+pub(crate) fn histogram(image: &GrayImage) -> [u64; 256] {
+    let mut hist = [0; 256];
+    for p in image.iter() {
+        hist[*p as usize] += 1;
+    }
+    hist
+}
+
+/// Returns the sum of histograms of all images.
+fn combined_histogram(images: &[GrayImage]) -> [u64; 256] {
+    let mut hist = [0; 256];
+    for image in images {
+        for (total, count) in hist.iter_mut().zip(histogram(image).iter()) {
+            *total += count;
+        }
+    }
+    hist
+}
+
+/// Like [`stretch_bounds()`], but operates on a histogram.
+fn histogram_bounds(hist: &[u64; 256], cutoff: u8) -> Option<(u8, u8)> {
+    let (lower, upper) = percentile_bounds(hist, cutoff);
+    checked_bounds(lower, upper)
+}
+
+/// Returns the `cutoff` and `100 - cutoff` percentiles of a histogram.
+fn percentile_bounds(hist: &[u64; 256], cutoff: u8) -> (u8, u8) {
+    let lower = histogram_percentile(hist, cutoff);
+    let upper = histogram_percentile(hist, 100_u8 - cutoff);
+    trace!(
+        "percentiles {} and {}: {} and {}",
+        cutoff,
+        100 - cutoff,
+        lower,
+        upper
+    );
+    (lower, upper)
+}
+
+/// Returns the given contrast stretch bounds, or `None` if stretching between them wouldn't
+/// change the image.
+fn checked_bounds(lower: u8, upper: u8) -> Option<(u8, u8)> {
+    // If lower is 0 and upper 255, the histogram won't change, making computation redundant.
+    if upper > lower && !(lower == 0 && upper == 255) {
+        debug!("stretching contrast from {}..={}", lower, upper);
+        Some((lower, upper))
+    } else if upper <= lower {
+        debug!("skipping contrast stretch, image is flat at {}", lower);
+        None
+    } else {
+        debug!("skipping contrast stretch, image already spans 0..=255");
+        None
+    }
+}
+
+/// Returns the least value such that at least `p`% of counted values are less than or equal to it.
+/// Matches [`imageproc::stats::percentile()`], but works on a precomputed histogram.
+fn histogram_percentile(hist: &[u64; 256], p: u8) -> u8 {
+    let total: u64 = hist.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+    let mut cumulative = 0;
+    for (i, count) in hist.iter().enumerate() {
+        cumulative += count;
+        if 100 * cumulative / total >= u64::from(p) {
+            return i as u8;
+        }
+    }
+    255
+}
+
+/// Share of the output range over which [`stretch_lut()`] rolls off when soft clipping.
+const SOFT_CLIP_KNEE: f64 = 0.1;
+
+/// Generates a lookup table linearly mapping `lower..=upper` onto `0..=255`.
+///
+/// Without `soft_clip`, values outside of the bounds are clipped, matching
+/// [`imageproc::contrast::stretch_contrast_mut()`] when truncating. With it, the mapping smoothly flattens out
+/// within [`SOFT_CLIP_KNEE`] of either end of the output range, approaching but never reaching
+/// black and white.
+fn stretch_lut(lower: u8, upper: u8, soft_clip: bool, rounding: Rounding) -> [u8; 256] {
+    let mut lut = [0; 256];
+    let len = u16::from(upper - lower);
+    for (i, x) in lut.iter_mut().enumerate() {
+        let i = i as u8;
+        *x = if soft_clip {
+            let t = f64::from(i as i16 - lower as i16) / f64::from(len);
+            clamp(soft_clip_value(t) * 255_f64)
+        } else if i >= upper {
+            255
+        } else if i <= lower {
+            0
+        } else {
+            let scaled = 255 * u16::from(i - lower);
+            match rounding {
+                Rounding::Truncate => (scaled / len) as u8,
+                Rounding::Nearest => ((scaled + len / 2) / len) as u8,
+            }
+        };
+    }
+    lut
+}
+
+/// Returns a lookup table which remaps an image with histogram `hist` so its histogram matches
+/// `target`, or `None` if either histogram is empty.
+///
+/// Each value is mapped to the smallest target value whose cumulative share reaches the share of
+/// pixels up to and halfway through the value in the source.
+fn specify_lut(hist: &[u64; 256], target: &[u32; 256]) -> Option<[u8; 256]> {
+    let source = cumulative(hist.iter().copied());
+    let target = cumulative(target.iter().map(|&count| u64::from(count)));
+    if source[255] == 0.0 || target[255] == 0.0 {
+        return None;
+    }
+    let mut lut = [0; 256];
+    let mut below = 0.0;
+    for (x, &share) in lut.iter_mut().zip(&source) {
+        let middle = (below + share) / 2.0;
+        *x = target.iter().position(|&t| t >= middle).unwrap_or(255) as u8;
+        below = share;
+    }
+    Some(lut)
+}
+
+/// Bends a lookup table with a power curve so that `value` maps onto itself. Black and white are
+/// kept in place. The table is left unchanged if either end of the mapping is black or white.
+fn preserve_value(lut: &mut [u8; 256], value: u8) {
+    let mapped = lut[value as usize];
+    if value == 0 || value == 255 || mapped == 0 || mapped == 255 {
+        return;
+    }
+    let exponent = (f64::from(value) / 255_f64).ln() / (f64::from(mapped) / 255_f64).ln();
+    debug!("mapping median {} back from {}", value, mapped);
+    for x in lut.iter_mut() {
+        *x = clamp((f64::from(*x) / 255_f64).powf(exponent) * 255_f64);
+    }
+}
+
+/// Maps `t` onto `0..=1` linearly, rolling off exponentially near the ends of the range. The curve
+/// is continuous, has the same slope on both sides of each knee and is strictly increasing.
+fn soft_clip_value(t: f64) -> f64 {
+    let k = SOFT_CLIP_KNEE;
+    if t > 1_f64 - k {
+        1_f64 - k * (-(t - (1_f64 - k)) / k).exp()
+    } else if t < k {
+        k * (-(k - t) / k).exp()
+    } else {
+        t
+    }
+}
+
+/// Records which tone stages were applied to an image.
+struct ToneStages {
+    /// Contrast stretch bounds, if the stretch was applied.
+    bounds: Option<(u8, u8)>,
+    /// Whether the gamma modifier was applied.
+    gamma: bool,
+    /// Whether contrast was stretched per tile.
+    local: bool,
+}
+
+/// Applies the contrast stretch and gamma stages of [`convert()`] in place.
+fn adjust_tone(image: &mut GrayImage, params: &ConvertParams) -> ToneStages {
+    adjust_tone_with(image, params, &mut |_| {})
+}
+
+/// Like [`adjust_tone()`], additionally reporting the start and end of each stage to `on_stage`.
+fn adjust_tone_with(
+    image: &mut GrayImage,
+    params: &ConvertParams,
+    on_stage: &mut dyn FnMut(Stage),
+) -> ToneStages {
+    on_stage(Stage::Started(Step::Contrast));
+    if let Some(tiles) = params.local_stretch {
+        local::local_stretch(image, tiles, |hist| match contrast_bounds(hist, params) {
+            Some((lower, upper)) => {
+                stretch_lut(lower, upper, params.soft_clip, params.stretch_rounding)
+            }
+            None => identity_lut(),
+        });
+        let stages = apply_tone_with(image, params, None, on_stage);
+        return ToneStages {
+            local: true,
+            ..stages
+        };
+    }
+    if let ContrastMethod::Specify(target) = &params.contrast_method {
+        if let Some(lut) = specify_lut(&histogram(image), target) {
+            apply_lut(image, &lut);
+        }
+        return apply_tone_with(image, params, None, on_stage);
+    }
+    let bounds = stretch_bounds(image, params);
+    apply_tone_with(image, params, bounds, on_stage)
+}
+
+/// Like [`adjust_tone()`], but stretches contrast using the given bounds.
+fn apply_tone(
+    image: &mut GrayImage,
+    params: &ConvertParams,
+    bounds: Option<(u8, u8)>,
+) -> ToneStages {
+    apply_tone_with(image, params, bounds, &mut |_| {})
+}
+
+/// Like [`apply_tone()`], additionally reporting the end of the contrast stage and the start and
+/// end of the gamma stage to `on_stage`.
+fn apply_tone_with(
+    image: &mut GrayImage,
+    params: &ConvertParams,
+    bounds: Option<(u8, u8)>,
+    on_stage: &mut dyn FnMut(Stage),
+) -> ToneStages {
+    if let Some((lower, upper)) = bounds {
+        let mut lut = stretch_lut(lower, upper, params.soft_clip, params.stretch_rounding);
+        if params.preserve_median {
+            let median = histogram_percentile(&histogram(image), 50);
+            preserve_value(&mut lut, median);
+        }
+        apply_lut(image, &lut);
+    }
+
+    if let Some(threshold) = params.threshold {
+        apply_lut(image, &threshold_lut(threshold, params.threshold_antialias));
+    }
+    on_stage(Stage::Finished(Step::Contrast));
+
+    on_stage(Stage::Started(Step::Gamma));
+    // Tone curves always map black to black and white to white, so binary images are unaffected.
+    let gamma = if gamma_is_identity(params) {
+        debug!("skipping gamma, {:?} is an identity", params.tone_curve);
+        false
+    } else if params.threshold.is_some() {
+        debug!("skipping gamma, image is thresholded");
+        false
+    } else if is_binary(image) {
+        debug!("skipping gamma, image is binary");
+        false
+    } else {
+        debug!(
+            "applying {:?}, ink boost {}",
+            params.tone_curve, params.ink_boost
+        );
+        true
+    };
+    let grain = params.add_grain > 0
+        && params.threshold.is_none()
+        && !params.dither_gamma
+        && !is_binary(image);
+    if grain {
+        debug!("adding grain of amount {}", params.add_grain);
+        dither::grain(
+            image,
+            &params.gamma_curve,
+            params.add_grain,
+            params.dither_seed,
+        );
+    } else if gamma {
+        if params.dither_gamma {
+            let curve = &params.gamma_curve;
+            let target = |p: u8| curve[p as usize];
+            let quantize = |v: f32| clamp(v.round().into());
+            match params.dither_method {
+                DitherMethod::FloydSteinberg => dither::floyd_steinberg(image, target, quantize),
+                DitherMethod::Noise => dither::noise(image, target, quantize, params.dither_seed),
+            }
+        } else {
+            apply_lut(image, &params.gamma_lut);
+        }
+    }
+    if params.adaptive_gamma > 0.0 && params.threshold.is_none() {
+        adaptive_gamma(image, params.adaptive_gamma);
+    }
+    on_stage(Stage::Finished(Step::Gamma));
+
+    ToneStages {
+        bounds,
+        gamma,
+        local: false,
+    }
+}
+
+/// Returns a single lookup table equivalent to the tone stages [`convert()`] applies to `image`, or
+/// `None` if an enabled stage depends on pixel positions, e.g. dithering or local stretching.
+///
+/// The image is expected at its final size, as the contrast stretch bounds are computed from it.
+#[cfg(feature = "gpu")]
+pub(crate) fn tone_lut(image: &GrayImage, params: &ConvertParams) -> Option<[u8; 256]> {
+    if !tone_stages_form_lut(params) {
+        return None;
+    }
+    Some(histogram_tone_lut(&histogram(image), params).0)
+}
+
+/// Returns whether the tone stages configured in `params` can be applied as a single lookup table.
+fn tone_stages_form_lut(params: &ConvertParams) -> bool {
+    let gamma = !gamma_is_identity(params);
+    !(params.local_stretch.is_some()
+        || (gamma && params.dither_gamma && params.threshold.is_none())
+        || (params.add_grain > 0 && !params.dither_gamma && params.threshold.is_none())
+        || (params.adaptive_gamma > 0.0 && params.threshold.is_none()))
+}
+
+/// Returns a single lookup table equivalent to the tone stages applied to an image with histogram
+/// `hist`, along with the stages it includes. Expects [`tone_stages_form_lut()`] to hold.
+fn histogram_tone_lut(hist: &[u64; 256], params: &ConvertParams) -> ([u8; 256], ToneStages) {
+    let bounds = match &params.contrast_method {
+        ContrastMethod::Stretch => contrast_bounds(hist, params),
+        ContrastMethod::Specify(_) => None,
+    };
+    let mut lut = match bounds {
+        Some((lower, upper)) => {
+            let mut lut = stretch_lut(lower, upper, params.soft_clip, params.stretch_rounding);
+            if params.preserve_median {
+                preserve_value(&mut lut, histogram_percentile(hist, 50));
+            }
+            lut
+        }
+        None => match &params.contrast_method {
+            ContrastMethod::Specify(target) => {
+                specify_lut(hist, target).unwrap_or_else(identity_lut)
+            }
+            ContrastMethod::Stretch => identity_lut(),
+        },
+    };
+    let binary = hist
+        .iter()
+        .zip(&lut)
+        .all(|(&count, &v)| count == 0 || v == 0 || v == 255);
+    let gamma = !gamma_is_identity(params) && params.threshold.is_none() && !binary;
+    if let Some(threshold) = params.threshold {
+        let threshold = threshold_lut(threshold, params.threshold_antialias);
+        lut.iter_mut().for_each(|v| *v = threshold[*v as usize]);
+    } else if gamma {
+        lut.iter_mut()
+            .for_each(|v| *v = params.gamma_lut[*v as usize]);
+    }
+    let stages = ToneStages {
+        bounds,
+        gamma,
+        local: false,
+    };
+    (lut, stages)
+}
+
+/// Resizes an image in linear light and applies the tone stages while encoding it back, if
+/// [`ConvertParamsBuilder::linear_resize()`] is set and no stage in between depends on pixel
+/// positions. Returns `None` if the stages should be applied one after another instead.
+///
+/// The output matches sequential application exactly, as the tone lookup table is composed with
+/// the 8-bit encoding rather than applied to linear values directly.
+fn fused_linear_stage(
+    image: &GrayImage,
+    params: &ConvertParams,
+) -> Option<(GrayImage, ToneStages)> {
+    if !params.linear_resize
+        || params.custom_filter.is_some()
+        || params.sharpen > 0.0
+        || params.despeckle > 1
+        || params.resize_edge != EdgeMode::Truncate
+        || !tone_stages_form_lut(params)
+    {
+        return None;
+    }
+    let (width, height) = target_dimensions(image.width(), image.height(), params)?;
+    if (width > image.width() && params.upscale_method == UpscaleMethod::EdgeDirected)
+        || (params.two_pass && image.width() > width.saturating_mul(TWO_PASS_FACTOR))
+    {
+        return None;
+    }
+    debug!("applying tone stages while encoding linear values");
+    let mut stages = None;
+    let filter = resize_filter(image, params);
+    let image = resample::resize_linear_with(image, width, height, filter, |hist| {
+        let (lut, tone) = histogram_tone_lut(hist, params);
+        stages = Some(tone);
+        lut
+    });
+    Some((image, stages.expect("lookup table was computed")))
+}
+
+/// Size of the neighborhood averaged by [`adaptive_gamma()`], as a fraction of the larger image
+/// dimension.
+const ADAPTIVE_WINDOW: f32 = 1.0 / 8.0;
+
+/// Applies a per-pixel gamma whose exponent grows with the average brightness around the pixel.
+///
+/// With strength 1, exponents range from 0.5 in black surroundings to 2 in white surroundings,
+/// while mid gray surroundings leave pixels unchanged.
+fn adaptive_gamma(image: &mut GrayImage, strength: f32) {
+    let (width, height) = image.dimensions();
+    let radius = ((width.max(height) as f32 * ADAPTIVE_WINDOW / 2.0) as u32).max(1);
+    let integral = IntegralImage::new(image);
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+            let window = Rect::new(x0, y0, x - x0 + radius + 1, y - y0 + radius + 1);
+            let local = f32::from(integral.mean(window)) / 255.0;
+            let exponent = 2_f32.powf(2.0 * strength * (local - 0.5));
+            let p = image.get_pixel_mut(x, y);
+            p[0] = (255.0 * (f32::from(p[0]) / 255.0).powf(exponent)).round() as u8;
+        }
+    }
+}
+
+/// Defines parameters for the [`convert()`] function.
+///
+/// `ConvertParams` is `Send + Sync`, so a single instance can be shared by reference between
+/// threads converting images in parallel, no cloning or locking required.
+///
+/// See also: [`ConvertParamsBuilder`], [`ConvertParamsBuilder::default()`].
+///
+/// Examples
+///
+/// ```
+/// use maco::ConvertParams;
+///
+/// ConvertParams::builder().gamma(0.8).cutoff(10_u8).build();
+/// ```
+#[derive(Debug)]
+pub struct ConvertParams {
+    width: Dimension,
+    height: Dimension,
+    upscale: bool,
+    cutoff: u8,
+    filter: FilterType,
+    tone_curve: ToneCurve,
+    gamma_lut: [u8; 256],
+    gamma_curve: [f32; 256],
+    gamma_protect: Option<(u8, u8)>,
+    upscale_method: UpscaleMethod,
+    dither_gamma: bool,
+    soft_clip: bool,
+    local_stretch: Option<(u32, u32)>,
+    threshold: Option<u8>,
+    custom_filter: Option<CustomFilter>,
+    ink_boost: f32,
+    max_aspect: Option<f32>,
+    split_strategy: SplitStrategy,
+    stretch_rounding: Rounding,
+    preserve_median: bool,
+    seam_carve: bool,
+    two_pass: bool,
+    sharpen: f32,
+    sharpen_downscale_only: bool,
+    dither_method: DitherMethod,
+    dither_seed: u64,
+    adaptive_gamma: f32,
+    linear_resize: bool,
+    threshold_antialias: u8,
+    despeckle: u32,
+    ringing_fallback: bool,
+    contrast_method: ContrastMethod,
+    remove_scan_lines: bool,
+    auto_invert: bool,
+    min_contrast: u8,
+    contrast_bounds: ContrastBounds,
+    resize_edge: EdgeMode,
+    source_gamma: f64,
+    power_of_two: bool,
+    add_grain: u8,
+}
+
+impl Default for ConvertParams {
+    /// Convenience method wrapping [`ConvertParamsBuilder::default()`].
+    fn default() -> Self {
+        ConvertParamsBuilder::default().build()
+    }
+}
+
+impl ConvertParams {
+    /// Returns a new [`ConvertParamsBuilder`] with default settings.
+    pub fn builder() -> ConvertParamsBuilder {
+        ConvertParamsBuilder::default()
+    }
+
+    /// Returns params which touch the image as little as possible while still fitting a generous
+    /// screen, for archiving scans at maximum fidelity.
+    ///
+    /// Images are only downscaled if larger than 3000x3000, using [`FilterType::Lanczos3`]. No
+    /// gamma is applied and no pixels are cut off by the contrast stretch, so images spanning the
+    /// full range from black to white keep their tones. Compare with the default params, which
+    /// aggressively adjust images for e-ink screens.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use image::GrayImage;
+    /// use maco::{convert, ConvertParams};
+    ///
+    /// let image = GrayImage::from_fn(4000, 2000, |x, _| image::Luma([(x % 256) as u8]));
+    ///
+    /// assert_eq!(convert(image, &ConvertParams::archival()).dimensions(), (3000, 1500));
+    /// ```
+    pub fn archival() -> ConvertParams {
+        ConvertParams::builder()
+            .width(3000)
+            .height(3000)
+            .cutoff(0)
+            .filter(FilterType::Lanczos3)
+            .gamma(1.0)
+            .build()
+    }
+
+    /// Returns the maximum width of the output image.
+    pub fn width(&self) -> Dimension {
+        self.width
+    }
+
+    /// Returns the maximum height of the output image.
+    pub fn height(&self) -> Dimension {
+        self.height
+    }
+
+    /// Returns whether images smaller than the target dimensions are upscaled.
+    pub fn upscale(&self) -> bool {
+        self.upscale
+    }
+
+    /// Returns the % of the brightest and darkest pixels ignored by the contrast stretch.
+    pub fn cutoff(&self) -> u8 {
+        self.cutoff
+    }
+
+    /// Returns the gamma modifier, or `None` if the tone curve isn't [`ToneCurve::Gamma`].
+    pub fn gamma(&self) -> Option<f64> {
+        match self.tone_curve {
+            ToneCurve::Gamma(gamma) => Some(gamma),
+            _ => None,
+        }
+    }
+
+    /// Returns the resampling filter used by the resize stage.
+    ///
+    /// This doesn't depend on the image, so some overrides aren't reflected:
+    /// [`ConvertParamsBuilder::ringing_fallback()`] may switch to CatmullRom for images with hard
+    /// edges, and [`ConvertParamsBuilder::custom_filter()`] and edge-directed upscaling replace the
+    /// filter altogether.
+    pub fn effective_filter(&self) -> FilterType {
+        self.filter
+    }
+
+    /// Returns the resampling filter set by [`ConvertParamsBuilder::filter()`].
+    pub fn filter(&self) -> FilterType {
+        self.filter
+    }
+
+    /// Returns the strength of sharpening applied after resizing.
+    pub fn sharpen(&self) -> f32 {
+        self.sharpen
+    }
+
+    /// Returns whether sharpening is only applied to downscaled images.
+    pub fn sharpen_downscale_only(&self) -> bool {
+        self.sharpen_downscale_only
+    }
+
+    /// Returns whether images are resized in linear light.
+    pub fn linear_resize(&self) -> bool {
+        self.linear_resize
+    }
+
+    /// Returns the width of the gray band kept around the threshold.
+    pub fn threshold_antialias(&self) -> u8 {
+        self.threshold_antialias
+    }
+
+    /// Returns the size below which specks are removed.
+    pub fn despeckle(&self) -> u32 {
+        self.despeckle
+    }
+
+    /// Returns whether Lanczos falls back to CatmullRom for images with hard edges.
+    pub fn ringing_fallback(&self) -> bool {
+        self.ringing_fallback
+    }
+
+    /// Returns how contrast is adjusted.
+    pub fn contrast_method(&self) -> &ContrastMethod {
+        &self.contrast_method
+    }
+
+    /// Returns whether stuck scanner lines are removed.
+    pub fn remove_scan_lines(&self) -> bool {
+        self.remove_scan_lines
+    }
+
+    /// Returns whether negative scans are detected and inverted.
+    pub fn auto_invert(&self) -> bool {
+        self.auto_invert
+    }
+
+    /// Returns the range between stretch bounds below which the full range is stretched.
+    pub fn min_contrast(&self) -> u8 {
+        self.min_contrast
+    }
+
+    /// Returns how the bounds of the contrast stretch are determined.
+    pub fn contrast_bounds(&self) -> ContrastBounds {
+        self.contrast_bounds
+    }
+
+    /// Returns how the resampling filter treats pixels beyond the edges of the image.
+    pub fn resize_edge(&self) -> EdgeMode {
+        self.resize_edge
+    }
+
+    /// Returns the gamma the source image is declared to be encoded with.
+    pub fn source_gamma(&self) -> f64 {
+        self.source_gamma
+    }
+
+    /// Returns whether the resize target is rounded down to powers of two.
+    pub fn power_of_two(&self) -> bool {
+        self.power_of_two
+    }
+
+    /// Returns the amount of noise added before the gamma stage.
+    pub fn add_grain(&self) -> u8 {
+        self.add_grain
+    }
+
+    /// Returns whether large reductions are done in two passes.
+    pub fn two_pass(&self) -> bool {
+        self.two_pass
+    }
+
+    /// Returns whether images are narrowed using seam carving before resizing.
+    pub fn seam_carve(&self) -> bool {
+        self.seam_carve
+    }
+
+    /// Returns the custom resampling filter, if any.
+    pub fn custom_filter(&self) -> Option<CustomFilter> {
+        self.custom_filter
+    }
+
+    /// Returns the method used to enlarge images when upscaling.
+    pub fn upscale_method(&self) -> UpscaleMethod {
+        self.upscale_method
+    }
+
+    /// Returns the tone curve applied by the gamma stage.
+    pub fn tone_curve(&self) -> ToneCurve {
+        self.tone_curve
+    }
+
+    /// Returns the lookup table applied by the gamma stage, mapping each input value to its
+    /// output value.
+    pub fn gamma_lut(&self) -> &[u8; 256] {
+        &self.gamma_lut
+    }
+
+    /// Returns the range of values the gamma modifier is restricted to, if any.
+    pub fn gamma_protect(&self) -> Option<(u8, u8)> {
+        self.gamma_protect
+    }
+
+    /// Returns whether the gamma modifier is applied using error diffusion dithering.
+    pub fn dither_gamma(&self) -> bool {
+        self.dither_gamma
+    }
+
+    /// Returns the method used for dithering.
+    pub fn dither_method(&self) -> DitherMethod {
+        self.dither_method
+    }
+
+    /// Returns the seed of the noise used by [`DitherMethod::Noise`].
+    pub fn dither_seed(&self) -> u64 {
+        self.dither_seed
+    }
+
+    /// Returns how strongly gamma adapts to local brightness.
+    pub fn adaptive_gamma(&self) -> f32 {
+        self.adaptive_gamma
+    }
+
+    /// Returns the strength of the ink boost.
+    pub fn ink_boost(&self) -> f32 {
+        self.ink_boost
+    }
+
+    /// Returns whether the contrast stretch rolls off softly instead of clipping.
+    pub fn soft_clip(&self) -> bool {
+        self.soft_clip
+    }
+
+    /// Returns whether the contrast stretch keeps the median brightness in place.
+    pub fn preserve_median(&self) -> bool {
+        self.preserve_median
+    }
+
+    /// Returns how the contrast stretch rounds mapped values.
+    pub fn stretch_rounding(&self) -> Rounding {
+        self.stretch_rounding
+    }
+
+    /// Returns the tiles contrast is stretched in, if it's stretched locally.
+    pub fn local_stretch(&self) -> Option<(u32, u32)> {
+        self.local_stretch
+    }
+
+    /// Returns the threshold images are binarized with, if any.
+    pub fn threshold(&self) -> Option<u8> {
+        self.threshold
+    }
+
+    /// Returns the maximum ratio of height to width allowed by [`process()`], if any.
+    pub fn max_aspect(&self) -> Option<f32> {
+        self.max_aspect
+    }
+
+    /// Returns how [`process()`] splits images exceeding the maximum aspect ratio.
+    pub fn split_strategy(&self) -> SplitStrategy {
+        self.split_strategy
+    }
+}
+
+/// Builds [`ConvertParams`].
+pub struct ConvertParamsBuilder {
+    width: Dimension,
+    height: Dimension,
+    upscale: bool,
+    cutoff: u8,
+    filter: FilterType,
+    tone_curve: ToneCurve,
+    gamma_protect: Option<(u8, u8)>,
+    upscale_method: UpscaleMethod,
+    dither_gamma: bool,
+    soft_clip: bool,
+    local_stretch: Option<(u32, u32)>,
+    threshold: Option<u8>,
+    custom_filter: Option<CustomFilter>,
+    ink_boost: f32,
+    max_aspect: Option<f32>,
+    split_strategy: SplitStrategy,
+    stretch_rounding: Rounding,
+    preserve_median: bool,
+    seam_carve: bool,
+    two_pass: bool,
+    sharpen: f32,
+    sharpen_downscale_only: bool,
+    dither_method: DitherMethod,
+    dither_seed: u64,
+    adaptive_gamma: f32,
+    linear_resize: bool,
+    threshold_antialias: u8,
+    despeckle: u32,
+    ringing_fallback: bool,
+    contrast_method: ContrastMethod,
+    remove_scan_lines: bool,
+    auto_invert: bool,
+    min_contrast: u8,
+    contrast_bounds: ContrastBounds,
+    resize_edge: EdgeMode,
+    source_gamma: f64,
+    power_of_two: bool,
+    add_grain: u8,
+}
+
+impl Default for ConvertParamsBuilder {
+    /// Default values are equal to the following:
+    ///
+    /// ```ignore
+    /// ConvertParamsBuilder {
+    ///     width: Dimension::Exact(1920),
+    ///     height: Dimension::Exact(1920),
+    ///     upscale: false,
+    ///     cutoff: 1,
+    ///     filter: FilterType::CatmullRom,
+    ///     tone_curve: ToneCurve::Gamma(0.75),
+    ///     gamma_protect: None,
+    ///     upscale_method: UpscaleMethod::Interpolate,
+    ///     dither_gamma: false,
+    ///     soft_clip: false,
+    ///     local_stretch: None,
+    ///     threshold: None,
+    ///     custom_filter: None,
+    ///     ink_boost: 0.0,
+    ///     max_aspect: None,
+    ///     split_strategy: SplitStrategy::Equal,
+    ///     stretch_rounding: Rounding::Truncate,
+    ///     preserve_median: false,
+    ///     seam_carve: false,
+    ///     two_pass: false,
+    ///     sharpen: 0.0,
+    ///     sharpen_downscale_only: false,
+    ///     dither_method: DitherMethod::FloydSteinberg,
+    ///     dither_seed: 0,
+    ///     adaptive_gamma: 0.0,
+    ///     linear_resize: false,
+    ///     threshold_antialias: 0,
+    ///     despeckle: 0,
+    ///     ringing_fallback: false,
+    ///     contrast_method: ContrastMethod::Stretch,
+    ///     remove_scan_lines: false,
+    ///     auto_invert: false,
+    ///     min_contrast: 0,
+    ///     contrast_bounds: ContrastBounds::Percentile,
+    ///     resize_edge: EdgeMode::Truncate,
+    ///     source_gamma: 1.0,
+    ///     power_of_two: false,
+    ///     add_grain: 0,
+    /// }
+    /// ```
+    fn default() -> Self {
+        ConvertParamsBuilder {
+            width: Dimension::Exact(1920),
+            height: Dimension::Exact(1920),
+            upscale: false,
+            cutoff: 1,
+            filter: FilterType::CatmullRom,
+            tone_curve: ToneCurve::Gamma(0.75),
+            gamma_protect: None,
+            upscale_method: UpscaleMethod::Interpolate,
+            dither_gamma: false,
+            soft_clip: false,
+            local_stretch: None,
+            threshold: None,
+            custom_filter: None,
+            ink_boost: 0.0,
+            max_aspect: None,
+            split_strategy: SplitStrategy::Equal,
+            stretch_rounding: Rounding::Truncate,
+            preserve_median: false,
+            seam_carve: false,
+            two_pass: false,
+            sharpen: 0.0,
+            sharpen_downscale_only: false,
+            dither_method: DitherMethod::FloydSteinberg,
+            dither_seed: 0,
+            adaptive_gamma: 0.0,
+            linear_resize: false,
+            threshold_antialias: 0,
+            despeckle: 0,
+            ringing_fallback: false,
+            contrast_method: ContrastMethod::Stretch,
+            remove_scan_lines: false,
+            auto_invert: false,
+            min_contrast: 0,
+            contrast_bounds: ContrastBounds::Percentile,
+            resize_edge: EdgeMode::Truncate,
+            source_gamma: 1.0,
+            power_of_two: false,
+            add_grain: 0,
+        }
+    }
+}
+
+impl ConvertParamsBuilder {
+    /// Sets maximum width for the output image. Aspect ratio will be preserved.
+    ///
+    /// Accepts a [`Dimension`] or a plain `u32`, which is treated as [`Dimension::Exact`].
+    pub fn width<D: Into<Dimension>>(&mut self, width: D) -> &mut Self {
+        self.width = width.into();
+        self
+    }
+
+    /// Sets maximum height for the output image. Aspect ratio will be preserved.
+    ///
+    /// Accepts a [`Dimension`] or a plain `u32`, which is treated as [`Dimension::Exact`].
+    pub fn height<D: Into<Dimension>>(&mut self, height: D) -> &mut Self {
+        self.height = height.into();
+        self
+    }
+
+    /// Sets maximum width and height for the output image from a physical size in millimeters,
+    /// printed at `dpi` dots per inch. Aspect ratio will be preserved.
+    ///
+    /// To make the saved image report the same size, pass the same resolution to
+    /// [`SaveParamsBuilder::dpi()`](crate::io::SaveParamsBuilder::dpi).
+    ///
+    /// Sizes smaller than a dot are rounded up to 1 pixel. Sizes which aren't positive, or a `dpi`
+    /// of 0, set the dimension to `Dimension::Exact(0)`: [`ConvertParamsBuilder::build()`] keeps
+    /// the source size then, while [`ConvertParamsBuilder::try_build()`] rejects it with
+    /// [`BuildError::ZeroDimension`].
+    pub fn target_physical(&mut self, width_mm: f32, height_mm: f32, dpi: u16) -> &mut Self {
+        let pixels = |mm: f32| {
+            let pixels = (f64::from(mm) / MM_PER_INCH * f64::from(dpi)).round() as u32;
+            if mm > 0_f32 && dpi > 0 {
+                pixels.max(1)
+            } else {
+                0
+            }
+        };
+        self.width = Dimension::Exact(pixels(width_mm));
+        self.height = Dimension::Exact(pixels(height_mm));
+        self
+    }
+
+    /// Sets whether or not an image smaller than the desired width x height should be upscaled.
+    pub fn upscale(&mut self, upscale: bool) -> &mut Self {
+        self.upscale = upscale;
+        self
+    }
+
+    /// Sets % of the brightest and darkest pixels to ignore when equalizing the histogram.
+    pub fn cutoff(&mut self, upscale_percentile: u8) -> &mut Self {
+        self.cutoff = upscale_percentile;
+        self
+    }
+
+    /// Sets resampling filter used when resizing the image.
+    pub fn filter(&mut self, filter: FilterType) -> &mut Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets a gamma modifier. Values < 1 darken the image, values > 1 brighten it.
+    ///
+    /// Shorthand for `tone_curve(ToneCurve::Gamma(gamma))`.
+    pub fn gamma(&mut self, gamma: f64) -> &mut Self {
+        self.tone_curve = ToneCurve::Gamma(gamma);
+        self
+    }
+
+    /// Sets the tone curve applied after stretching the contrast.
+    ///
+    /// See also: [`ToneCurve`].
+    pub fn tone_curve(&mut self, curve: ToneCurve) -> &mut Self {
+        self.tone_curve = curve;
+        self
+    }
+
+    /// Restricts the gamma modifier to values between `low` and `high`. Values outside of this range
+    /// are left unchanged, which keeps dark ink and bright paper intact while still adjusting
+    /// midtones. The curve is rescaled to fit the range, so the output stays monotonic.
+    ///
+    /// `low` must be lower than `high`, otherwise the gamma modifier has no effect.
+    pub fn gamma_protect(&mut self, low: u8, high: u8) -> &mut Self {
+        self.gamma_protect = Some((low, high));
+        self
+    }
+
+    /// Sets the method used to enlarge images when upscaling is enabled.
+    pub fn upscale_method(&mut self, method: UpscaleMethod) -> &mut Self {
+        self.upscale_method = method;
+        self
+    }
+
+    /// Sets whether the gamma modifier should be applied using error diffusion dithering.
+    ///
+    /// Mapping 256 input values onto 256 output values through a curve makes some inputs collapse
+    /// into one output, which shows up as banding on smooth gradients. Dithering preserves the
+    /// average tone of such areas instead.
+    ///
+    /// See also: [`ConvertParamsBuilder::dither_method()`].
+    pub fn dither_gamma(&mut self, dither: bool) -> &mut Self {
+        self.dither_gamma = dither;
+        self
+    }
+
+    /// Sets whether the contrast stretch should gently roll off near black and white instead of
+    /// clipping. Values beyond the stretch bounds remain distinguishable, avoiding harsh blown
+    /// highlights, at the cost of slightly less contrast at the extremes.
+    pub fn soft_clip(&mut self, soft_clip: bool) -> &mut Self {
+        self.soft_clip = soft_clip;
+        self
+    }
+
+    /// Sets whether contrast should be stretched separately in each of `tiles` (columns, rows)
+    /// tiles, with bounds computed from the histogram of each tile. Results are blended smoothly
+    /// between tile centers. This evens out pages with uneven lighting, at the cost of no longer
+    /// preserving the relative brightness of distant areas.
+    ///
+    /// Off by default. Not used when contrast bounds are given explicitly, such as by
+    /// [`convert_frames()`].
+    pub fn local_stretch(&mut self, tiles: (u32, u32)) -> &mut Self {
+        self.local_stretch = Some(tiles);
+        self
+    }
+
+    /// Sets a threshold which binarizes the image after stretching contrast. Values at or above
+    /// `threshold` become white, values below it black.
+    ///
+    /// The gamma modifier has no effect on pure black and white, so it's skipped when binarizing.
+    pub fn threshold(&mut self, threshold: u8) -> &mut Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Sets a tunable resampling filter used when resizing the image, overriding
+    /// [`ConvertParamsBuilder::filter()`].
+    ///
+    /// See also: [`CustomFilter`].
+    pub fn custom_filter(&mut self, filter: CustomFilter) -> &mut Self {
+        self.custom_filter = Some(filter);
+        self
+    }
+
+    /// Sets how much to deepen dark tones, in range `0..=1`. Useful for faded pencil or ink scans,
+    /// where line art ends up dark gray instead of black.
+    ///
+    /// Unlike gamma, this targets the dark end: lower midtones are pulled toward black while
+    /// highlights are barely affected. The curve stays monotonic, so no detail is crushed. Applied
+    /// after the tone curve. Values outside of the range are clamped.
+    pub fn ink_boost(&mut self, strength: f32) -> &mut Self {
+        self.ink_boost = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Checks the settings for problems, returning all of them at once.
+    ///
+    /// [`ConvertParamsBuilder::build()`] doesn't validate and never panics, invalid settings only
+    /// produce unexpected output. The deprecated 0 target dimension is reported as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use maco::{BuildError, ConvertParams};
+    ///
+    /// let errors = ConvertParams::builder().gamma(-1.0).cutoff(60).validate().unwrap_err();
+    ///
+    /// assert_eq!(errors, vec![BuildError::InvalidGamma(-1.0), BuildError::InvalidCutoff(60)]);
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<BuildError>> {
+        let mut errors = Vec::new();
+        if let ToneCurve::Gamma(gamma) = self.tone_curve {
+            if !(gamma.is_finite() && gamma > 0_f64) {
+                errors.push(BuildError::InvalidGamma(gamma));
+            }
+        }
+        if !(self.source_gamma.is_finite() && self.source_gamma > 0_f64) {
+            errors.push(BuildError::InvalidSourceGamma(self.source_gamma));
+        }
+        if self.cutoff >= 50 {
+            errors.push(BuildError::InvalidCutoff(self.cutoff));
+        }
+        if self.width == Dimension::Exact(0) || self.height == Dimension::Exact(0) {
+            errors.push(BuildError::ZeroDimension);
+        }
+        if let Some((low, high)) = self.gamma_protect {
+            if low >= high {
+                errors.push(BuildError::InvalidGammaProtect { low, high });
+            }
+        }
+        if let Some((x, y)) = self.local_stretch {
+            if x == 0 || y == 0 {
+                errors.push(BuildError::ZeroTiles);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Like [`ConvertParamsBuilder::build()`], but returns all problems found by
+    /// [`ConvertParamsBuilder::validate()`] instead of building invalid params.
+    pub fn try_build(&self) -> Result<ConvertParams, Vec<BuildError>> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
+    /// Sets the maximum ratio of height to width allowed by [`process()`]. Taller images, such as
+    /// webtoon strips, are split into several pages instead of being shrunk to fit the target
+    /// dimensions.
+    ///
+    /// See also: [`ConvertParamsBuilder::split_strategy()`].
+    pub fn max_aspect(&mut self, max_aspect: f32) -> &mut Self {
+        self.max_aspect = Some(max_aspect);
+        self
+    }
+
+    /// Sets how [`process()`] splits images exceeding [`ConvertParamsBuilder::max_aspect()`].
+    pub fn split_strategy(&mut self, strategy: SplitStrategy) -> &mut Self {
+        self.split_strategy = strategy;
+        self
+    }
+
+    /// Sets how the contrast stretch rounds mapped values. Defaults to truncation, which matches
+    /// [`imageproc::contrast::stretch_contrast_mut()`] but maps midtones up to a value too dark.
+    pub fn stretch_rounding(&mut self, rounding: Rounding) -> &mut Self {
+        self.stretch_rounding = rounding;
+        self
+    }
+
+    /// Sets whether the contrast stretch should keep the median brightness of the image where it
+    /// was. A power curve is folded into the stretch, bending it so the median maps back onto its
+    /// original value while the bounds still map to black and white. This keeps overall
+    /// brightness stable across pages, reducing flicker within a chapter. Composes with the gamma
+    /// stage, which is applied afterwards.
+    pub fn preserve_median(&mut self, preserve: bool) -> &mut Self {
+        self.preserve_median = preserve;
+        self
+    }
+
+    /// Sets whether images wider than the aspect ratio of the target dimensions should be narrowed
+    /// using seam carving before resizing. Low-detail vertical seams, such as empty background,
+    /// are removed instead of squishing or cropping the whole image.
+    ///
+    /// At most a quarter of the width is carved away, any remaining difference is left to the
+    /// resize stage, as larger changes distort content. Only applies if both target dimensions are
+    /// bounded. This is very slow and off by default.
+    pub fn seam_carve(&mut self, seam_carve: bool) -> &mut Self {
+        self.seam_carve = seam_carve;
+        self
+    }
+
+    /// Sets whether large reductions should be done in two passes. Images shrunk by more than
+    /// 3 times are first downscaled by averaging blocks of pixels, then resized to the
+    /// final size with the configured filter. This reduces aliasing of fine patterns, such as
+    /// screentone, with filters which sample few source pixels.
+    pub fn two_pass(&mut self, two_pass: bool) -> &mut Self {
+        self.two_pass = two_pass;
+        self
+    }
+
+    /// Sets the strength of an unsharp mask applied after resizing, 0 to disable. Values around 0.5
+    /// recover some crispness lost when downscaling. Negative values are treated as 0.
+    ///
+    /// See also: [`ConvertParamsBuilder::sharpen_downscale_only()`].
+    pub fn sharpen(&mut self, amount: f32) -> &mut Self {
+        self.sharpen = amount.max(0.0);
+        self
+    }
+
+    /// Sets whether sharpening should only be applied to images which were downscaled. Sharpening
+    /// upscaled images amplifies interpolation artifacts.
+    ///
+    /// See also: [`ConvertParamsBuilder::sharpen()`].
+    pub fn sharpen_downscale_only(&mut self, only: bool) -> &mut Self {
+        self.sharpen_downscale_only = only;
+        self
+    }
+
+    /// Sets the method used when dithering is enabled.
+    ///
+    /// See also: [`ConvertParamsBuilder::dither_gamma()`].
+    pub fn dither_method(&mut self, method: DitherMethod) -> &mut Self {
+        self.dither_method = method;
+        self
+    }
+
+    /// Sets the seed of the noise used by [`DitherMethod::Noise`]. Converting the same image with
+    /// the same seed always produces identical output.
+    pub fn dither_seed(&mut self, seed: u64) -> &mut Self {
+        self.dither_seed = seed;
+        self
+    }
+
+    /// Sets how strongly gamma adapts to local brightness, in range `0..=1`, 0 to disable.
+    ///
+    /// Each pixel gets its own exponent, based on the average brightness of its surroundings:
+    /// bright areas are darkened and dark areas lifted, flattening uneven lighting or mixed
+    /// panels. Applied after the tone curve. Values outside of the range are clamped.
+    pub fn adaptive_gamma(&mut self, strength: f32) -> &mut Self {
+        self.adaptive_gamma = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets whether images should be resized in linear light rather than in sRGB space.
+    ///
+    /// Averaging sRGB values darkens thin bright features, e.g. the gaps between lines in text or
+    /// line art, when downscaling. Resizing linearized values keeps their brightness. Ignored by
+    /// [`ConvertParamsBuilder::custom_filter()`] and edge-directed upscaling.
+    pub fn linear_resize(&mut self, linear: bool) -> &mut Self {
+        self.linear_resize = linear;
+        self
+    }
+
+    /// Sets the width of a gray transition band kept around the threshold, anti-aliasing edges
+    /// of the binarized image.
+    ///
+    /// Values within `width / 2` of the threshold are ramped linearly from black to white instead
+    /// of being snapped, which in practice only affects pixels along edges and smooths staircase
+    /// artifacts on diagonal lines. Near either end of the value range the band narrows, so that
+    /// it stays centered on the threshold and black and white stay pure. `0`, the default,
+    /// binarizes strictly. Has no effect without [`ConvertParamsBuilder::threshold()`].
+    pub fn threshold_antialias(&mut self, width: u8) -> &mut Self {
+        self.threshold_antialias = width;
+        self
+    }
+
+    /// Sets the size in pixels below which isolated specks, such as scan dust, are removed before
+    /// stretching contrast.
+    ///
+    /// Both dark specks on light backgrounds and light specks on dark backgrounds are filled with
+    /// their surroundings. Unlike blurring, this leaves line art and text untouched as long as
+    /// their strokes are larger than `max_size`. `0`, the default, disables despeckling.
+    pub fn despeckle(&mut self, max_size: u32) -> &mut Self {
+        self.despeckle = max_size;
+        self
+    }
+
+    /// Sets whether images with hard, high contrast edges, such as line art, should be resized with
+    /// [`FilterType::CatmullRom`] when the filter is [`FilterType::Lanczos3`].
+    ///
+    /// Lanczos rings around such edges, leaving visible halos, while CatmullRom overshoots much
+    /// less. Images without hard edges keep using Lanczos.
+    pub fn ringing_fallback(&mut self, fallback: bool) -> &mut Self {
+        self.ringing_fallback = fallback;
+        self
+    }
+
+    /// Sets how contrast is adjusted before the tone curve is applied.
+    ///
+    /// [`ContrastMethod::Specify`] remaps each page to a reference histogram, giving a series a
+    /// consistent tonal look. Overridden by [`ConvertParamsBuilder::local_stretch()`] and not
+    /// used when contrast bounds are given explicitly, such as by [`convert_frames()`].
+    pub fn contrast_method(&mut self, method: ContrastMethod) -> &mut Self {
+        self.contrast_method = method;
+        self
+    }
+
+    /// Sets whether full-length rows and columns left by stuck scanner sensor elements should be
+    /// replaced with the average of their neighbors.
+    ///
+    /// Such lines are uniform along their whole length while their surroundings aren't, or differ
+    /// in brightness from both neighbors. They're removed before resizing, so they neither blur
+    /// into neighboring pixels nor skew the histogram the contrast stretch is based on.
+    pub fn remove_scan_lines(&mut self, remove: bool) -> &mut Self {
+        self.remove_scan_lines = remove;
+        self
+    }
+
+    /// Sets whether negative scans, with bright text or line art on a dark background, should be
+    /// detected and inverted before any other stage.
+    ///
+    /// Detection is conservative: an image is only inverted if its inverse looks like a text page
+    /// according to [`is_text_page()`](crate::is_text_page) and its borders are dark too, so
+    /// pages with large dark panels are left alone.
+    pub fn auto_invert(&mut self, invert: bool) -> &mut Self {
+        self.auto_invert = invert;
+        self
+    }
+
+    /// Sets the smallest range between the contrast stretch bounds, as determined by
+    /// [`ConvertParamsBuilder::cutoff()`], below which the stretch is forced to use the darkest and
+    /// brightest values of the image instead. `0`, the default, disables this.
+    ///
+    /// Pages with very low dynamic range may otherwise be left flat, as the stretch is skipped
+    /// when both bounds fall on the same value. Unless the image is a single flat value, stretching
+    /// its full range guarantees at least `min_contrast` between the darkest and brightest output
+    /// values.
+    pub fn min_contrast(&mut self, min_contrast: u8) -> &mut Self {
+        self.min_contrast = min_contrast;
+        self
+    }
+
+    /// Sets how the bounds of the contrast stretch are determined.
+    ///
+    /// [`ContrastBounds::MeanStd`] can be more robust for images with bell shaped histograms,
+    /// such as photos or screentone heavy pages, where percentiles land on sparse tails.
+    pub fn contrast_bounds(&mut self, bounds: ContrastBounds) -> &mut Self {
+        self.contrast_bounds = bounds;
+        self
+    }
+
+    /// Sets how the resampling filter treats pixels beyond the edges of the image.
+    ///
+    /// By default the kernel is truncated at the edges, which can leave a faint light or dark line
+    /// along content touching the border after downscaling. [`EdgeMode::Replicate`] and
+    /// [`EdgeMode::Reflect`] pad the image before resizing and crop the padding afterwards.
+    pub fn resize_edge(&mut self, mode: EdgeMode) -> &mut Self {
+        self.resize_edge = mode;
+        self
+    }
+
+    /// Declares the gamma the source image is encoded with, which is undone before applying the
+    /// tone curve.
+    ///
+    /// Some scanners tag images with a gamma that doesn't match their pixels, so applying a gamma
+    /// modifier on top darkens them twice. Raising values to the power of `gamma` first avoids
+    /// that: declaring the same gamma as [`gamma()`](Self::gamma) leaves tones nearly unchanged.
+    /// The default of 1 assumes the source needs no correction. Must be positive.
+    pub fn source_gamma(&mut self, gamma: f64) -> &mut Self {
+        self.source_gamma = gamma;
+        self
+    }
+
+    /// Sets whether the resize target should be rounded down to a power of two in each
+    /// dimension, as required by some GPU texture formats.
+    ///
+    /// Both dimensions are rounded independently after fitting the image into the configured
+    /// dimensions, so the aspect ratio is generally not preserved and the image is stretched
+    /// slightly along one axis. For example, a 1000x1500 image becomes 512x1024. Images are
+    /// rounded down even if they already fit and upscaling is disabled.
+    pub fn power_of_two(&mut self, enabled: bool) -> &mut Self {
+        self.power_of_two = enabled;
+        self
+    }
+
+    /// Sets the amount of noise added before the gamma stage quantizes the image, as the range of
+    /// the noise in 8-bit levels, 0 to disable.
+    ///
+    /// A tiny amount of grain breaks up banding on smooth gradients, which some e-ink displays show
+    /// more prominently than the noise itself. The noise is seeded with
+    /// [`dither_seed()`](Self::dither_seed), so output is reproducible. Has no effect on
+    /// thresholded or binary images, or if [`dither_gamma()`](Self::dither_gamma) is set, as
+    /// dithering already breaks up banding.
+    pub fn add_grain(&mut self, amount: u8) -> &mut Self {
+        self.add_grain = amount;
+        self
+    }
+
+    /// Builds and returns a [`ConvertParams`] instance.
+    pub fn build(&self) -> ConvertParams {
+        ConvertParams {
+            width: self.width,
+            height: self.height,
+            upscale: self.upscale,
+            cutoff: self.cutoff,
+            filter: self.filter,
+            tone_curve: self.tone_curve,
+            gamma_lut: generate_gamma_lut(
+                self.tone_curve,
+                self.gamma_protect,
+                self.ink_boost,
+                self.source_gamma,
+            ),
+            gamma_curve: generate_gamma_curve(
+                self.tone_curve,
+                self.gamma_protect,
+                self.ink_boost,
+                self.source_gamma,
+            ),
+            gamma_protect: self.gamma_protect,
+            upscale_method: self.upscale_method,
+            dither_gamma: self.dither_gamma,
+            soft_clip: self.soft_clip,
+            local_stretch: self.local_stretch,
+            threshold: self.threshold,
+            custom_filter: self.custom_filter,
+            ink_boost: self.ink_boost,
+            max_aspect: self.max_aspect,
+            split_strategy: self.split_strategy,
+            stretch_rounding: self.stretch_rounding,
+            preserve_median: self.preserve_median,
+            seam_carve: self.seam_carve,
+            two_pass: self.two_pass,
+            sharpen: self.sharpen,
+            sharpen_downscale_only: self.sharpen_downscale_only,
+            dither_method: self.dither_method,
+            dither_seed: self.dither_seed,
+            adaptive_gamma: self.adaptive_gamma,
+            linear_resize: self.linear_resize,
+            threshold_antialias: self.threshold_antialias,
+            despeckle: self.despeckle,
+            ringing_fallback: self.ringing_fallback,
+            contrast_method: self.contrast_method.clone(),
+            remove_scan_lines: self.remove_scan_lines,
+            auto_invert: self.auto_invert,
+            min_contrast: self.min_contrast,
+            contrast_bounds: self.contrast_bounds,
+            resize_edge: self.resize_edge,
+            source_gamma: self.source_gamma,
+            power_of_two: self.power_of_two,
+            add_grain: self.add_grain,
+        }
+    }
+}
+
+/// Defines how fractional values are mapped to integers.
+///
+/// See also: [`ConvertParamsBuilder::stretch_rounding()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Rounds towards zero.
+    Truncate,
+    /// Rounds to the nearest integer, halves away from zero.
+    Nearest,
+}
+
+/// Defines how [`convert()`] adjusts contrast.
+///
+/// See also: [`ConvertParamsBuilder::contrast_method()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContrastMethod {
+    /// Stretches the histogram between the bounds set by [`ConvertParamsBuilder::cutoff()`].
+    Stretch,
+    /// Remaps values so the histogram matches the given one, e.g. of a reference page. Only the
+    /// shape of the histogram matters, not its pixel count. An empty histogram leaves values
+    /// unchanged.
+    Specify(Box<[u32; 256]>),
+}
+
+/// Defines how the bounds of the contrast stretch are determined.
+///
+/// See also: [`ConvertParamsBuilder::contrast_bounds()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContrastBounds {
+    /// Bounds at the percentiles set by [`ConvertParamsBuilder::cutoff()`].
+    Percentile,
+    /// Bounds at the mean brightness minus and plus `k` standard deviations, clamped to
+    /// `0..=255`. The cutoff is ignored.
+    MeanStd {
+        /// Number of standard deviations between the mean and each bound.
+        k: f32,
+    },
+}
+
+/// Defines the tone curve applied by the gamma stage of [`convert()`].
+///
+/// See also: [`ConvertParamsBuilder::tone_curve()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ToneCurve {
+    /// Raises values to the power of `1 / gamma`. Values < 1 darken the image, values > 1 brighten
+    /// it.
+    Gamma(f64),
+    /// The sRGB transfer function, mapping sRGB encoded values to linear light.
+    Srgb,
+    /// The Rec. 709 transfer function, mapping Rec. 709 encoded values to linear light.
+    Rec709,
+    /// Leaves values unchanged.
+    Linear,
+}
+
+impl ToneCurve {
+    /// Maps a value in the range `0..=1` through the curve.
+    fn transfer(self, x: f64) -> f64 {
+        match self {
+            ToneCurve::Gamma(gamma) => x.powf(1_f64 / gamma),
+            ToneCurve::Srgb if x <= 0.04045 => x / 12.92,
+            ToneCurve::Srgb => ((x + 0.055) / 1.055).powf(2.4),
+            ToneCurve::Rec709 if x < 0.081 => x / 4.5,
+            ToneCurve::Rec709 => ((x + 0.099) / 1.099).powf(1_f64 / 0.45),
+            ToneCurve::Linear => x,
+        }
+    }
+
+    /// Returns whether the curve leaves an image unchanged.
+    fn is_identity(self) -> bool {
+        match self {
+            // If gamma == 1 the image doesn't change.
+            ToneCurve::Gamma(gamma) => (gamma - 1_f64).abs() <= 0.001,
+            ToneCurve::Linear => true,
+            ToneCurve::Srgb | ToneCurve::Rec709 => false,
+        }
+    }
+}
+
+/// Returns whether the gamma stage configured in `params` leaves images unchanged.
+fn gamma_is_identity(params: &ConvertParams) -> bool {
+    params.tone_curve.is_identity() && params.ink_boost == 0.0 && is_unit_gamma(params.source_gamma)
+}
+
+/// Returns whether `gamma` is close enough to 1 to be skipped.
+fn is_unit_gamma(gamma: f64) -> bool {
+    (gamma - 1_f64).abs() <= 0.001
+}
+
+/// Generates a lookup table with gamma modifications applied.
+/// If `protect` is set, only values within the given range are modified.
+fn generate_gamma_lut(
+    curve: ToneCurve,
+    protect: Option<(u8, u8)>,
+    ink_boost: f32,
+    source_gamma: f64,
+) -> [u8; 256] {
+    let mut lut = [0; 256];
+    for (x, v) in lut
+        .iter_mut()
+        .zip(gamma_curve(curve, protect, ink_boost, source_gamma))
+    {
+        *x = clamp(v);
+    }
+    lut
+}
+
+/// Like [`generate_gamma_lut()`], but keeps the exact, unquantized values for dithering.
+fn generate_gamma_curve(
+    curve: ToneCurve,
+    protect: Option<(u8, u8)>,
+    ink_boost: f32,
+    source_gamma: f64,
+) -> [f32; 256] {
+    let mut values = [0_f32; 256];
+    for (x, v) in values
+        .iter_mut()
+        .zip(gamma_curve(curve, protect, ink_boost, source_gamma))
+    {
+        *x = v as f32;
+    }
+    values
+}
+
+/// Returns the exact output value of the gamma modifier for each input value.
+fn gamma_curve(
+    curve: ToneCurve,
+    protect: Option<(u8, u8)>,
+    ink_boost: f32,
+    source_gamma: f64,
+) -> impl Iterator<Item = f64> {
+    let (low, high) = protect.unwrap_or((0, 255));
+    let (low, high) = (f64::from(low), f64::from(high));
+    let ink_boost = f64::from(ink_boost);
+    (0..256).map(move |i| {
+        let i = f64::from(i);
+        // Undo the encoding of the source before applying the curve.
+        let i = if is_unit_gamma(source_gamma) {
+            i
+        } else {
+            (i / 255_f64).powf(source_gamma) * 255_f64
+        };
+        let v = if i <= low || i >= high {
+            i
+        } else {
+            low + curve.transfer((i - low) / (high - low)) * (high - low)
+        };
+        // x * (1 - s * (1 - x)^3) has a derivative of at least 1 - s, so it's monotonic for s <= 1.
+        let x = v / 255_f64;
+        x * (1_f64 - ink_boost * (1_f64 - x).powi(3)) * 255_f64
+    })
+}
+
+fn clamp(i: f64) -> u8 {
+    if i > 255_f64 {
+        return 255;
+    }
+    if i > 0_f64 {
+        return i as u8;
+    }
+    0
+}
+
+/// Applies a lookup table to a grayscale image, i.e. for each pixel, given pixel value x, replaces
+/// said pixel with lut[x].
+pub(crate) fn apply_lut(image: &mut GrayImage, lut: &[u8; 256]) {
+    // A u8 always indexes into 256 entries, so no bounds checks are needed.
+    for p in image.pixels_mut() {
+        *p = Luma([lut[p[0] as usize]]);
+    }
+}
+
+/// Maps every pixel of a 16-bit image through a lookup table.
+///
+/// The table may have fewer than 65536 entries, in which case values beyond its end map to its
+/// last entry. An empty table leaves the image unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use image::{ImageBuffer, Luma};
+/// use maco::apply_lut16;
+///
+/// let mut image = ImageBuffer::from_raw(3, 1, vec![0_u16, 1, 1000]).unwrap();
+/// apply_lut16(&mut image, &[10, 20, 30]);
+///
+/// assert_eq!(image.into_raw(), vec![10, 20, 30]);
+/// ```
+pub fn apply_lut16(image: &mut ImageBuffer<Luma<u16>, Vec<u16>>, lut: &[u16]) {
+    let last = match lut.len().checked_sub(1) {
+        Some(last) => last,
+        None => return,
+    };
+    for p in image.pixels_mut() {
+        *p = Luma([lut[usize::from(p[0]).min(last)]]);
+    }
+}
+
+/// Returns a lookup table which maps values below `threshold` to black and the rest to white.
+///
+/// A non-zero `band` replaces the step with a linear ramp of that width centered on `threshold`.
+/// The ramp is narrowed to fit into the value range, and 0 and 255 always map to black and white.
+fn threshold_lut(threshold: u8, band: u8) -> [u8; 256] {
+    let threshold = u32::from(threshold);
+    let half = (u32::from(band) / 2).min(threshold).min(255 - threshold);
+    let (lower, upper) = (threshold - half, threshold + half);
+    let mut lut = [0; 256];
+    for (i, x) in lut.iter_mut().enumerate() {
+        let i = i as u32;
+        *x = if i >= upper {
+            255
+        } else if i < lower {
+            0
+        } else {
+            ((i - lower) * 255 + half) / (2 * half)
+        } as u8;
+    }
+    if half > 0 {
+        lut[0] = 0;
+        lut[255] = 255;
+    }
+    lut
+}
+
+/// Returns whether an image contains only black and white pixels.
+fn is_binary(image: &GrayImage) -> bool {
+    image.iter().all(|&p| p == 0 || p == 255)
+}
+
+/// Returns a lookup table which leaves values unchanged.
+fn identity_lut() -> [u8; 256] {
+    let mut lut = [0; 256];
+    for (i, x) in lut.iter_mut().enumerate() {
+        *x = i as u8;
+    }
+    lut
+}
+
+/// Defines a limit on one of the output dimensions.
+///
+/// See also: [`ConvertParamsBuilder::width()`], [`ConvertParamsBuilder::height()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{convert, ConvertParams, Dimension};
+///
+/// let params = ConvertParams::builder()
+///     .width(Dimension::Unbounded)
+///     .height(500)
+///     .upscale(true)
+///     .build();
+///
+/// assert_eq!(convert(GrayImage::new(200, 100), &params).dimensions(), (1000, 500));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    /// The dimension imposes no limit, the output size is determined by the other dimension.
+    Unbounded,
+    /// The output is at most this large.
+    ///
+    /// For backwards compatibility, `Exact(0)` limits the output to the size of the source
+    /// image. This is deprecated, use [`Dimension::Unbounded`] to lift the limit instead.
+    Exact(u32),
+}
+
+impl Dimension {
+    /// Returns the limit for a source of given size, or `None` if unbounded.
+    fn limit(self, source: u32) -> Option<u32> {
+        match self {
+            Dimension::Unbounded => None,
+            Dimension::Exact(0) => Some(source),
+            Dimension::Exact(n) => Some(n),
+        }
+    }
+}
+
+impl From<u32> for Dimension {
+    fn from(value: u32) -> Self {
+        Dimension::Exact(value)
+    }
+}
+
+/// Identifies which of the target dimensions limits the size of a resized image.
+///
+/// See also: [`binding_constraint()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    /// The output reaches the target width, height is scaled down to preserve aspect ratio.
+    Width,
+    /// The output reaches the target height, width is scaled down to preserve aspect ratio.
+    Height,
+}
+
+/// Returns which of the dimensions configured in `params` limits an image of the given size.
+///
+/// Useful for explaining why an output image didn't reach the requested width or height. If the
+/// image fits both dimensions exactly, [`Constraint::Width`] is returned.
+///
+/// # Examples
+///
+/// ```
+/// use maco::{binding_constraint, Constraint, ConvertParams};
+///
+/// let params = ConvertParams::builder().width(70).height(50).build();
+///
+/// assert_eq!(binding_constraint(100, 100, &params), Constraint::Height);
+/// ```
+pub fn binding_constraint(width: u32, height: u32, params: &ConvertParams) -> Constraint {
+    fit(width, height, params.width, params.height).1
+}
+
+/// Calculates the width and height an image should be resized to.
+/// Preserves aspect ratio so that both dimensions are contained within the given `nx` and `ny`.
+/// A [`Dimension::Unbounded`] dimension is determined by the other one alone, while
+/// `Dimension::Exact(0)` is replaced by `x` or `y` respectively. If both are unbounded, the size
+/// is left unchanged. Dimensions too large for a `u32` saturate at `u32::MAX`.
+fn resize_dimensions(x: u32, y: u32, nx: Dimension, ny: Dimension) -> (u32, u32) {
+    fit(x, y, nx, ny).0
+}
+
+/// Does the work for [`resize_dimensions()`], additionally returning the binding constraint.
+fn fit(x: u32, y: u32, nx: Dimension, ny: Dimension) -> ((u32, u32), Constraint) {
+    let (nx, ny) = match (nx.limit(x), ny.limit(y)) {
+        (Some(nx), Some(ny)) => (nx, ny),
+        (Some(nx), None) => (nx, u32::MAX),
+        (None, Some(ny)) => (u32::MAX, ny),
+        (None, None) => return ((x, y), Constraint::Width),
+    };
+
+    let ratio = u64::from(x) * u64::from(ny);
+    let nratio = u64::from(nx) * u64::from(y);
+
+    let use_y = nratio <= ratio;
+
+    let intermediate = if use_y {
+        u64::from(y) * u64::from(nx) / u64::from(x)
+    } else {
+        u64::from(x) * u64::from(ny) / u64::from(y)
+    };
+    let intermediate = intermediate.clamp(1, u64::from(u32::MAX)) as u32;
+
+    if use_y {
+        ((nx, intermediate), Constraint::Width)
+    } else {
+        ((intermediate, ny), Constraint::Height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::{assert_pixels_eq, gray_image};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    macro_rules! dimensions_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
                 let (x, y, nx, ny, expected) = $value;
-                assert_eq!(expected, resize_dimensions(x, y, nx, ny));
+                assert_eq!(expected, resize_dimensions(x, y, nx.into(), ny.into()));
             }
         )*
         }
@@ -250,5 +3017,1081 @@ mod tests {
         resize_dimensions_y_gt_x: (100, 100, 50, 70, (50, 50)),
         resize_dimensions_0nx: (100, 100, 0, 50, (50, 50)),
         resize_dimensions_0ny: (100, 100, 50, 0, (50, 50)),
+        resize_dimensions_unbounded_nx: (100, 100, Dimension::Unbounded, 500, (500, 500)),
+        resize_dimensions_0nx_upscale: (100, 100, 0, 500, (100, 100)),
+        resize_dimensions_unbounded_ny: (200, 100, 50, Dimension::Unbounded, (50, 25)),
+        resize_dimensions_unbounded: (200, 100, Dimension::Unbounded, Dimension::Unbounded, (200, 100)),
+        resize_dimensions_unbounded_large: (1, 100_000, 100_000, Dimension::Unbounded, (42_949, u32::MAX)),
+    }
+
+    /// Generates target dimensions, including the legacy 0 meaning "source size".
+    fn target() -> impl Strategy<Value = u32> {
+        prop_oneof![Just(0_u32), 1..100_000_u32]
+    }
+
+    proptest! {
+        #[test]
+        fn resize_dimensions_invariants(
+            x in 1..100_000_u32,
+            y in 1..100_000_u32,
+            nx in target(),
+            ny in target(),
+        ) {
+            let (width, height) = resize_dimensions(x, y, nx.into(), ny.into());
+            let (max_width, max_height) = (if nx > 0 { nx } else { x }, if ny > 0 { ny } else { y });
+
+            prop_assert!(width > 0 && height > 0);
+            prop_assert!(width <= max_width && height <= max_height);
+            // One dimension is exact, the other rounded down, or up to 1.
+            let skew = (u64::from(width) * u64::from(y)).abs_diff(u64::from(height) * u64::from(x));
+            prop_assert!(skew < u64::from(x.max(y)));
+        }
+    }
+
+    #[test]
+    fn clip_map_flags_extremes() {
+        let image = gray_image!(
+            0,  20,  40,  60,  80;
+            100, 120, 140, 160, 180;
+            200, 220, 240, 250, 255);
+        let params = ConvertParams::builder().cutoff(10).build();
+
+        assert_pixels_eq!(
+            clip_map(&image, &params),
+            gray_image!(
+                0,   0,   128, 128, 128;
+                128, 128, 128, 128, 128;
+                128, 128, 128, 255, 255)
+        );
+    }
+
+    #[test]
+    fn clip_map_without_stretch() {
+        let image = gray_image!(0, 100, 255);
+        let params = ConvertParams::builder().cutoff(0).build();
+
+        assert_pixels_eq!(clip_map(&image, &params), gray_image!(128, 128, 128));
+    }
+
+    #[test]
+    fn histogram_percentile_matches_imageproc() {
+        let image = GrayImage::from_fn(37, 11, |x, y| Luma([(x * x + y * 7) as u8]));
+        let hist = histogram(&image);
+
+        for p in 0..=100 {
+            assert_eq!(
+                histogram_percentile(&hist, p),
+                imageproc::stats::percentile(&image, p)
+            );
+        }
+    }
+
+    #[test]
+    fn stretch_lut_matches_imageproc() {
+        let mut want = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+        imageproc::contrast::stretch_contrast_mut(&mut want, 37, 201);
+
+        let lut = stretch_lut(37, 201, false, Rounding::Truncate);
+
+        assert_eq!(&lut[..], &want.into_raw()[..]);
+    }
+
+    #[test]
+    fn stretch_lut_rounding() {
+        let truncated = stretch_lut(0, 200, false, Rounding::Truncate);
+        let rounded = stretch_lut(0, 200, false, Rounding::Nearest);
+
+        // 100 * 255 / 200 = 127.5
+        assert_eq!(truncated[100], 127);
+        assert_eq!(rounded[100], 128);
+        // 99 * 255 / 200 = 126.225
+        assert_eq!(rounded[99], 126);
+        assert_eq!(rounded[0], 0);
+        assert_eq!(rounded[200], 255);
+    }
+
+    #[test]
+    fn stretch_lut_soft_clip() {
+        let hard = stretch_lut(20, 200, false, Rounding::Truncate);
+        let soft = stretch_lut(20, 200, true, Rounding::Truncate);
+
+        // Hard clipping maps everything above upper to white, soft clipping compresses.
+        assert!(hard[200..].iter().all(|&x| x == 255));
+        assert!(soft[200..].iter().all(|&x| x < 255));
+        assert!(soft[200] < soft[230] && soft[230] < soft[255]);
+        assert!(soft[..20].iter().all(|&x| x < 20));
+        // Midtones are unaffected.
+        assert_eq!(soft[110], hard[110]);
+        assert!(soft.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn gamma_lut_protect() {
+        let lut = generate_gamma_lut(ToneCurve::Gamma(0.75), Some((32, 224)), 0.0, 1.0);
+
+        assert_eq!(lut[0], 0);
+        assert_eq!(lut[16], 16);
+        assert_eq!(lut[32], 32);
+        assert!(lut[128] < 128);
+        assert_eq!(lut[224], 224);
+        assert_eq!(lut[240], 240);
+        assert_eq!(lut[255], 255);
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn gamma_lut_srgb() {
+        let lut = generate_gamma_lut(ToneCurve::Srgb, None, 0.0, 1.0);
+
+        assert_eq!(lut[0], 0);
+        assert_eq!(lut[10], 0);
+        assert_eq!(lut[64], 13);
+        assert_eq!(lut[128], 55);
+        assert_eq!(lut[188], 128);
+        assert_eq!(lut[255], 255);
+    }
+
+    #[test]
+    fn gamma_lut_rec709() {
+        let lut = generate_gamma_lut(ToneCurve::Rec709, None, 0.0, 1.0);
+
+        assert_eq!(lut[0], 0);
+        assert_eq!(lut[18], 4);
+        assert_eq!(lut[128], 66);
+        assert_eq!(lut[255], 255);
+    }
+
+    #[test]
+    fn source_gamma_undoes_gamma() {
+        let lut = generate_gamma_lut(ToneCurve::Gamma(2.2), None, 0.0, 2.2);
+        for (i, &v) in lut.iter().enumerate() {
+            assert!(i32::from(v).abs_diff(i as i32) <= 1, "{} -> {}", i, v);
+        }
+
+        // Without the declaration, the gamma modifier applies in full.
+        let image = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).gamma(2.2);
+        let brightened = convert(image.clone(), &params.build());
+        let kept = convert(image.clone(), &params.source_gamma(2.2).build());
+        assert!(brightened[(64, 0)][0] > 120);
+        for (&v, &i) in kept.iter().zip(image.iter()) {
+            assert!(v.abs_diff(i) <= 1, "{} -> {}", i, v);
+        }
+    }
+
+    #[test]
+    fn source_gamma_near_one_is_skipped() {
+        let params = ConvertParams::builder()
+            .gamma(1.0)
+            .source_gamma(1.0005)
+            .build();
+        let curve = |source_gamma| -> Vec<f64> {
+            gamma_curve(ToneCurve::Gamma(1.0), None, 0.0, source_gamma).collect()
+        };
+
+        assert!(gamma_is_identity(&params));
+        assert_eq!(curve(1.0005), curve(1.0));
+    }
+
+    #[test]
+    fn gamma_lut_ink_boost() {
+        let plain = generate_gamma_lut(ToneCurve::Gamma(0.75), None, 0.0, 1.0);
+        let lut = generate_gamma_lut(ToneCurve::Gamma(0.75), None, 0.5, 1.0);
+
+        let linear = generate_gamma_lut(ToneCurve::Linear, None, 0.5, 1.0);
+        assert!(linear[80] < 70);
+        assert!(linear[200] >= 198);
+        assert!(lut[80] < plain[80]);
+        assert_eq!(lut[0], 0);
+        assert_eq!(lut[255], 255);
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+        let strongest = generate_gamma_lut(ToneCurve::Linear, None, 1.0, 1.0);
+        assert!(strongest.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn dither_gamma_reduces_banding() {
+        let gradient = GrayImage::from_fn(256, 64, |x, _| Luma([x as u8]));
+        let column_sums = |image: &GrayImage| -> Vec<u32> {
+            (0..image.width())
+                .map(|x| {
+                    (0..image.height())
+                        .map(|y| u32::from(image[(x, y)][0]))
+                        .sum()
+                })
+                .collect()
+        };
+        let flat_steps = |sums: Vec<u32>| sums.windows(2).filter(|w| w[0] == w[1]).count();
+
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).gamma(0.75);
+        let plain = convert(gradient.clone(), &params.build());
+        let dithered = convert(gradient, &params.dither_gamma(true).build());
+
+        assert!(flat_steps(column_sums(&dithered)) < flat_steps(column_sums(&plain)));
+    }
+
+    #[test]
+    fn local_stretch_evens_lighting() {
+        // Dark left half and bright right half, each with a vertical gradient.
+        let image = GrayImage::from_fn(200, 100, |x, y| {
+            Luma([if x < 100 { 20 } else { 180 } + y as u8 / 2])
+        });
+        let range = |image: &GrayImage, columns: std::ops::Range<u32>| {
+            let values: Vec<u8> = columns
+                .flat_map(|x| (0..image.height()).map(move |y| (x, y)))
+                .map(|(x, y)| image.get_pixel(x, y)[0])
+                .collect();
+            values.iter().max().unwrap() - values.iter().min().unwrap()
+        };
+        let mut params = ConvertParams::builder();
+        params.cutoff(1).gamma(1.0);
+
+        let global = convert(image.clone(), &params.build());
+        let local = convert(image.clone(), &params.local_stretch((2, 1)).build());
+
+        for columns in [0..50, 150..200].iter() {
+            assert!(range(&local, columns.clone()) > 2 * range(&image, columns.clone()));
+            assert!(range(&local, columns.clone()) > range(&global, columns.clone()));
+        }
+    }
+
+    #[test]
+    fn threshold_ignores_gamma() {
+        let image = GrayImage::from_fn(64, 4, |x, y| Luma([(x * 4 + y) as u8]));
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).threshold(128);
+
+        let want = convert(image.clone(), &params.gamma(1.0).build());
+        let got = convert(image, &params.gamma(0.5).build());
+
+        assert!(is_binary(&want));
+        assert_pixels_eq!(got, want);
+    }
+
+    #[test]
+    fn threshold_antialias_keeps_edge_transitions() {
+        // A soft diagonal edge, as produced by scanning or resizing line art.
+        let image = GrayImage::from_fn(32, 32, |x, y| {
+            let d = x as i32 - y as i32;
+            Luma([(128 + d * 32).clamp(0, 255) as u8])
+        });
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).threshold(128);
+
+        let hard = convert(image.clone(), &params.build());
+        let soft = convert(image, &params.threshold_antialias(64).build());
+
+        assert!(is_binary(&hard));
+        let gray: Vec<_> = soft
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[0] != 0 && p[0] != 255)
+            .collect();
+        assert!(!gray.is_empty());
+        // Only pixels next to the edge are gray.
+        assert!(gray
+            .iter()
+            .all(|(x, y, _)| (*x as i32 - *y as i32).abs() <= 1));
+    }
+
+    #[test]
+    fn threshold_lut_ramp_stays_in_range() {
+        for &(threshold, band) in &[(250, 64), (255, 64), (3, 64), (128, 255), (1, 2)] {
+            let lut = threshold_lut(threshold, band);
+            let case = format!("threshold {}, band {}", threshold, band);
+
+            assert_eq!((lut[0], lut[255]), (0, 255), "{}", case);
+            assert!(lut.windows(2).all(|w| w[0] <= w[1]), "{}", case);
+            // The ramp stays centered on the threshold.
+            if (1..255).contains(&threshold) {
+                let t = usize::from(threshold);
+                assert!(lut[t - 1] < 128 && lut[t] >= 128, "{}", case);
+            }
+        }
+        // Strict binarization is unchanged.
+        let lut = threshold_lut(100, 0);
+        assert_eq!((lut[99], lut[100]), (0, 255));
+        // Without room for a ramp, the band has no effect.
+        assert_eq!(threshold_lut(0, 64), threshold_lut(0, 0));
+    }
+
+    #[test]
+    fn binary_image_skips_gamma() {
+        let image = gray_image!(0, 255, 255, 0);
+        let params = ConvertParams::builder().gamma(0.5).build();
+
+        let (got, stats) = convert_with_stats(image.clone(), &params);
+
+        assert!(stats.gamma_skipped);
+        assert_pixels_eq!(got, image);
+    }
+
+    #[test]
+    fn archival_keeps_tones() {
+        let image = GrayImage::from_fn(800, 600, |x, y| Luma([((x + y) % 256) as u8]));
+
+        let (got, stats) = convert_with_stats(image.clone(), &ConvertParams::archival());
+
+        assert!(stats.contrast_skipped);
+        assert!(stats.gamma_skipped);
+        assert_pixels_eq!(got, image);
+    }
+
+    #[test]
+    fn export_lut_image_identity() {
+        let plot = export_lut_image(&ConvertParams::builder().gamma(1.0).build());
+
+        assert_eq!(plot.dimensions(), (256, 256));
+        for (x, y, p) in plot.enumerate_pixels() {
+            assert_eq!(p[0] == 0, y == 255 - x, "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn export_lut_image_connects_points() {
+        let plot = export_lut_image(&ConvertParams::builder().gamma(0.5).build());
+
+        // Every column and row has at least one point, so the curve has no gaps.
+        for i in 0..256 {
+            assert!((0..256).any(|j| plot.get_pixel(i, j)[0] == 0));
+            assert!((0..256).any(|j| plot.get_pixel(j, i)[0] == 0));
+        }
+    }
+
+    #[test]
+    fn validate_reports_all_errors() {
+        let mut params = ConvertParams::builder();
+        params
+            .gamma(f64::NAN)
+            .cutoff(50)
+            .width(0)
+            .gamma_protect(200, 100)
+            .local_stretch((0, 4));
+
+        let errors = params.validate().unwrap_err();
+
+        assert_eq!(errors.len(), 5);
+        assert!(matches!(errors[0], BuildError::InvalidGamma(g) if g.is_nan()));
+        assert_eq!(
+            errors[1..],
+            [
+                BuildError::InvalidCutoff(50),
+                BuildError::ZeroDimension,
+                BuildError::InvalidGammaProtect {
+                    low: 200,
+                    high: 100
+                },
+                BuildError::ZeroTiles,
+            ]
+        );
+        assert!(params.try_build().is_err());
+        assert!(ConvertParams::builder().try_build().is_ok());
+    }
+
+    #[test]
+    fn validate_tells_gammas_apart() {
+        let errors = |gamma, source_gamma| {
+            ConvertParams::builder()
+                .gamma(gamma)
+                .source_gamma(source_gamma)
+                .validate()
+                .unwrap_err()
+        };
+
+        assert_eq!(errors(-1.0, 1.0), vec![BuildError::InvalidGamma(-1.0)]);
+        assert_eq!(errors(1.0, 0.0), vec![BuildError::InvalidSourceGamma(0.0)]);
+        assert_eq!(
+            BuildError::InvalidSourceGamma(0.0).to_string(),
+            "source gamma must be positive and finite, got 0"
+        );
+    }
+
+    #[test]
+    fn looks_converted_detects_converted_pages() {
+        let params = ConvertParams::builder().width(100).height(100).build();
+        let scan = GrayImage::from_fn(150, 200, |x, y| Luma([(80 + (x + y) / 4) as u8]));
+        let converted = convert(scan.clone(), &params);
+
+        assert!(looks_converted(&converted, &params));
+        // Too large, low contrast.
+        assert!(!looks_converted(&scan, &params));
+        let small = imageops::resize(&scan, 75, 100, FilterType::Triangle);
+        assert!(!looks_converted(&small, &params));
+        // Full contrast, but values the gamma stage never produces.
+        let stretched = convert(small, &ConvertParams::builder().gamma(1.0).build());
+        assert!(!looks_converted(&stretched, &params));
+    }
+
+    #[test]
+    fn convert_params_accessors() {
+        let params = ConvertParams::builder()
+            .width(Dimension::Unbounded)
+            .height(800)
+            .upscale(true)
+            .cutoff(3)
+            .gamma(0.9)
+            .filter(FilterType::Gaussian)
+            .build();
+
+        assert_eq!(params.width(), Dimension::Unbounded);
+        assert_eq!(params.height(), Dimension::Exact(800));
+        assert!(params.upscale());
+        assert_eq!(params.cutoff(), 3);
+        assert_eq!(params.gamma(), Some(0.9));
+        assert_eq!(params.effective_filter(), FilterType::Gaussian);
+        let srgb = ConvertParams::builder().tone_curve(ToneCurve::Srgb).build();
+        assert_eq!(srgb.gamma(), None);
+    }
+
+    #[test]
+    fn convert_params_getters() {
+        let params = ConvertParams::builder()
+            .filter(FilterType::Nearest)
+            .custom_filter(CustomFilter::Lanczos { a: 2 })
+            .upscale_method(UpscaleMethod::EdgeDirected)
+            .tone_curve(ToneCurve::Rec709)
+            .gamma_protect(10, 240)
+            .dither_gamma(true)
+            .ink_boost(0.25)
+            .soft_clip(true)
+            .stretch_rounding(Rounding::Nearest)
+            .local_stretch((3, 4))
+            .threshold(100)
+            .max_aspect(2.5)
+            .split_strategy(SplitStrategy::FromTop)
+            .build();
+
+        assert_eq!(params.filter(), FilterType::Nearest);
+        assert_eq!(params.custom_filter(), Some(CustomFilter::Lanczos { a: 2 }));
+        assert_eq!(params.upscale_method(), UpscaleMethod::EdgeDirected);
+        assert_eq!(params.tone_curve(), ToneCurve::Rec709);
+        assert_eq!(
+            params.gamma_lut(),
+            &generate_gamma_lut(ToneCurve::Rec709, Some((10, 240)), 0.25, 1.0)
+        );
+        assert_eq!(params.gamma_protect(), Some((10, 240)));
+        assert!(params.dither_gamma());
+        assert_eq!(params.ink_boost(), 0.25);
+        assert!(params.soft_clip());
+        assert_eq!(params.stretch_rounding(), Rounding::Nearest);
+        assert_eq!(params.local_stretch(), Some((3, 4)));
+        assert_eq!(params.threshold(), Some(100));
+        assert_eq!(params.max_aspect(), Some(2.5));
+        assert_eq!(params.split_strategy(), SplitStrategy::FromTop);
+    }
+
+    #[test]
+    fn preserve_median_keeps_brightness() {
+        // Mostly bright page, with the median at 150.
+        let image = GrayImage::from_fn(100, 100, |x, _| {
+            Luma([if x < 10 { 40 } else { 100 + x as u8 }])
+        });
+        let median = |image: &GrayImage| histogram_percentile(&histogram(image), 50);
+        let mut params = ConvertParams::builder();
+        params.cutoff(1).gamma(1.0);
+
+        let plain = convert(image.clone(), &params.build());
+        let preserved = convert(image.clone(), &params.preserve_median(true).build());
+
+        assert_eq!(median(&image), 149);
+        assert!(median(&plain).abs_diff(149) > 20);
+        assert!(median(&preserved).abs_diff(149) <= 2);
+        assert_eq!(preserved.iter().min(), Some(&0));
+        assert_eq!(preserved.iter().max(), Some(&255));
+    }
+
+    #[test]
+    fn seam_carve_removes_flat_areas() {
+        // Noise with a flat gray band in columns 20..30.
+        let image = GrayImage::from_fn(40, 20, |x, y| {
+            Luma([match x {
+                20..=29 => 128,
+                _ => ((x * 37 + y * 71) ^ (x * y * 13)) as u8,
+            }])
+        });
+        let params = ConvertParams::builder()
+            .width(30)
+            .height(20)
+            .cutoff(0)
+            .gamma(1.0)
+            .seam_carve(true)
+            .build();
+
+        let carved = convert(image, &params);
+
+        assert_eq!(carved.dimensions(), (30, 20));
+        let flat = (0..30)
+            .filter(|&x| (0..20).all(|y| carved.get_pixel(x, y)[0] == 128))
+            .count();
+        assert!(flat <= 2, "{} flat columns left", flat);
+    }
+
+    #[test]
+    fn tint_ramp_sepia() {
+        let sepia = Rgb([112, 66, 20]);
+        let ramp = tint_ramp(sepia);
+        let image = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+        let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+
+        let tinted = convert_tinted(image, &params, sepia);
+
+        assert_eq!(tinted.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(tinted.get_pixel(255, 0), &Rgb([255, 255, 255]));
+        assert_eq!(ramp[128], Rgb([113, 67, 21]));
+        // Every channel gets brighter along the ramp.
+        for (a, b) in ramp.iter().zip(&ramp[1..]) {
+            assert!(a.0.iter().zip(&b.0).all(|(a, b)| a <= b));
+        }
+    }
+
+    #[cfg(feature = "cbz")]
+    #[test]
+    fn resize_color_matches_grayscale() {
+        let gray = GrayImage::from_fn(300, 200, |x, y| Luma([((x * 7 + y * 3) % 256) as u8]));
+        let color = RgbImage::from_fn(300, 200, |x, y| Rgb([gray[(x, y)][0]; 3]));
+        let params = ConvertParams::builder()
+            .width(90)
+            .height(90)
+            .two_pass(true)
+            .custom_filter(CustomFilter::Lanczos { a: 2 })
+            .build();
+
+        let want = resize_only(gray, &params);
+        let got = resize_color(color, &params);
+
+        assert_eq!(got.dimensions(), want.dimensions());
+        assert!(got
+            .enumerate_pixels()
+            .all(|(x, y, p)| p[1] == want[(x, y)][0]));
+    }
+
+    #[test]
+    fn resize_only_skips_tone() {
+        let image = GrayImage::from_fn(300, 200, |x, y| Luma([((x * 7 + y * 3) % 256) as u8]));
+        for upscale in [false, true] {
+            let mut params = ConvertParams::builder();
+            params.width(150).height(400).upscale(upscale);
+
+            let resized = resize_only(image.clone(), &params.build());
+            let converted = convert(image.clone(), &params.cutoff(0).gamma(1.0).build());
+
+            assert_eq!(resized, converted);
+        }
+    }
+
+    #[test]
+    fn tone_only_skips_resize() {
+        let image = GrayImage::from_fn(300, 200, |x, y| Luma([((x + y) / 3 + 40) as u8]));
+        let mut params = ConvertParams::builder();
+        params.width(100).height(100);
+
+        let toned = tone_only(image.clone(), &params.build());
+        let converted = convert(image, &params.width(300).height(200).upscale(false).build());
+
+        assert_eq!(toned.dimensions(), (300, 200));
+        assert_eq!(toned, converted);
+    }
+
+    #[test]
+    fn apply_lut16_clamps_index() {
+        let mut image = ImageBuffer::from_raw(4, 1, vec![0_u16, 255, 256, u16::MAX]).unwrap();
+        let lut: Vec<u16> = (0..256).map(|i| i * 257).collect();
+
+        apply_lut16(&mut image, &lut);
+
+        assert_eq!(image.into_raw(), vec![0, u16::MAX, u16::MAX, u16::MAX]);
+
+        let mut image = ImageBuffer::from_pixel(1, 1, Luma([u16::MAX]));
+        let lut: Vec<u16> = (0..=u16::MAX).rev().collect();
+        apply_lut16(&mut image, &lut);
+        assert_eq!(image[(0, 0)][0], 0);
+
+        apply_lut16(&mut image, &[]);
+        assert_eq!(image[(0, 0)][0], 0);
+    }
+
+    #[test]
+    fn sharpen_downscale_only() {
+        let page =
+            |size: u32| GrayImage::from_fn(size, size, |x, y| Luma([((x * y) % 200) as u8 + 20]));
+        let small = page(50);
+        let large = page(400);
+        let mut params = ConvertParams::builder();
+        params
+            .width(100)
+            .height(100)
+            .upscale(true)
+            .cutoff(0)
+            .gamma(1.0);
+        let plain = params.build();
+        let always = params.sharpen(1.0).build();
+        let downscale_only = params.sharpen_downscale_only(true).build();
+
+        // Upscaled images are left unsharpened.
+        let enlarged = convert(small.clone(), &downscale_only);
+        assert_eq!(enlarged.dimensions(), (100, 100));
+        assert_eq!(enlarged, convert(small.clone(), &plain));
+        assert_ne!(convert(small, &always), enlarged);
+
+        // Downscaled images are sharpened either way.
+        let reduced = convert(large.clone(), &downscale_only);
+        assert_eq!(reduced, convert(large.clone(), &always));
+        assert_ne!(reduced, convert(large, &plain));
+    }
+
+    #[test]
+    fn convert_with_mask_ignores_border() {
+        // A pure black border around a gray interior.
+        let image = GrayImage::from_fn(60, 80, |x, y| {
+            let border = x < 10 || y < 10 || x >= 50 || y >= 70;
+            Luma([if border { 0 } else { (x + y) as u8 + 80 }])
+        });
+        let mask = GrayImage::from_fn(60, 80, |x, y| {
+            let border = x < 10 || y < 10 || x >= 50 || y >= 70;
+            Luma([if border { 0 } else { 255 }])
+        });
+        let interior = imageops::crop_imm(&image, 10, 10, 40, 60).to_image();
+        let params = ConvertParams::builder().cutoff(1).gamma(1.0).build();
+        let bounds = compute_bounds_batch(&[interior], params.cutoff());
+        assert!(bounds.0 > 80);
+
+        let masked = convert_with_mask(image.clone(), &mask, &params);
+
+        assert_eq!(masked, convert_with_bounds(image.clone(), &params, bounds));
+        assert_ne!(masked, convert(image.clone(), &params));
+        // A smaller mask is scaled up to the image.
+        let small_mask = imageops::resize(&mask, 6, 8, FilterType::Nearest);
+        assert_eq!(convert_with_mask(image, &small_mask, &params), masked);
+    }
+
+    #[test]
+    fn noise_dither_is_reproducible() {
+        let gradient = GrayImage::from_fn(256, 32, |x, _| Luma([x as u8]));
+        let mut params = ConvertParams::builder();
+        params
+            .cutoff(0)
+            .gamma(0.6)
+            .dither_gamma(true)
+            .dither_method(DitherMethod::Noise)
+            .dither_seed(42);
+
+        let first = convert(gradient.clone(), &params.build());
+        let second = convert(gradient.clone(), &params.build());
+        let other_seed = convert(gradient.clone(), &params.dither_seed(43).build());
+
+        assert_eq!(first, second);
+        assert_ne!(first, other_seed);
+        assert_ne!(
+            first,
+            convert(gradient, &params.dither_gamma(false).build())
+        );
+    }
+
+    #[test]
+    fn adaptive_gamma_flattens_halves() {
+        // Dark left half, bright right half, both with some texture and the full range present.
+        let image = GrayImage::from_fn(200, 100, |x, y| {
+            let base = if x < 100 { 40 } else { 200 };
+            Luma([match (x, y) {
+                (0, 0) => 0,
+                (199, 99) => 255,
+                _ => base + ((x * 7 + y * 13) % 30) as u8,
+            }])
+        });
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).gamma(1.0);
+        let mean_error = |image: &GrayImage, left: bool| {
+            let half = imageops::crop_imm(image, if left { 0 } else { 100 }, 0, 100, 100);
+            let sum: u32 = half.to_image().iter().map(|&p| u32::from(p)).sum();
+            (sum as f32 / 10_000.0 - 128.0).abs()
+        };
+
+        let plain = convert(image.clone(), &params.build());
+        let adaptive = convert(image, &params.adaptive_gamma(1.0).build());
+
+        assert!(mean_error(&adaptive, true) < mean_error(&plain, true));
+        assert!(mean_error(&adaptive, false) < mean_error(&plain, false));
+    }
+
+    #[test]
+    fn convert_if_changed_detects_changes() {
+        let image = GrayImage::from_fn(64, 32, |x, y| Luma([((x + y) * 4).min(255) as u8]));
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).gamma(1.0);
+
+        let (same, changed) = convert_if_changed(image.clone(), &params.build());
+        assert_eq!(same, image);
+        assert!(!changed);
+
+        let (darker, changed) = convert_if_changed(image.clone(), &params.gamma(0.5).build());
+        assert!(changed);
+        assert_eq!(darker, convert(image.clone(), &params.build()));
+
+        let (smaller, changed) = convert_if_changed(image, &params.gamma(1.0).width(32).build());
+        assert!(changed);
+        assert_eq!(smaller.dimensions(), (32, 16));
+    }
+
+    #[test]
+    fn linear_resize_keeps_thin_lines_bright() {
+        // One pixel wide white lines on black, every fourth column.
+        let image = GrayImage::from_fn(400, 40, |x, _| Luma([if x % 4 == 0 { 255 } else { 0 }]));
+        let mean = |image: &GrayImage| {
+            image.iter().map(|&p| f32::from(p)).sum::<f32>() / image.len() as f32
+        };
+        let mut params = ConvertParams::builder();
+        params.width(100).filter(FilterType::Triangle);
+
+        let plain = resize_only(image.clone(), &params.build());
+        let linear = resize_only(image, &params.linear_resize(true).build());
+
+        assert_eq!(plain.dimensions(), linear.dimensions());
+        assert!((mean(&plain) - 63.75).abs() < 2.0);
+        // A quarter of the light, encoded back into sRGB.
+        assert!((mean(&linear) - 137.0).abs() < 4.0);
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn tone_lut_matches_tone_stages() {
+        let image = GrayImage::from_fn(64, 48, |x, y| Luma([(x * 2 + y) as u8 + 30]));
+        let mut params = ConvertParams::builder();
+        let check = |params: &ConvertParams| {
+            let mut image = image.clone();
+            let lut = tone_lut(&image, params).expect("stages should form a lookup table");
+            let want = tone_only(image.clone(), params);
+            apply_lut(&mut image, &lut);
+            assert_eq!(image, want);
+        };
+
+        check(&params.build());
+        check(&params.preserve_median(true).soft_clip(true).build());
+        check(&params.ink_boost(0.5).gamma_protect(10, 200).build());
+        check(&params.threshold(128).build());
+        assert!(tone_lut(&image, &params.dither_gamma(true).build()).is_some());
+        let params = ConvertParams::builder().dither_gamma(true).build();
+        assert!(tone_lut(&image, &params).is_none());
+    }
+
+    #[test]
+    fn estimate_memory_counts_buffers() {
+        let params = ConvertParams::builder().width(100).height(100).build();
+        assert_eq!(estimate_memory(100, 50, &params), 5000);
+        // The downscaled output and the intermediate come on top of the source.
+        assert!(estimate_memory(400, 200, &params) > 400 * 200 + 100 * 50);
+
+        let mut sharpened = ConvertParams::builder();
+        sharpened.width(100).height(100).sharpen(1.0);
+        assert!(estimate_memory(100, 50, &sharpened.build()) > 5000);
+    }
+
+    #[test]
+    fn ringing_fallback_reduces_overshoot() {
+        let image = GrayImage::from_fn(100, 10, |x, _| Luma([if x < 47 { 40 } else { 215 }]));
+        let mut params = ConvertParams::builder();
+        params.width(30).height(30).filter(FilterType::Lanczos3);
+        let overshoot = |image: &GrayImage| {
+            let max = image.iter().max().unwrap() - 215;
+            let min = 40 - image.iter().min().unwrap();
+            max.max(min)
+        };
+
+        let lanczos = resize_only(image.clone(), &params.build());
+        let fallback = resize_only(image, &params.ringing_fallback(true).build());
+
+        assert!(overshoot(&lanczos) > 0);
+        assert!(overshoot(&fallback) < overshoot(&lanczos));
+    }
+
+    #[test]
+    fn convert_raw_matches_convert() {
+        let image = GrayImage::from_fn(40, 30, |x, y| Luma([(x * 5 + y) as u8]));
+        let params = ConvertParams::builder().width(20).height(20).build();
+
+        let want = convert(image.clone(), &params);
+        let (pixels, width, height) = convert_raw(&image, 40, 30, &params).unwrap();
+
+        assert_eq!((width, height), want.dimensions());
+        assert_eq!(pixels, want.into_raw());
+        match convert_raw(&image.as_raw()[..100], 40, 30, &params) {
+            Err(Error::BufferSize { len, expected }) => assert_eq!((len, expected), (100, 1200)),
+            other => panic!("expected Error::BufferSize, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn contrast_method_specify_matches_target() {
+        // Mostly dark, with values clustered towards black.
+        let image = GrayImage::from_fn(256, 64, |x, y| Luma([((x * x + y) / 256) as u8]));
+        let params = ConvertParams::builder()
+            .contrast_method(ContrastMethod::Specify(Box::new([1; 256])))
+            .gamma(1.0)
+            .build();
+
+        let got = convert(image, &params);
+
+        let hist = histogram(&got);
+        let total: u64 = hist.iter().sum();
+        for bucket in hist.chunks(32) {
+            let share = bucket.iter().sum::<u64>() as f64 / total as f64;
+            assert!((share - 0.125).abs() < 0.03, "share was {}", share);
+        }
+
+        let empty = ConvertParams::builder()
+            .contrast_method(ContrastMethod::Specify(Box::new([0; 256])))
+            .gamma(1.0)
+            .build();
+        let flat = gray_image!(10, 20, 30);
+        assert_pixels_eq!(convert(flat.clone(), &empty), flat);
+    }
+
+    #[test]
+    fn fused_linear_matches_sequential() {
+        let image = GrayImage::from_fn(90, 60, |x, y| Luma([((x * 2 + y) / 2) as u8 + 20]));
+        let mut params = ConvertParams::builder();
+        params.width(40).height(40).linear_resize(true).gamma(0.6);
+
+        for params in [&params.build(), &params.preserve_median(true).build()].iter() {
+            let (fused, stages) = fused_linear_stage(&image, params).unwrap();
+            let mut sequential = resize_stage(image.clone(), params);
+            let want = adjust_tone(&mut sequential, params);
+            assert_pixels_eq!(fused, sequential);
+            assert_eq!((stages.bounds, stages.gamma), (want.bounds, want.gamma));
+        }
+        assert!(fused_linear_stage(&image, &params.sharpen(1.0).build()).is_none());
+    }
+
+    #[test]
+    fn auto_invert_flips_negatives() {
+        let text = GrayImage::from_fn(200, 200, |x, y| {
+            let (gx, gy) = (x % 8, y % 14);
+            let stroke = (8..192).contains(&x)
+                && (8..192).contains(&y)
+                && gy < 9
+                && (gx < 2 || (gy == 4 && gx < 6));
+            Luma([if stroke { 25 } else { 235 }])
+        });
+        let mut negative = text.clone();
+        imageops::invert(&mut negative);
+        // A dark panel with bright lettering above regular text.
+        let panel = GrayImage::from_fn(200, 200, |x, y| {
+            if y < 120 {
+                negative[(x, y)]
+            } else {
+                text[(x, y)]
+            }
+        });
+        let params = ConvertParams::builder().auto_invert(true).build();
+
+        let flipped = resize_only(negative, &params);
+        assert_pixels_eq!(flipped, text);
+        let kept = resize_only(panel.clone(), &params);
+        assert_pixels_eq!(kept, panel);
+    }
+
+    #[test]
+    fn min_contrast_forces_stretch() {
+        // Percentile bounds both fall on 121.
+        let image = GrayImage::from_fn(100, 100, |x, y| {
+            Luma([match (x, y) {
+                (0, 0) => 119,
+                (1, 0) => 124,
+                _ => 121,
+            }])
+        });
+        let mut params = ConvertParams::builder();
+        params.gamma(1.0);
+
+        let flat = convert(image.clone(), &params.build());
+        let forced = convert(image, &params.min_contrast(50).build());
+
+        assert_eq!(flat.iter().max().unwrap() - flat.iter().min().unwrap(), 5);
+        assert!(forced.iter().max().unwrap() - forced.iter().min().unwrap() >= 50);
+    }
+
+    #[test]
+    fn convert_with_progress_reports_stages_in_order() {
+        let image = GrayImage::from_fn(40, 40, |x, y| Luma([(x + y) as u8 + 50]));
+        let params = ConvertParams::builder().width(20).height(20).build();
+        let mut stages = Vec::new();
+
+        let got = convert_with_progress(image.clone(), &params, |stage| stages.push(stage));
+
+        let want = convert(image, &params);
+        assert_pixels_eq!(got, want);
+        let steps = [Step::Resize, Step::Contrast, Step::Gamma];
+        let order: Vec<_> = steps
+            .iter()
+            .flat_map(|&step| vec![Stage::Started(step), Stage::Finished(step)])
+            .collect();
+        assert_eq!(stages, order);
+    }
+
+    #[test]
+    fn contrast_bounds_mean_std() {
+        // Approximately normally distributed around 128 with a standard deviation of 20, as the
+        // sum of 12 pseudo-random uniform values.
+        let mut state = 1_u32;
+        let mut uniform = || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            f64::from(state >> 8) / f64::from(1_u32 << 24)
+        };
+        let image = GrayImage::from_fn(200, 200, |_, _| {
+            let sum: f64 = (0..12).map(|_| uniform()).sum();
+            Luma([(128.0 + 20.0 * (sum - 6.0)).round() as u8])
+        });
+        let mut params = ConvertParams::builder();
+        params.cutoff(2);
+
+        let (_, percentile) = convert_with_report(image.clone(), &params.build());
+        let mean_std = ConvertParams::builder()
+            .contrast_bounds(ContrastBounds::MeanStd { k: 2.0 })
+            .build();
+        let (_, report) = convert_with_report(image, &mean_std);
+
+        let (lower, upper) = report.stretch_bounds.unwrap();
+        assert!((i32::from(lower) - 88).abs() <= 2, "lower was {}", lower);
+        assert!((i32::from(upper) - 168).abs() <= 2, "upper was {}", upper);
+        // Two standard deviations cover about 95.4% of a normal distribution, close to the 2nd
+        // and 98th percentiles.
+        let (p_lower, p_upper) = percentile.stretch_bounds.unwrap();
+        assert!((i32::from(lower) - i32::from(p_lower)).abs() <= 3);
+        assert!((i32::from(upper) - i32::from(p_upper)).abs() <= 3);
+    }
+
+    #[test]
+    fn resize_edge_replicate_avoids_border() {
+        // A dark band touching the left edge and a light area touching the right edge.
+        let image = GrayImage::from_fn(200, 40, |x, _| Luma([if x < 60 { 30 } else { 220 }]));
+        let params = ConvertParams::builder()
+            .width(20)
+            .height(20)
+            .filter(FilterType::Lanczos3)
+            .cutoff(0)
+            .resize_edge(EdgeMode::Replicate)
+            .build();
+
+        let resized = resize_stage(image, &params);
+
+        assert_eq!(resized.dimensions(), (20, 4));
+        for y in 0..4 {
+            for x in 0..3 {
+                assert_eq!(resized[(x, y)][0], 30, "({}, {})", x, y);
+            }
+            for x in 10..20 {
+                assert_eq!(resized[(x, y)][0], 220, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn power_of_two_rounds_dimensions_down() {
+        let params = ConvertParams::builder()
+            .width(Dimension::Unbounded)
+            .height(Dimension::Unbounded)
+            .power_of_two(true)
+            .build();
+
+        assert_eq!(target_dimensions(1000, 1500, &params), Some((512, 1024)));
+        assert_eq!(target_dimensions(512, 1024, &params), None);
+        assert_eq!(
+            convert(GrayImage::new(1000, 1500), &params).dimensions(),
+            (512, 1024)
+        );
+        assert_eq!(previous_power_of_two(1), 1);
+        assert_eq!(previous_power_of_two(1023), 512);
+        assert_eq!(previous_power_of_two(1024), 1024);
+    }
+
+    #[test]
+    fn add_grain_breaks_up_banding() {
+        let gradient = GrayImage::from_fn(1024, 8, |x, _| Luma([(x / 4) as u8]));
+        let levels = |image: &GrayImage| {
+            let mut seen = [false; 256];
+            image.iter().for_each(|&p| seen[p as usize] = true);
+            seen.iter().filter(|&&s| s).count()
+        };
+        let mut params = ConvertParams::builder();
+        params.cutoff(0).gamma(0.5);
+
+        let plain = convert(gradient.clone(), &params.build());
+        let grainy = convert(gradient.clone(), &params.add_grain(4).build());
+
+        assert!(levels(&grainy) > levels(&plain));
+        assert_eq!(convert(gradient, &params.build()), grainy);
+    }
+
+    #[test]
+    fn target_physical_computes_pixels() {
+        // A4 at 150 dpi.
+        let params = ConvertParams::builder()
+            .target_physical(210.0, 297.0, 150)
+            .build();
+
+        assert_eq!(params.width(), Dimension::Exact(1240));
+        assert_eq!(params.height(), Dimension::Exact(1754));
+
+        let params = ConvertParams::builder()
+            .target_physical(0.01, 297.0, 150)
+            .build();
+        assert_eq!(params.width(), Dimension::Exact(1));
+        for (width_mm, dpi) in [(-10.0, 150), (f32::NAN, 150), (210.0, 0)] {
+            let errors = ConvertParams::builder()
+                .target_physical(width_mm, 297.0, dpi)
+                .try_build()
+                .unwrap_err();
+            assert_eq!(errors, vec![BuildError::ZeroDimension]);
+        }
+    }
+
+    #[test]
+    fn two_pass_reduces_aliasing() {
+        // Alternating black and white columns, reduced 12 times.
+        let image = GrayImage::from_fn(1200, 60, |x, _| Luma([if x % 2 == 0 { 0 } else { 255 }]));
+        // The pattern should average out to mid gray; aliasing shows up as solid black or white.
+        let error = |image: &GrayImage| {
+            image
+                .iter()
+                .map(|&p| i32::from(p).abs_diff(128))
+                .max()
+                .unwrap()
+        };
+        let mut params = ConvertParams::builder();
+        params
+            .width(100)
+            .height(100)
+            .filter(FilterType::Nearest)
+            .cutoff(0)
+            .gamma(1.0);
+
+        let single = resize_stage(image.clone(), &params.build());
+        let double = resize_stage(image, &params.two_pass(true).build());
+
+        assert_eq!(single.dimensions(), double.dimensions());
+        assert!(error(&single) >= 127);
+        assert!(error(&double) <= 2);
+    }
+
+    #[test]
+    fn convert_params_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ConvertParams>();
+    }
+
+    #[test]
+    fn fit_square_into_wide_box_is_height_limited() {
+        assert_eq!(
+            ((50, 50), Constraint::Height),
+            fit(100, 100, 70.into(), 50.into())
+        );
+    }
+
+    #[test]
+    fn fit_square_into_tall_box_is_width_limited() {
+        assert_eq!(
+            ((50, 50), Constraint::Width),
+            fit(100, 100, 50.into(), 70.into())
+        );
     }
 }
@@ -0,0 +1,164 @@
+//! Device profiles, describing the screens converted images are meant for.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{ConvertParams, ConvertParamsBuilder, Error};
+
+/// Screen size and preferred conversion settings of a reading device.
+///
+/// Profiles are defined in TOML files as an array of `device` tables, so that new devices can be
+/// added without recompiling:
+///
+/// ```toml
+/// [[device]]
+/// name = "Kobo Clara HD"
+/// width = 1072
+/// height = 1448
+/// gamma = 0.75
+/// cutoff = 1
+/// ```
+///
+/// `gamma` and `cutoff` are optional and default to the values of
+/// [`ConvertParamsBuilder::default()`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeviceProfile {
+    /// Name the device is selected by.
+    pub name: String,
+    /// Screen width in pixels.
+    pub width: u32,
+    /// Screen height in pixels.
+    pub height: u32,
+    /// Gamma exponent suited to the screen.
+    pub gamma: Option<f64>,
+    /// Contrast stretch cutoff suited to the screen.
+    pub cutoff: Option<u8>,
+}
+
+#[derive(Deserialize)]
+struct DeviceFile {
+    #[serde(default)]
+    device: Vec<DeviceProfile>,
+}
+
+impl DeviceProfile {
+    /// Parses device definitions in TOML format, returning the one with the given name.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Toml`] if definitions are malformed, or [`Error::UnknownDevice`] if
+    /// none of them has the given name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use maco::DeviceProfile;
+    ///
+    /// let toml = r#"
+    ///     [[device]]
+    ///     name = "small"
+    ///     width = 600
+    ///     height = 800
+    /// "#;
+    /// let device = DeviceProfile::from_str(toml, "small")?;
+    ///
+    /// assert_eq!((device.width, device.height), (600, 800));
+    /// # Ok::<(), maco::Error>(())
+    /// ```
+    pub fn from_str(toml: &str, name: &str) -> Result<DeviceProfile, Error> {
+        let file: DeviceFile = toml::from_str(toml)?;
+        file.device
+            .into_iter()
+            .find(|device| device.name == name)
+            .ok_or_else(|| Error::UnknownDevice(name.to_string()))
+    }
+
+    /// Reads device definitions in TOML format from a file, returning the one with the given
+    /// name.
+    ///
+    /// See also: [`DeviceProfile::from_str()`].
+    pub fn from_file(path: impl AsRef<Path>, name: &str) -> Result<DeviceProfile, Error> {
+        DeviceProfile::from_str(&std::fs::read_to_string(path)?, name)
+    }
+
+    /// Returns a [`ConvertParamsBuilder`] targeting this device, which can be further customized.
+    pub fn builder(&self) -> ConvertParamsBuilder {
+        let mut builder = ConvertParams::builder();
+        builder.width(self.width).height(self.height);
+        if let Some(gamma) = self.gamma {
+            builder.gamma(gamma);
+        }
+        if let Some(cutoff) = self.cutoff {
+            builder.cutoff(cutoff);
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICES: &str = r#"
+        [[device]]
+        name = "Kindle Paperwhite"
+        width = 1072
+        height = 1448
+        gamma = 0.8
+
+        [[device]]
+        name = "Kobo Elipsa"
+        width = 1404
+        height = 1872
+        gamma = 0.7
+        cutoff = 2
+    "#;
+
+    #[test]
+    fn from_str_selects_device() {
+        let device = DeviceProfile::from_str(DEVICES, "Kobo Elipsa").unwrap();
+
+        assert_eq!(
+            device,
+            DeviceProfile {
+                name: "Kobo Elipsa".to_string(),
+                width: 1404,
+                height: 1872,
+                gamma: Some(0.7),
+                cutoff: Some(2),
+            }
+        );
+        let params = device.builder().build();
+        assert_eq!((params.gamma(), params.cutoff()), (Some(0.7), 2));
+
+        let params = DeviceProfile::from_str(DEVICES, "Kindle Paperwhite")
+            .unwrap()
+            .builder()
+            .build();
+        assert_eq!(params.cutoff(), ConvertParams::default().cutoff());
+    }
+
+    #[test]
+    fn from_str_errors() {
+        assert!(matches!(
+            DeviceProfile::from_str(DEVICES, "Nook"),
+            Err(Error::UnknownDevice(name)) if name == "Nook"
+        ));
+        assert!(matches!(
+            DeviceProfile::from_str("[[device]]\nname = 1", "Nook"),
+            Err(Error::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("devices.toml");
+        std::fs::write(&path, DEVICES).unwrap();
+
+        let device = DeviceProfile::from_file(&path, "Kindle Paperwhite").unwrap();
+
+        assert_eq!(device.width, 1072);
+    }
+}
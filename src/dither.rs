@@ -0,0 +1,122 @@
+use image::GrayImage;
+
+/// Defines how dithering spreads quantization error.
+///
+/// See also: [`ConvertParamsBuilder::dither_gamma()`](crate::ConvertParamsBuilder::dither_gamma).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMethod {
+    /// Floyd–Steinberg error diffusion. Deterministic, but can form worm-like patterns.
+    FloydSteinberg,
+    /// Random noise added before rounding, seeded with
+    /// [`ConvertParamsBuilder::dither_seed()`](crate::ConvertParamsBuilder::dither_seed). Output
+    /// is reproducible for a fixed seed.
+    Noise,
+}
+
+/// Quantizes an image using Floyd–Steinberg error diffusion.
+///
+/// For each pixel, `target` returns the desired, unquantized output value and `quantize` maps it to
+/// the closest representable one. The quantization error is spread onto neighboring pixels, which
+/// preserves the average tone of an area even if individual values can't be represented.
+pub(crate) fn floyd_steinberg<T, Q>(image: &mut GrayImage, target: T, quantize: Q)
+where
+    T: Fn(u8) -> f32,
+    Q: Fn(f32) -> u8,
+{
+    let width = image.width() as usize;
+    // Errors carried over to the current and next row. Padded by one pixel on each side to avoid
+    // bounds checks at the edges.
+    let mut current = vec![0_f32; width + 2];
+    let mut next = vec![0_f32; width + 2];
+
+    for row in image.chunks_mut(width.max(1)) {
+        for (x, p) in row.iter_mut().enumerate() {
+            let want = target(*p) + current[x + 1];
+            let got = quantize(want);
+            let error = want - f32::from(got);
+            *p = got;
+
+            current[x + 2] += error * 7.0 / 16.0;
+            next[x] += error * 3.0 / 16.0;
+            next[x + 1] += error * 5.0 / 16.0;
+            next[x + 2] += error / 16.0;
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|e| *e = 0.0);
+    }
+}
+
+/// Quantizes an image by adding uniform noise in range `-0.5..0.5` to each target value before
+/// quantizing it. Noise is generated from `seed`, so equal seeds give equal output.
+pub(crate) fn noise<T, Q>(image: &mut GrayImage, target: T, quantize: Q, seed: u64)
+where
+    T: Fn(u8) -> f32,
+    Q: Fn(f32) -> u8,
+{
+    let mut rng = SplitMix64(seed);
+    for p in image.iter_mut() {
+        *p = quantize(target(*p) + rng.next_f32() - 0.5);
+    }
+}
+
+/// Adds uniform noise in range `-amount / 2..amount / 2` to each pixel, maps the result through
+/// `curve`, interpolating between its entries, and rounds it. Noise is generated from `seed`, so
+/// equal seeds give equal output.
+pub(crate) fn grain(image: &mut GrayImage, curve: &[f32; 256], amount: u8, seed: u64) {
+    let mut rng = SplitMix64(seed);
+    let amount = f32::from(amount);
+    for p in image.iter_mut() {
+        let x = (f32::from(*p) + (rng.next_f32() - 0.5) * amount).clamp(0.0, 255.0);
+        let i = (x as usize).min(254);
+        let v = curve[i] + (curve[i + 1] - curve[i]) * (x - i as f32);
+        *p = v.round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Small, fast pseudorandom number generator. Not suitable for cryptography.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in range `0..1`.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1_u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn floyd_steinberg_preserves_average() {
+        let mut image = GrayImage::from_pixel(16, 16, Luma([0]));
+
+        floyd_steinberg(&mut image, |_| 63.75, |v| if v < 127.5 { 0 } else { 255 });
+
+        let sum: u32 = image.pixels().map(|p| u32::from(p[0])).sum();
+        let mean = sum as f32 / 256.0;
+        assert!((mean - 63.75).abs() < 4.0, "mean was {}", mean);
+    }
+
+    #[test]
+    fn noise_preserves_average() {
+        let mut image = GrayImage::from_pixel(64, 64, Luma([0]));
+
+        noise(&mut image, |_| 100.3, |v| v.round() as u8, 7);
+
+        let sum: u32 = image.pixels().map(|p| u32::from(p[0])).sum();
+        let mean = sum as f32 / 4096.0;
+        assert!((mean - 100.3).abs() < 0.05, "mean was {}", mean);
+        assert!(image.iter().all(|&p| p == 100 || p == 101));
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use image::ImageError;
+
+/// Errors which can occur when loading, converting or saving images.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O operation failed.
+    Io(std::io::Error),
+    /// An image couldn't be decoded or encoded.
+    Image(ImageError),
+    /// An image has more pixels than allowed.
+    TooLarge {
+        /// Number of pixels of the image.
+        pixels: u64,
+        /// Maximum allowed number of pixels.
+        limit: u64,
+    },
+    /// A CBZ archive couldn't be read or written.
+    #[cfg(feature = "cbz")]
+    Zip(zip::result::ZipError),
+    /// An image doesn't fit into a texture atlas.
+    AtlasFull {
+        /// Index of the first image which didn't fit.
+        index: usize,
+    },
+    /// Device definitions couldn't be parsed.
+    #[cfg(feature = "devices")]
+    Toml(toml::de::Error),
+    /// No device definition has the requested name.
+    #[cfg(feature = "devices")]
+    UnknownDevice(String),
+    /// The output path of a conversion refers to its input, which would be overwritten.
+    SamePath(std::path::PathBuf),
+    /// Several outputs would be saved to the same path, overwriting each other.
+    DuplicatePath(std::path::PathBuf),
+    /// A raw pixel buffer doesn't match the given dimensions.
+    BufferSize {
+        /// Length of the buffer.
+        len: usize,
+        /// Length expected from the dimensions.
+        expected: u64,
+    },
+    /// A thread pool for parallel conversion couldn't be created.
+    #[cfg(feature = "rayon")]
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {}", e),
+            Error::Image(e) => write!(f, "image error: {}", e),
+            Error::TooLarge { pixels, limit } => write!(
+                f,
+                "image has {} pixels, which exceeds the limit of {}",
+                pixels, limit
+            ),
+            #[cfg(feature = "cbz")]
+            Error::Zip(e) => write!(f, "zip error: {}", e),
+            Error::AtlasFull { index } => write!(f, "image {} doesn't fit into the atlas", index),
+            #[cfg(feature = "devices")]
+            Error::Toml(e) => write!(f, "toml error: {}", e),
+            #[cfg(feature = "devices")]
+            Error::UnknownDevice(name) => write!(f, "unknown device {:?}", name),
+            Error::SamePath(path) => write!(f, "refusing to overwrite input {}", path.display()),
+            Error::DuplicatePath(path) => {
+                write!(f, "refusing to save several outputs to {}", path.display())
+            }
+            Error::BufferSize { len, expected } => {
+                write!(f, "pixel buffer has {} bytes, expected {}", len, expected)
+            }
+            #[cfg(feature = "rayon")]
+            Error::ThreadPool(e) => write!(f, "thread pool error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Image(e) => Some(e),
+            #[cfg(feature = "cbz")]
+            Error::Zip(e) => Some(e),
+            #[cfg(feature = "devices")]
+            Error::Toml(e) => Some(e),
+            #[cfg(feature = "rayon")]
+            Error::ThreadPool(e) => Some(e),
+            #[cfg(feature = "devices")]
+            Error::UnknownDevice(_) => None,
+            Error::TooLarge { .. }
+            | Error::AtlasFull { .. }
+            | Error::SamePath(_)
+            | Error::DuplicatePath(_)
+            | Error::BufferSize { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+#[cfg(feature = "cbz")]
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
+
+#[cfg(feature = "devices")]
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Toml(e)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl From<rayon::ThreadPoolBuildError> for Error {
+    fn from(e: rayon::ThreadPoolBuildError) -> Self {
+        Error::ThreadPool(e)
+    }
+}
+
+/// Problems with [`ConvertParamsBuilder`](crate::ConvertParamsBuilder) settings.
+///
+/// See also: [`ConvertParamsBuilder::validate()`](crate::ConvertParamsBuilder::validate).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildError {
+    /// Gamma is not a positive, finite number.
+    InvalidGamma(f64),
+    /// The gamma set by
+    /// [`ConvertParamsBuilder::source_gamma()`](crate::ConvertParamsBuilder::source_gamma) is not
+    /// a positive, finite number.
+    InvalidSourceGamma(f64),
+    /// Cutoff is 50% or more, which leaves no pixels to compute the contrast stretch from.
+    InvalidCutoff(u8),
+    /// A target dimension is 0. Use [`Dimension::Unbounded`](crate::Dimension::Unbounded) to lift
+    /// the limit instead.
+    ZeroDimension,
+    /// The lower bound of the gamma protect range isn't lower than the upper bound.
+    InvalidGammaProtect {
+        /// Lower bound of the range.
+        low: u8,
+        /// Upper bound of the range.
+        high: u8,
+    },
+    /// Local contrast stretch uses 0 tiles along an axis.
+    ZeroTiles,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidGamma(gamma) => {
+                write!(f, "gamma must be positive and finite, got {}", gamma)
+            }
+            BuildError::InvalidSourceGamma(gamma) => {
+                write!(f, "source gamma must be positive and finite, got {}", gamma)
+            }
+            BuildError::InvalidCutoff(cutoff) => {
+                write!(f, "cutoff must be lower than 50%, got {}%", cutoff)
+            }
+            BuildError::ZeroDimension => write!(f, "target dimensions must not be 0"),
+            BuildError::InvalidGammaProtect { low, high } => write!(
+                f,
+                "gamma protect range must have low < high, got {}..{}",
+                low, high
+            ),
+            BuildError::ZeroTiles => write!(f, "local stretch needs at least 1 tile per axis"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
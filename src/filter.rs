@@ -0,0 +1,81 @@
+use image::GrayImage;
+use imageproc::filter::Kernel;
+
+/// Convolves an image with a kernel of `size.0` x `size.1` values, given in row-major order.
+///
+/// This is a primitive for custom pipelines, e.g. applying a particular sharpen, emboss or edge
+/// detection kernel, complementing
+/// [`ConvertParamsBuilder::sharpen()`](crate::ConvertParamsBuilder::sharpen). The kernel is
+/// centered on each pixel, with pixels beyond the image borders repeating the nearest edge
+/// pixel. Results are rounded and clamped to `0..=255`.
+///
+/// # Panics
+///
+/// Panics if either dimension of `size` is zero or the length of `kernel` doesn't match `size`.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::convolve;
+///
+/// let image = GrayImage::from_pixel(4, 4, Luma([100]));
+/// let blur = [1.0 / 9.0; 9];
+///
+/// assert_eq!(convolve(&image, &blur, (3, 3)), image);
+/// ```
+pub fn convolve(image: &GrayImage, kernel: &[f32], size: (u32, u32)) -> GrayImage {
+    let (width, height) = size;
+    assert!(
+        width > 0 && height > 0 && kernel.len() as u64 == u64::from(width) * u64::from(height),
+        "kernel has {} values, expected {}x{}",
+        kernel.len(),
+        width,
+        height
+    );
+    // Kernel::filter correlates, convolving is correlating with the flipped kernel.
+    let flipped: Vec<f32> = kernel.iter().rev().copied().collect();
+    Kernel::new(&flipped, width, height).filter(image, |channel, acc: f32| {
+        *channel = acc.round().clamp(0.0, 255.0) as u8;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::{assert_pixels_eq, gray_image};
+
+    use super::*;
+
+    #[test]
+    fn convolve_sharpen_kernel() {
+        let image = gray_image!(
+            10, 20, 30;
+            40, 50, 60;
+            70, 80, 90);
+        let sharpen = [0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0];
+
+        let got = convolve(&image, &sharpen, (3, 3));
+
+        let want = gray_image!(
+            0,  0,   10;
+            30, 50,  70;
+            90, 110, 130);
+        assert_pixels_eq!(got, want);
+    }
+
+    #[test]
+    fn convolve_flips_kernel() {
+        let image = gray_image!(0, 100, 0);
+
+        // Picks the left neighbor when convolving, the right one when correlating.
+        let got = convolve(&image, &[0.0, 0.0, 1.0], (3, 1));
+
+        assert_pixels_eq!(got, gray_image!(0, 0, 100));
+    }
+
+    #[test]
+    #[should_panic(expected = "kernel has 8 values, expected 3x3")]
+    fn convolve_rejects_mismatched_kernel() {
+        convolve(&GrayImage::new(2, 2), &[0.0; 8], (3, 3));
+    }
+}
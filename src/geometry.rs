@@ -0,0 +1,121 @@
+/// An axis-aligned rectangle in image coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    /// Horizontal position of the left edge.
+    pub x: u32,
+    /// Vertical position of the top edge.
+    pub y: u32,
+    /// Width of the rectangle.
+    pub width: u32,
+    /// Height of the rectangle.
+    pub height: u32,
+}
+
+impl Rect {
+    /// Creates a rectangle with the given position and size.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the horizontal position just past the right edge.
+    pub fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// Returns the vertical position just past the bottom edge.
+    pub fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    /// Returns whether the rectangle covers no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns the area covered by both rectangles, or `None` if they don't overlap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use maco::Rect;
+    ///
+    /// let a = Rect::new(0, 0, 10, 10);
+    ///
+    /// assert_eq!(a.intersect(&Rect::new(5, 8, 10, 10)), Some(Rect::new(5, 8, 5, 2)));
+    /// assert_eq!(a.intersect(&Rect::new(10, 0, 5, 5)), None);
+    /// ```
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(Rect::new(x, y, right - x, bottom - y))
+    }
+
+    /// Returns the part of the rectangle which lies within an image of the given dimensions.
+    ///
+    /// Rectangles entirely outside the image become empty, positioned at the nearest edge.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use maco::Rect;
+    ///
+    /// assert_eq!(Rect::new(5, 5, 10, 10).clamp_to(8, 20), Rect::new(5, 5, 3, 10));
+    /// ```
+    pub fn clamp_to(&self, width: u32, height: u32) -> Rect {
+        let (x, y) = (self.x.min(width), self.y.min(height));
+        Rect::new(
+            x,
+            y,
+            self.right().min(width) - x,
+            self.bottom().min(height) - y,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersect() {
+        let a = Rect::new(10, 20, 30, 40);
+
+        assert_eq!(a.intersect(&a), Some(a));
+        assert_eq!(
+            a.intersect(&Rect::new(0, 0, 15, 100)),
+            Some(Rect::new(10, 20, 5, 40))
+        );
+        assert_eq!(
+            a.intersect(&Rect::new(20, 30, 5, 5)),
+            Some(Rect::new(20, 30, 5, 5))
+        );
+        assert_eq!(a.intersect(&Rect::new(40, 20, 10, 10)), None);
+        assert_eq!(a.intersect(&Rect::new(0, 0, 100, 20)), None);
+        assert_eq!(a.intersect(&Rect::new(15, 25, 0, 5)), None);
+    }
+
+    #[test]
+    fn clamp_to() {
+        let a = Rect::new(10, 20, 30, 40);
+
+        assert_eq!(a.clamp_to(100, 100), a);
+        assert_eq!(a.clamp_to(25, 50), Rect::new(10, 20, 15, 30));
+        assert_eq!(a.clamp_to(5, 50), Rect::new(5, 20, 0, 30));
+        assert!(a.clamp_to(5, 5).is_empty());
+        assert_eq!(
+            Rect::new(u32::MAX - 1, 0, 10, 10).clamp_to(u32::MAX, 5),
+            Rect::new(u32::MAX - 1, 0, 1, 5)
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Applying the tone stages on the GPU.
+//!
+//! The contrast stretch and tone curve of [`convert()`](crate::convert) map each value
+//! independently, so they combine into a single lookup table which a compute shader applies to
+//! all pixels in parallel. Resizing stays on the CPU.
+
+use std::error::Error;
+use std::sync::mpsc;
+
+use image::GrayImage;
+use log::debug;
+use wgpu::util::DeviceExt;
+
+use crate::convert::{apply_lut, tone_lut};
+use crate::{resize_only, tone_only, ConvertParams};
+
+/// Compute shader mapping pixels, packed four to a word, through a lookup table.
+const SHADER: &str = r#"
+@group(0) @binding(0) var<storage, read> lut: array<u32, 256>;
+@group(0) @binding(1) var<storage, read_write> pixels: array<u32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = id.y * groups.x * 64u + id.x;
+    if (i >= arrayLength(&pixels)) {
+        return;
+    }
+    let p = pixels[i];
+    pixels[i] = lut[p & 0xffu]
+        | (lut[(p >> 8u) & 0xffu] << 8u)
+        | (lut[(p >> 16u) & 0xffu] << 16u)
+        | (lut[p >> 24u] << 24u);
+}
+"#;
+
+/// Number of invocations per workgroup, matching the shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Largest number of workgroups dispatched along one dimension.
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// A GPU device prepared for converting images.
+///
+/// Creating a context is expensive, so create one and reuse it for all images.
+pub struct GpuContext {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl GpuContext {
+    /// Connects to the default GPU, returning `None` if none is available.
+    pub fn new() -> Option<GpuContext> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .ok()?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("maco tone"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("maco tone"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Some(GpuContext {
+            device,
+            queue,
+            pipeline,
+        })
+    }
+
+    /// Maps every pixel of an image through a lookup table. The image is left unchanged if the GPU
+    /// fails.
+    fn apply_lut(&self, image: &mut GrayImage, lut: &[u8; 256]) -> Result<(), Box<dyn Error>> {
+        if image.is_empty() {
+            return Ok(());
+        }
+        let out_of_memory = self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        let validation = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let lut: Vec<u8> = lut
+            .iter()
+            .flat_map(|&v| u32::from(v).to_le_bytes())
+            .collect();
+        // Pad to whole words.
+        let mut pixels = image.as_raw().clone();
+        pixels.resize(image.len().div_ceil(4) * 4, 0);
+
+        let lut = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("maco lut"),
+                contents: &lut,
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let storage = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("maco pixels"),
+                contents: &pixels,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("maco readback"),
+            size: pixels.len() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("maco tone"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: lut.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: storage.as_entire_binding(),
+                },
+            ],
+        });
+
+        let words = (pixels.len() / 4) as u32;
+        let groups = words.div_ceil(WORKGROUP_SIZE);
+        let (groups_x, groups_y) = if groups <= MAX_WORKGROUPS {
+            (groups, 1)
+        } else {
+            (MAX_WORKGROUPS, groups.div_ceil(MAX_WORKGROUPS))
+        };
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&storage, 0, &readback, 0, pixels.len() as u64);
+        self.queue.submit(Some(encoder.finish()));
+
+        if let Some(e) = pollster::block_on(validation.pop()) {
+            return Err(e.into());
+        }
+        if let Some(e) = pollster::block_on(out_of_memory.pop()) {
+            return Err(e.into());
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        readback.map_async(wgpu::MapMode::Read, .., move |result| {
+            // The receiver only goes away after polling, so sending can't fail.
+            sender.send(result).ok();
+        });
+        self.device.poll(wgpu::PollType::wait_indefinitely())?;
+        receiver.recv()??;
+        let mapped = readback.get_mapped_range(..)?;
+        let len = image.len();
+        image.copy_from_slice(&mapped[..len]);
+        drop(mapped);
+        readback.unmap();
+        Ok(())
+    }
+}
+
+/// Converts an image according to given params, applying the tone stages on the GPU if `gpu` is
+/// given.
+///
+/// Output is identical to [`convert()`](crate::convert). Without a GPU, if the GPU fails, e.g.
+/// because the device was lost, or if params enable stages which depend on pixel positions, such
+/// as dithering or local stretching, the tone stages run on the CPU instead.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::gpu::{convert_gpu, GpuContext};
+/// use maco::{convert, ConvertParams};
+///
+/// let gpu = GpuContext::new();
+/// let image = GrayImage::from_fn(64, 64, |x, y| image::Luma([(x + y) as u8]));
+/// let params = ConvertParams::default();
+///
+/// assert_eq!(convert_gpu(image.clone(), &params, gpu.as_ref()), convert(image, &params));
+/// ```
+pub fn convert_gpu(
+    image: GrayImage,
+    params: &ConvertParams,
+    gpu: Option<&GpuContext>,
+) -> GrayImage {
+    let mut image = resize_only(image, params);
+    match (tone_lut(&image, params), gpu) {
+        (Some(lut), Some(gpu)) => {
+            if let Err(e) = gpu.apply_lut(&mut image, &lut) {
+                debug!("gpu failed, applying tone stages on the cpu: {}", e);
+                apply_lut(&mut image, &lut);
+            }
+            image
+        }
+        (Some(lut), None) => {
+            debug!("no gpu given, applying tone stages on the cpu");
+            apply_lut(&mut image, &lut);
+            image
+        }
+        (None, _) => {
+            debug!("tone stages depend on pixel positions, applying them on the cpu");
+            tone_only(image, params)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+    use crate::convert;
+
+    #[test]
+    fn convert_gpu_matches_cpu() {
+        let gradient = GrayImage::from_fn(301, 97, |x, y| Luma([((x + y * 3) % 220) as u8 + 20]));
+        let gpu = GpuContext::new();
+        let mut params = ConvertParams::builder();
+        params.width(200).height(200);
+
+        for params in [
+            params.build(),
+            params.cutoff(5).gamma(0.5).build(),
+            params.threshold(100).build(),
+            params.dither_gamma(true).build(),
+        ] {
+            let want = convert(gradient.clone(), &params);
+            for gpu in [gpu.as_ref(), None] {
+                let got = convert_gpu(gradient.clone(), &params, gpu);
+                assert_eq!(got.dimensions(), want.dimensions());
+                assert!(got
+                    .iter()
+                    .zip(want.iter())
+                    .all(|(a, b)| a.abs_diff(*b) <= 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use image::{GrayImage, ImageBuffer, Luma};
+
+use crate::{convert, ConvertParams};
+
+/// Defines the operator used by [`convert_f32()`] to compress high dynamic range images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Reinhard's global operator, `L / (1 + L)`. Compresses highlights strongly.
+    Reinhard,
+    /// Drago's adaptive logarithmic operator with a bias of 0.85. Scales with the brightest value
+    /// in the image, preserving more highlight detail.
+    Drago,
+}
+
+/// Bias used by [`ToneMap::Drago`], as recommended by the original paper.
+const DRAGO_BIAS: f32 = 0.85;
+
+impl ToneMap {
+    /// Maps a luminance value into range `0..=1`, given the largest luminance in the image.
+    fn apply(self, l: f32, max: f32) -> f32 {
+        match self {
+            ToneMap::Reinhard => l / (1_f32 + l),
+            ToneMap::Drago => {
+                let exponent = DRAGO_BIAS.ln() / 0.5_f32.ln();
+                let scale = 1_f32 / (max + 1_f32).log10();
+                scale * (l + 1_f32).ln() / (2_f32 + 8_f32 * (l / max).powf(exponent)).ln()
+            }
+        }
+    }
+}
+
+/// Converts a floating point image, e.g. a scientific or HDR capture, according to given params.
+///
+/// Values are expected to be non-negative, with 1 corresponding to reference white. They're
+/// compressed into range `0..=1` with the given tone mapping operator, quantized to 8 bits and
+/// then converted as usual. Negative and NaN values are treated as 0.
+///
+/// See also: [`convert()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::{ImageBuffer, Luma};
+/// use maco::{convert_f32, ConvertParams, ToneMap};
+///
+/// let image = ImageBuffer::from_fn(4, 1, |x, _| Luma([x as f32 * 10.0]));
+/// let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+/// let image = convert_f32(image, &params, ToneMap::Reinhard);
+///
+/// assert_eq!(image.into_raw(), vec![0, 239, 250, 255]);
+/// ```
+pub fn convert_f32(
+    image: ImageBuffer<Luma<f32>, Vec<f32>>,
+    params: &ConvertParams,
+    tonemap: ToneMap,
+) -> GrayImage {
+    let max = image.pixels().map(|p| p[0]).fold(0_f32, f32::max);
+    let image = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let l = image.get_pixel(x, y)[0];
+        // Comparison is false for NaN.
+        let l = if l > 0_f32 { l } else { 0_f32 };
+        Luma([(tonemap.apply(l, max) * 255_f32)
+            .round()
+            .clamp(0_f32, 255_f32) as u8])
+    });
+    convert(image, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tonemap_keeps_highlights() {
+        let values = [0.0, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+        let params = ConvertParams::builder().cutoff(0).gamma(1.0).build();
+
+        for tonemap in [ToneMap::Reinhard, ToneMap::Drago] {
+            let image =
+                ImageBuffer::from_fn(values.len() as u32, 1, |x, _| Luma([values[x as usize]]));
+
+            let image = convert_f32(image, &params, tonemap).into_raw();
+
+            // Values above 1 remain distinguishable, only the brightest one is white.
+            assert!(
+                image.windows(2).all(|w| w[0] < w[1]),
+                "{:?}: {:?}",
+                tonemap,
+                image
+            );
+            assert_eq!(image[values.len() - 1], 255);
+        }
+    }
+
+    #[test]
+    fn tonemap_in_range() {
+        for tonemap in [ToneMap::Reinhard, ToneMap::Drago] {
+            assert_eq!(tonemap.apply(0.0, 100.0), 0.0);
+            assert!((tonemap.apply(100.0, 100.0) - 1.0).abs() < 0.02);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::resample::srgb_to_linear;
+
+/// Number of entries in the tone curve of [`gray_profile()`].
+const CURVE_LEN: usize = 1024;
+
+/// D50 white point of the profile connection space, as s15Fixed16 numbers.
+const D50: [u32; 3] = [0x0000_f6d6, 0x0001_0000, 0x0000_d32d];
+
+/// Returns an ICC v2 display profile for grayscale images encoded with the sRGB tone curve.
+///
+/// This is what the crate assumes pixel values to be, so viewers honoring the profile render
+/// tones as intended.
+pub(crate) fn gray_profile() -> Vec<u8> {
+    let description = b"Gray sRGB";
+    let mut desc = tag_type(b"desc");
+    desc.extend_from_slice(&(description.len() as u32 + 1).to_be_bytes());
+    desc.extend_from_slice(description);
+    desc.push(0);
+    // Empty Unicode and ScriptCode descriptions.
+    desc.extend_from_slice(&[0; 8]);
+    desc.extend_from_slice(&[0; 3]);
+    desc.extend_from_slice(&[0; 67]);
+
+    let mut cprt = tag_type(b"text");
+    cprt.extend_from_slice(b"No copyright, use freely\0");
+
+    let mut wtpt = tag_type(b"XYZ ");
+    for v in D50.iter() {
+        wtpt.extend_from_slice(&v.to_be_bytes());
+    }
+
+    let mut ktrc = tag_type(b"curv");
+    ktrc.extend_from_slice(&(CURVE_LEN as u32).to_be_bytes());
+    for i in 0..CURVE_LEN {
+        let linear = srgb_to_linear(i as f32 / (CURVE_LEN - 1) as f32);
+        ktrc.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    let tags: [(&[u8; 4], Vec<u8>); 4] = [
+        (b"desc", desc),
+        (b"cprt", cprt),
+        (b"wtpt", wtpt),
+        (b"kTRC", ktrc),
+    ];
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_start = 128 + 4 + 12 * tags.len();
+    for (signature, tag) in tags.iter() {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        // Tag data starts on 4 byte boundaries.
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let mut profile = header(data_start + data.len());
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+/// Returns the 128 byte profile header of a grayscale display profile with the given size.
+fn header(size: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(size);
+    header.extend_from_slice(&(size as u32).to_be_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&0x0210_0000_u32.to_be_bytes());
+    header.extend_from_slice(b"mntrGRAYXYZ ");
+    // Creation date, 2021-01-01 00:00:00.
+    for v in [2021_u16, 1, 1, 0, 0, 0].iter() {
+        header.extend_from_slice(&v.to_be_bytes());
+    }
+    header.extend_from_slice(b"acsp");
+    // Platform, flags, manufacturer, model, attributes and rendering intent.
+    header.extend_from_slice(&[0; 28]);
+    for v in D50.iter() {
+        header.extend_from_slice(&v.to_be_bytes());
+    }
+    header.resize(128, 0);
+    header
+}
+
+/// Returns the type signature and reserved bytes which start tag data.
+fn tag_type(signature: &[u8; 4]) -> Vec<u8> {
+    let mut tag = signature.to_vec();
+    tag.extend_from_slice(&[0; 4]);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_profile_is_well_formed() {
+        let profile = gray_profile();
+
+        let size = u32::from_be_bytes([profile[0], profile[1], profile[2], profile[3]]);
+        assert_eq!(size as usize, profile.len());
+        assert_eq!(&profile[36..40], b"acsp");
+        assert_eq!(&profile[16..20], b"GRAY");
+        assert!(profile.len().is_multiple_of(4));
+    }
+}
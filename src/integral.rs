@@ -0,0 +1,94 @@
+use image::GrayImage;
+
+use crate::geometry::Rect;
+
+/// Summed-area table of an image, answering sums of pixel values over any rectangle in constant
+/// time.
+///
+/// Local operations, such as box averages, use this to run in time independent of their window
+/// size.
+pub(crate) struct IntegralImage {
+    width: u32,
+    height: u32,
+    /// Sums of all pixels above and to the left of each position, with an extra row and column of
+    /// zeroes at the top and left.
+    sums: Vec<u64>,
+}
+
+impl IntegralImage {
+    /// Computes the integral image of `image`.
+    pub(crate) fn new(image: &GrayImage) -> Self {
+        let (width, height) = image.dimensions();
+        let stride = width as usize + 1;
+        let mut sums = vec![0_u64; stride * (height as usize + 1)];
+        for y in 0..height as usize {
+            let mut row = 0_u64;
+            for x in 0..width as usize {
+                row += u64::from(image.get_pixel(x as u32, y as u32)[0]);
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+            }
+        }
+        IntegralImage {
+            width,
+            height,
+            sums,
+        }
+    }
+
+    /// Returns the sum of pixel values within `rect`. Parts outside the image are ignored.
+    pub(crate) fn sum(&self, rect: Rect) -> u64 {
+        let rect = rect.clamp_to(self.width, self.height);
+        let stride = self.width as usize + 1;
+        let at = |x: u32, y: u32| self.sums[y as usize * stride + x as usize];
+        let (right, bottom) = (rect.right(), rect.bottom());
+        at(right, bottom) + at(rect.x, rect.y) - at(rect.x, bottom) - at(right, rect.y)
+    }
+
+    /// Returns the mean pixel value within `rect`, rounded to the nearest integer. Parts outside
+    /// the image are ignored. Empty areas have a mean of 0.
+    pub(crate) fn mean(&self, rect: Rect) -> u8 {
+        let rect = rect.clamp_to(self.width, self.height);
+        let count = u64::from(rect.width) * u64::from(rect.height);
+        if count == 0 {
+            return 0;
+        }
+        ((self.sum(rect) + count / 2) / count) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn sums_match_brute_force() {
+        let image = GrayImage::from_fn(23, 17, |x, y| Luma([((x * 37 + y * 91) % 256) as u8]));
+        let integral = IntegralImage::new(&image);
+        let brute_force = |rect: Rect| {
+            let rect = rect.clamp_to(image.width(), image.height());
+            let mut sum = 0;
+            for y in rect.y..rect.bottom() {
+                for x in rect.x..rect.right() {
+                    sum += u64::from(image.get_pixel(x, y)[0]);
+                }
+            }
+            sum
+        };
+
+        for (x, y, width, height) in [
+            (0, 0, 23, 17),
+            (0, 0, 1, 1),
+            (5, 3, 7, 9),
+            (22, 16, 1, 1),
+            (10, 10, 0, 5),
+            (20, 15, 10, 10),
+            (30, 30, 5, 5),
+        ] {
+            let rect = Rect::new(x, y, width, height);
+            assert_eq!(integral.sum(rect), brute_force(rect), "{:?}", rect);
+        }
+        assert_eq!(integral.mean(Rect::new(30, 30, 5, 5)), 0);
+    }
+}
@@ -0,0 +1,890 @@
+//! Helpers for loading and saving images.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::io::Reader;
+use image::{ColorType, GrayImage, ImageError, ImageFormat};
+
+use crate::{convert, icc, ConvertParams, Error};
+
+/// Decodes an image from memory, refusing to decode images with more than `max_pixels` pixels.
+///
+/// Dimensions are read from the image header before decoding, so oversized images (e.g.
+/// decompression bombs) are rejected without allocating memory for their pixels. The format is
+/// guessed from the image contents.
+///
+/// # Examples
+///
+/// ```
+/// use maco::io::load_limited;
+/// use maco::Error;
+///
+/// let header = b"P5\n50000 50000\n255\n";
+///
+/// assert!(matches!(
+///     load_limited(header, 10_000_000),
+///     Err(Error::TooLarge { pixels: 2_500_000_000, limit: 10_000_000 })
+/// ));
+/// ```
+pub fn load_limited(bytes: &[u8], max_pixels: u64) -> Result<GrayImage, Error> {
+    let (width, height) = reader(bytes)?.into_dimensions()?;
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels > max_pixels {
+        return Err(Error::TooLarge {
+            pixels,
+            limit: max_pixels,
+        });
+    }
+    Ok(reader(bytes)?.decode()?.into_luma8())
+}
+
+/// Decodes an image from a reader, e.g. an in-memory buffer or a network stream.
+///
+/// If `format_hint` is `None`, the format is guessed from the image contents.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use image::ImageFormat;
+/// use maco::io::load_reader;
+///
+/// let image = load_reader(Cursor::new(b"P5\n2 1\n255\n\x10\x20"), Some(ImageFormat::Pnm)).unwrap();
+///
+/// assert_eq!(image.dimensions(), (2, 1));
+/// ```
+pub fn load_reader(
+    r: impl Read + Seek,
+    format_hint: Option<ImageFormat>,
+) -> Result<GrayImage, Error> {
+    let reader = match format_hint {
+        Some(format) => Reader::with_format(BufReader::new(r), format),
+        None => Reader::new(BufReader::new(r)).with_guessed_format()?,
+    };
+    Ok(reader.decode()?.into_luma8())
+}
+
+fn reader(bytes: &[u8]) -> Result<Reader<Cursor<&[u8]>>, Error> {
+    Ok(Reader::new(Cursor::new(bytes)).with_guessed_format()?)
+}
+
+/// Encoding gamma assumed for images which don't specify one, approximating sRGB.
+const STANDARD_GAMMA: f64 = 1_f64 / 2.2;
+
+/// Loads an image from a file according to given params.
+///
+/// By default pixel values are used as-is, which assumes images are sRGB encoded. Enable
+/// [`LoadParamsBuilder::normalize_gamma()`] to respect a gamma embedded in the file instead. ICC
+/// profiles are ignored.
+///
+/// See also: [`LoadParams`].
+pub fn load(path: impl AsRef<Path>, params: &LoadParams) -> Result<GrayImage, Error> {
+    let bytes = std::fs::read(path)?;
+    let reader = reader(&bytes)?;
+    let format = reader.format();
+    let mut image = reader.decode()?.into_luma8();
+
+    if params.normalize_gamma && format == Some(ImageFormat::Png) {
+        if let Some(gamma) = png_gamma(&bytes) {
+            normalize_gamma(&mut image, gamma);
+        }
+    }
+    Ok(image)
+}
+
+/// Returns the encoding gamma stored in the gAMA chunk of a PNG file.
+fn png_gamma(png: &[u8]) -> Option<f64> {
+    let data = png_chunk(png, b"gAMA")?;
+    if data.len() != 4 {
+        return None;
+    }
+    let gamma = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    if gamma == 0 {
+        None
+    } else {
+        Some(f64::from(gamma) / 100_000_f64)
+    }
+}
+
+/// Re-encodes pixel values from the given encoding gamma to [`STANDARD_GAMMA`].
+fn normalize_gamma(image: &mut GrayImage, gamma: f64) {
+    if (gamma - STANDARD_GAMMA).abs() < 0.0001 {
+        return;
+    }
+    let mut lut = [0_u8; 256];
+    for (i, x) in lut.iter_mut().enumerate() {
+        let linear = (i as f64 / 255_f64).powf(1_f64 / gamma);
+        *x = (linear.powf(STANDARD_GAMMA) * 255_f64).round() as u8;
+    }
+    for p in image.iter_mut() {
+        *p = lut[*p as usize];
+    }
+}
+
+/// Returns the data of the first chunk with the given name in a PNG file.
+fn png_chunk<'a>(png: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut rest = png.get(8..)?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if &rest[4..8] == name {
+            return rest.get(8..8 + len);
+        }
+        rest = rest.get(12 + len..)?;
+    }
+    None
+}
+
+/// Defines parameters for the [`load()`] function.
+///
+/// See also: [`LoadParamsBuilder`], [`LoadParamsBuilder::default()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::io::LoadParams;
+///
+/// LoadParams::builder().normalize_gamma(true).build();
+/// ```
+#[derive(Debug)]
+pub struct LoadParams {
+    normalize_gamma: bool,
+}
+
+impl Default for LoadParams {
+    /// Convenience method wrapping [`LoadParamsBuilder::default()`].
+    fn default() -> Self {
+        LoadParamsBuilder::default().build()
+    }
+}
+
+impl LoadParams {
+    /// Returns a new [`LoadParamsBuilder`] with default settings.
+    pub fn builder() -> LoadParamsBuilder {
+        LoadParamsBuilder::default()
+    }
+}
+
+/// Builds [`LoadParams`].
+pub struct LoadParamsBuilder {
+    normalize_gamma: bool,
+}
+
+impl Default for LoadParamsBuilder {
+    /// Default values are equal to the following:
+    ///
+    /// ```ignore
+    /// LoadParamsBuilder {
+    ///     normalize_gamma: false,
+    /// }
+    /// ```
+    fn default() -> Self {
+        LoadParamsBuilder {
+            normalize_gamma: false,
+        }
+    }
+}
+
+impl LoadParamsBuilder {
+    /// Sets whether pixel values should be re-encoded from the gamma embedded in the image (the
+    /// gAMA chunk of PNG files) to the standard gamma of 1/2.2. Such images then look the same
+    /// through the rest of the pipeline as if they were authored in sRGB. Images without an
+    /// embedded gamma are left unchanged.
+    pub fn normalize_gamma(&mut self, normalize: bool) -> &mut Self {
+        self.normalize_gamma = normalize;
+        self
+    }
+
+    /// Builds and returns a [`LoadParams`] instance.
+    pub fn build(&self) -> LoadParams {
+        LoadParams {
+            normalize_gamma: self.normalize_gamma,
+        }
+    }
+}
+
+/// Image formats supported by [`save()`] and [`encode()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Lossless PNG.
+    Png,
+    /// Lossy JPEG with the given quality in range `1..=100`.
+    Jpeg(u8),
+    /// Lossless WebP, which compresses line art considerably better than PNG.
+    #[cfg(feature = "webp")]
+    WebPLossless,
+}
+
+impl OutputFormat {
+    /// Returns the file extension commonly used for this format, without the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg(_) => "jpg",
+            #[cfg(feature = "webp")]
+            OutputFormat::WebPLossless => "webp",
+        }
+    }
+}
+
+/// Defines parameters for the [`save()`] and [`encode()`] functions.
+///
+/// See also: [`SaveParamsBuilder`], [`SaveParamsBuilder::default()`].
+///
+/// # Examples
+///
+/// ```
+/// use maco::io::{OutputFormat, SaveParams};
+///
+/// SaveParams::builder().format(OutputFormat::Jpeg(90)).dpi(300).build();
+/// ```
+#[derive(Debug)]
+pub struct SaveParams {
+    format: OutputFormat,
+    dpi: Option<u16>,
+    bilevel: bool,
+    icc_profile: bool,
+}
+
+impl Default for SaveParams {
+    /// Convenience method wrapping [`SaveParamsBuilder::default()`].
+    fn default() -> Self {
+        SaveParamsBuilder::default().build()
+    }
+}
+
+impl SaveParams {
+    /// Returns a new [`SaveParamsBuilder`] with default settings.
+    pub fn builder() -> SaveParamsBuilder {
+        SaveParamsBuilder::default()
+    }
+}
+
+/// Builds [`SaveParams`].
+pub struct SaveParamsBuilder {
+    format: OutputFormat,
+    dpi: Option<u16>,
+    bilevel: bool,
+    icc_profile: bool,
+}
+
+impl Default for SaveParamsBuilder {
+    /// Default values are equal to the following:
+    ///
+    /// ```ignore
+    /// SaveParamsBuilder {
+    ///     format: OutputFormat::Png,
+    ///     dpi: None,
+    ///     bilevel: false,
+    ///     icc_profile: false,
+    /// }
+    /// ```
+    fn default() -> Self {
+        SaveParamsBuilder {
+            format: OutputFormat::Png,
+            dpi: None,
+            bilevel: false,
+            icc_profile: false,
+        }
+    }
+}
+
+impl SaveParamsBuilder {
+    /// Sets the format of the output image.
+    pub fn format(&mut self, format: OutputFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the intended print density of the output image in dots per inch. It's stored in the
+    /// pHYs chunk for PNG and the JFIF header for JPEG, allowing e.g. PDF tools to size pages
+    /// correctly. Pixels aren't modified.
+    pub fn dpi(&mut self, dpi: u16) -> &mut Self {
+        self.dpi = Some(dpi);
+        self
+    }
+
+    /// Sets whether PNG images containing only black and white pixels, e.g. thresholded line art,
+    /// should be stored with 1 bit per pixel. This makes such files much smaller. Other images are
+    /// stored with 8 bits per pixel as usual.
+    pub fn bilevel(&mut self, bilevel: bool) -> &mut Self {
+        self.bilevel = bilevel;
+        self
+    }
+
+    /// Sets whether PNG images should embed an ICC profile describing their pixels as grayscale
+    /// with the sRGB tone curve, so color managed viewers render tones as intended. Adds a few
+    /// KiB to every file. Other formats are saved without a profile.
+    pub fn icc_profile(&mut self, embed: bool) -> &mut Self {
+        self.icc_profile = embed;
+        self
+    }
+
+    /// Builds and returns a [`SaveParams`] instance.
+    pub fn build(&self) -> SaveParams {
+        SaveParams {
+            format: self.format,
+            dpi: self.dpi,
+            bilevel: self.bilevel,
+            icc_profile: self.icc_profile,
+        }
+    }
+}
+
+/// Returns a file name for page number `index` out of `total` pages.
+///
+/// The number is zero-padded to the width of `total`, so names sort correctly as plain strings.
+/// `index` is used as is, it's up to the caller whether numbering starts at 0 or 1.
+///
+/// # Examples
+///
+/// ```
+/// use maco::io::numbered_name;
+///
+/// assert_eq!(numbered_name(1, 150, "png"), "page_001.png");
+/// assert_eq!(numbered_name(150, 150, "png"), "page_150.png");
+/// ```
+pub fn numbered_name(index: usize, total: usize, ext: &str) -> String {
+    let width = total.max(1).to_string().len();
+    format!("page_{:0width$}.{}", index, ext, width = width)
+}
+
+/// Saves an image to a file according to given params. The file is created or truncated.
+///
+/// See also: [`encode()`].
+pub fn save(image: &GrayImage, path: impl AsRef<Path>, params: &SaveParams) -> Result<(), Error> {
+    let mut w = BufWriter::new(File::create(path)?);
+    encode(image, &mut w, params)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Saves an image in each of the given formats, e.g. a PNG master and a JPEG for distribution.
+///
+/// Each file is named after `base_path` with the extension of its format, replacing any extension
+/// `base_path` already has. Formats are saved with default [`SaveParams`] otherwise. Returns the
+/// paths of the saved files, in the order of `formats`.
+///
+/// # Errors
+///
+/// Returns [`Error::DuplicatePath`] if several formats share an extension, e.g. JPEGs of
+/// different quality, before anything is written.
+///
+/// See also: [`save()`].
+pub fn save_all(
+    image: &GrayImage,
+    base_path: impl AsRef<Path>,
+    formats: &[OutputFormat],
+) -> Result<Vec<PathBuf>, Error> {
+    let base_path = base_path.as_ref();
+    let paths: Vec<PathBuf> = formats
+        .iter()
+        .map(|format| base_path.with_extension(format.extension()))
+        .collect();
+    for (i, path) in paths.iter().enumerate() {
+        if paths[..i].contains(path) {
+            return Err(Error::DuplicatePath(path.clone()));
+        }
+    }
+    for (path, &format) in paths.iter().zip(formats) {
+        save(image, path, &SaveParams::builder().format(format).build())?;
+    }
+    Ok(paths)
+}
+
+/// JPEG quality used by [`convert_file()`].
+const CONVERT_FILE_QUALITY: u8 = 90;
+
+/// Loads an image, converts it according to given params and saves it to `output`, refusing to
+/// overwrite the input.
+///
+/// The output format is picked by the extension of `output`: PNG, or JPEG at quality 90 for `jpg`
+/// and `jpeg`. Other options are left at their defaults.
+///
+/// # Errors
+///
+/// Returns [`Error::SamePath`] if `output` refers to the same file as `input`, e.g. because of
+/// a typo in a script, before anything is read or written. Fails if the extension of `output` is
+/// unsupported.
+pub fn convert_file(input: &Path, output: &Path, params: &ConvertParams) -> Result<(), Error> {
+    let input = input.canonicalize()?;
+    if resolve(output)? == input {
+        return Err(Error::SamePath(input));
+    }
+    let ext = output
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let format = match ext.as_str() {
+        "png" => OutputFormat::Png,
+        "jpg" | "jpeg" => OutputFormat::Jpeg(CONVERT_FILE_QUALITY),
+        #[cfg(feature = "webp")]
+        "webp" => OutputFormat::WebPLossless,
+        _ => {
+            let hint = ImageFormatHint::PathExtension(ext.into());
+            return Err(Error::Image(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    hint.clone(),
+                    UnsupportedErrorKind::Format(hint),
+                ),
+            )));
+        }
+    };
+    let image = convert(load(&input, &LoadParams::default())?, params);
+    save(
+        &image,
+        output,
+        &SaveParams::builder().format(format).build(),
+    )
+}
+
+/// Returns the absolute path a possibly not yet existing file would have, resolving symlinks in
+/// its directory.
+fn resolve(path: &Path) -> Result<PathBuf, Error> {
+    if path.exists() {
+        return Ok(path.canonicalize()?);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+        _ => std::env::current_dir()?,
+    };
+    Ok(match path.file_name() {
+        Some(name) => parent.join(name),
+        None => parent,
+    })
+}
+
+/// Encodes an image into a writer according to given params.
+///
+/// See also: [`save()`].
+pub fn encode(image: &GrayImage, mut w: impl Write, params: &SaveParams) -> Result<(), Error> {
+    match params.format {
+        OutputFormat::Png => encode_png(image, w, params),
+        OutputFormat::Jpeg(quality) => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut w, quality);
+            if let Some(dpi) = params.dpi {
+                encoder.set_pixel_density(PixelDensity::dpi(dpi));
+            }
+            encoder.encode(image, image.width(), image.height(), ColorType::L8)?;
+            Ok(())
+        }
+        #[cfg(feature = "webp")]
+        OutputFormat::WebPLossless => {
+            let encoder = image_webp::WebPEncoder::new(w);
+            encoder.encode(
+                image,
+                image.width(),
+                image.height(),
+                image_webp::ColorType::L8,
+            )?;
+            Ok(())
+        }
+    }
+}
+
+/// Converts an image according to given params and encodes the result in the given format.
+///
+/// See also: [`convert()`](crate::convert), [`encode()`].
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::io::{convert_to_bytes, OutputFormat};
+/// use maco::ConvertParams;
+///
+/// let bytes = convert_to_bytes(GrayImage::new(8, 8), &ConvertParams::default(), OutputFormat::Png);
+///
+/// assert!(bytes.unwrap().starts_with(b"\x89PNG"));
+/// ```
+pub fn convert_to_bytes(
+    image: GrayImage,
+    params: &ConvertParams,
+    format: OutputFormat,
+) -> Result<Vec<u8>, Error> {
+    let image = convert(image, params);
+    let mut bytes = Vec::new();
+    encode(
+        &image,
+        &mut bytes,
+        &SaveParams::builder().format(format).build(),
+    )?;
+    Ok(bytes)
+}
+
+/// Encodes an image as a grayscale PNG, with 1 bit per pixel if requested and possible, else 8.
+fn encode_png(image: &GrayImage, w: impl Write, params: &SaveParams) -> Result<(), Error> {
+    let bilevel = params.bilevel && image.iter().all(|&p| p == 0 || p == 255);
+    let mut encoder = png::Encoder::new(w, image.width(), image.height());
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(if bilevel {
+        png::BitDepth::One
+    } else {
+        png::BitDepth::Eight
+    });
+    let mut writer = encoder.write_header()?;
+
+    if params.icc_profile {
+        // Profile name, compression method and the zlib compressed profile.
+        let mut iccp = b"Gray sRGB\0\0".to_vec();
+        iccp.extend_from_slice(&deflate::deflate_bytes_zlib(&icc::gray_profile()));
+        writer.write_chunk(*b"iCCP", &iccp)?;
+    }
+
+    if let Some(dpi) = params.dpi {
+        // pHYs stores pixels per meter.
+        let ppm = (f64::from(dpi) / 0.0254).round() as u32;
+        let mut phys = [0; 9];
+        phys[..4].copy_from_slice(&ppm.to_be_bytes());
+        phys[4..8].copy_from_slice(&ppm.to_be_bytes());
+        phys[8] = 1;
+        writer.write_chunk(*b"pHYs", &phys)?;
+    }
+
+    if bilevel {
+        writer.write_image_data(&pack_bits(image))?;
+    } else {
+        writer.write_image_data(image)?;
+    }
+    Ok(())
+}
+
+/// Packs a black and white image into rows of 1-bit pixels, most significant bit first. Each row
+/// is padded to a whole byte.
+fn pack_bits(image: &GrayImage) -> Vec<u8> {
+    let row_len = image.width().div_ceil(8) as usize;
+    let mut packed = vec![0; row_len * image.height() as usize];
+    for (x, y, p) in image.enumerate_pixels() {
+        if p[0] == 255 {
+            packed[y as usize * row_len + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+    packed
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(e: png::EncodingError) -> Self {
+        match e {
+            png::EncodingError::IoError(e) => Error::Io(e),
+            e => Error::Image(ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(ImageFormat::Png),
+                e,
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "webp")]
+impl From<image_webp::EncodingError> for Error {
+    fn from(e: image_webp::EncodingError) -> Self {
+        match e {
+            image_webp::EncodingError::IoError(e) => Error::Io(e),
+            e => Error::Image(ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(ImageFormat::WebP),
+                e,
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn load_limited_rejects_large_images() {
+        // Header only, decoding would fail due to missing pixel data.
+        let header = b"P5\n50000 50000\n255\n";
+
+        match load_limited(header, 1 << 20) {
+            Err(Error::TooLarge { pixels, limit }) => {
+                assert_eq!(pixels, 50000 * 50000);
+                assert_eq!(limit, 1 << 20);
+            }
+            other => panic!("expected Error::TooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_limited_decodes_small_images() {
+        let image = b"P5\n2 1\n255\n\x10\x20";
+
+        let image = load_limited(image, 2).unwrap();
+
+        assert_eq!(image.into_raw(), vec![0x10, 0x20]);
+    }
+
+    #[test]
+    fn load_normalizes_png_gamma() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("linear.png");
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 3, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        // Gamma 1.0, i.e. pixel values are linear light.
+        writer
+            .write_chunk(*b"gAMA", &100_000_u32.to_be_bytes())
+            .unwrap();
+        writer.write_image_data(&[0, 64, 255]).unwrap();
+        drop(writer);
+
+        let ignored = load(&path, &LoadParams::default()).unwrap();
+        let params = LoadParams::builder().normalize_gamma(true).build();
+        let normalized = load(&path, &params).unwrap();
+
+        assert_eq!(ignored.into_raw(), vec![0, 64, 255]);
+        // (64 / 255) ^ (1 / 2.2) * 255 = 136
+        assert_eq!(normalized.into_raw(), vec![0, 136, 255]);
+    }
+
+    #[test]
+    fn convert_to_bytes_decodes_to_convert() {
+        let image = GrayImage::from_fn(64, 48, |x, y| Luma([(x * 3 + y * 2) as u8]));
+        let params = ConvertParams::builder().width(32).height(32).build();
+        let want = convert(image.clone(), &params);
+
+        let png = convert_to_bytes(image.clone(), &params, OutputFormat::Png).unwrap();
+        let jpeg = convert_to_bytes(image, &params, OutputFormat::Jpeg(95)).unwrap();
+
+        assert_eq!(image::load_from_memory(&png).unwrap().into_luma8(), want);
+        let jpeg = image::load_from_memory(&jpeg).unwrap().into_luma8();
+        assert_eq!(jpeg.dimensions(), want.dimensions());
+        let max_diff = jpeg
+            .iter()
+            .zip(want.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max();
+        assert!(max_diff.unwrap() <= 8, "{:?}", max_diff);
+    }
+
+    #[test]
+    fn load_reader_from_cursor() {
+        let image = GrayImage::from_fn(8, 4, |x, y| Luma([(x * 30 + y) as u8]));
+        let mut png = Vec::new();
+        encode(&image, &mut png, &SaveParams::default()).unwrap();
+
+        let sniffed = load_reader(Cursor::new(&png), None).unwrap();
+        let hinted = load_reader(Cursor::new(&png), Some(ImageFormat::Png)).unwrap();
+
+        assert_eq!(sniffed, image);
+        assert_eq!(hinted, image);
+        assert!(load_reader(Cursor::new(&png), Some(ImageFormat::Jpeg)).is_err());
+    }
+
+    #[test]
+    fn numbered_name_padding() {
+        assert_eq!(numbered_name(3, 150, "png"), "page_003.png");
+        assert_eq!(numbered_name(9, 10, "jpg"), "page_09.jpg");
+        assert_eq!(numbered_name(10, 10, "jpg"), "page_10.jpg");
+        assert_eq!(numbered_name(0, 0, "png"), "page_0.png");
+
+        let mut names: Vec<String> = (1..=150).map(|i| numbered_name(i, 150, "png")).collect();
+        let ordered = names.clone();
+        names.sort();
+        assert_eq!(names, ordered);
+    }
+
+    #[cfg(feature = "webp")]
+    #[test]
+    fn save_webp_lossless() {
+        let image = GrayImage::from_fn(100, 60, |x, y| {
+            Luma([if (x / 3 + y) % 5 == 0 { 0 } else { 255 }])
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.webp");
+        let params = SaveParams::builder()
+            .format(OutputFormat::WebPLossless)
+            .build();
+
+        save(&image, &path, &params).unwrap();
+
+        let mut decoder =
+            image_webp::WebPDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert!(!decoder.is_lossy());
+        assert_eq!(decoder.dimensions(), image.dimensions());
+        let mut rgb = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut rgb).unwrap();
+        let stride = rgb.len() / image.len();
+        let loaded: Vec<_> = rgb.chunks(stride).map(|p| p[0]).collect();
+        assert_eq!(loaded, image.as_raw().as_slice());
+    }
+
+    #[test]
+    fn save_bilevel_png() {
+        let image = GrayImage::from_fn(100, 60, |x, y| {
+            Luma([if (x / 3 + y) % 5 == 0 { 0 } else { 255 }])
+        });
+        let params = SaveParams::builder().bilevel(true).build();
+        let mut bilevel = Vec::new();
+        let mut full = Vec::new();
+
+        encode(&image, &mut bilevel, &params).unwrap();
+        encode(&image, &mut full, &SaveParams::default()).unwrap();
+
+        // IHDR: width, height, bit depth.
+        assert_eq!(png_chunk(&bilevel, b"IHDR").unwrap()[8], 1);
+        assert!(bilevel.len() < full.len());
+        assert_eq!(
+            image::load_from_memory(&bilevel).unwrap().into_luma8(),
+            image
+        );
+    }
+
+    #[test]
+    fn save_bilevel_png_keeps_gray() {
+        let image = GrayImage::from_fn(9, 3, |x, _| Luma([x as u8 * 20]));
+        let mut png = Vec::new();
+
+        encode(
+            &image,
+            &mut png,
+            &SaveParams::builder().bilevel(true).build(),
+        )
+        .unwrap();
+
+        assert_eq!(png_chunk(&png, b"IHDR").unwrap()[8], 8);
+        assert_eq!(image::load_from_memory(&png).unwrap().into_luma8(), image);
+    }
+
+    #[test]
+    fn save_png_with_dpi() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.png");
+        let image = GrayImage::from_fn(8, 4, |x, y| Luma([(x * y) as u8]));
+        let params = SaveParams::builder().dpi(300).build();
+
+        save(&image, &path, &params).unwrap();
+
+        let png = std::fs::read(&path).unwrap();
+        // 300 dpi is 11811 pixels per meter.
+        let ppm = 11811_u32.to_be_bytes();
+        let mut want = ppm.to_vec();
+        want.extend_from_slice(&ppm);
+        want.push(1);
+        assert_eq!(png_chunk(&png, b"pHYs"), Some(&want[..]));
+        assert_eq!(image::open(&path).unwrap().into_luma8(), image);
+    }
+
+    #[test]
+    fn save_png_without_dpi() {
+        let image = GrayImage::new(4, 4);
+        let mut png = Vec::new();
+
+        encode(&image, &mut png, &SaveParams::default()).unwrap();
+
+        assert_eq!(png_chunk(&png, b"pHYs"), None);
+    }
+
+    #[test]
+    fn save_png_with_icc_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("page.png");
+        let image = GrayImage::from_fn(8, 4, |x, y| Luma([(x * y) as u8]));
+
+        save(&image, &path, &SaveParams::default()).unwrap();
+        assert_eq!(png_chunk(&std::fs::read(&path).unwrap(), b"iCCP"), None);
+
+        save(
+            &image,
+            &path,
+            &SaveParams::builder().icc_profile(true).build(),
+        )
+        .unwrap();
+        let png = std::fs::read(&path).unwrap();
+        assert!(png_chunk(&png, b"iCCP")
+            .unwrap()
+            .starts_with(b"Gray sRGB\0\0"));
+        assert_eq!(image::open(&path).unwrap().into_luma8(), image);
+    }
+
+    #[test]
+    fn save_all_writes_each_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = GrayImage::from_fn(8, 4, |x, y| Luma([(x * y * 8) as u8]));
+
+        let paths = save_all(
+            &image,
+            dir.path().join("page"),
+            &[OutputFormat::Png, OutputFormat::Jpeg(90)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            paths,
+            vec![dir.path().join("page.png"), dir.path().join("page.jpg")]
+        );
+        assert_eq!(image::open(&paths[0]).unwrap().into_luma8(), image);
+        assert_eq!(
+            image::ImageFormat::from_path(&paths[1]).unwrap(),
+            image::ImageFormat::Jpeg
+        );
+        assert!(std::fs::read(&paths[1]).unwrap().starts_with(&[0xff, 0xd8]));
+    }
+
+    #[test]
+    fn save_all_rejects_shared_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = GrayImage::new(8, 4);
+
+        let result = save_all(
+            &image,
+            dir.path().join("page"),
+            &[
+                OutputFormat::Jpeg(90),
+                OutputFormat::Png,
+                OutputFormat::Jpeg(50),
+            ],
+        );
+
+        match result {
+            Err(Error::DuplicatePath(path)) => assert_eq!(path, dir.path().join("page.jpg")),
+            other => panic!("expected Error::DuplicatePath, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn convert_file_refuses_same_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("page.png");
+        let image = GrayImage::from_fn(8, 4, |x, y| Luma([(x * y * 8) as u8]));
+        save(&image, &input, &SaveParams::default()).unwrap();
+        let params = ConvertParams::default();
+
+        let same = dir.path().join(".").join("page.png");
+        match convert_file(&input, &same, &params) {
+            Err(Error::SamePath(path)) => assert_eq!(path, input.canonicalize().unwrap()),
+            other => panic!("expected Error::SamePath, got {:?}", other),
+        }
+        assert_eq!(image::open(&input).unwrap().into_luma8(), image);
+
+        let output = dir.path().join("page.jpg");
+        convert_file(&input, &output, &params).unwrap();
+        assert!(std::fs::read(&output).unwrap().starts_with(&[0xff, 0xd8]));
+        assert!(convert_file(&input, &dir.path().join("page.tiff"), &params).is_err());
+    }
+
+    #[test]
+    fn save_jpeg_with_dpi() {
+        let image = GrayImage::new(4, 4);
+        let params = SaveParams::builder()
+            .format(OutputFormat::Jpeg(80))
+            .dpi(150)
+            .build();
+        let mut jpeg = Vec::new();
+
+        encode(&image, &mut jpeg, &params).unwrap();
+
+        // JFIF APP0 segment: identifier, version, unit (1 = dots per inch), x and y density.
+        let jfif = jpeg.windows(5).position(|w| w == b"JFIF\0").unwrap();
+        assert_eq!(&jpeg[jfif + 7..jfif + 12], &[1, 0, 150, 0, 150]);
+    }
+}
@@ -0,0 +1,430 @@
+use image::{imageops, GrayImage, Luma};
+
+use crate::error::Error;
+use crate::geometry::Rect;
+
+/// Crops the largest square from the center of an image.
+///
+/// Square images are returned unchanged. If the leftover margin is odd, the extra pixel is cropped
+/// from the right or bottom.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::center_square;
+///
+/// let square = center_square(&GrayImage::new(100, 60));
+///
+/// assert_eq!(square.dimensions(), (60, 60));
+/// ```
+pub fn center_square(image: &GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let size = width.min(height);
+    imageops::crop_imm(image, (width - size) / 2, (height - size) / 2, size, size).to_image()
+}
+
+/// Defines the order in which pages are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingDirection {
+    /// Western comics, the left page comes first.
+    LeftToRight,
+    /// Manga, the right page comes first.
+    RightToLeft,
+}
+
+/// Splits a two-page spread into separate pages, in reading order.
+///
+/// Images wider than they are tall are considered spreads and cut in half. Other images are
+/// returned unchanged, as the only page. If the width is odd, the extra column goes to the right
+/// page.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{split_spread, ReadingDirection};
+///
+/// let pages = split_spread(&GrayImage::new(200, 150), ReadingDirection::RightToLeft);
+///
+/// assert_eq!(pages.len(), 2);
+/// assert_eq!(pages[0].dimensions(), (100, 150));
+/// ```
+pub fn split_spread(image: &GrayImage, direction: ReadingDirection) -> Vec<GrayImage> {
+    let (width, height) = image.dimensions();
+    if width <= height {
+        return vec![image.clone()];
+    }
+    let half = width / 2;
+    let left = imageops::crop_imm(image, 0, 0, half, height).to_image();
+    let right = imageops::crop_imm(image, half, 0, width - half, height).to_image();
+    match direction {
+        ReadingDirection::LeftToRight => vec![left, right],
+        ReadingDirection::RightToLeft => vec![right, left],
+    }
+}
+
+/// Defines how [`split_vertical()`] cuts a tall image into strips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// Cuts the image into the fewest strips of equal height. The last strip may be up to a pixel
+    /// taller than the others.
+    Equal,
+    /// Cuts strips of the maximum allowed height from the top, leaving any remainder in a shorter
+    /// last strip.
+    FromTop,
+}
+
+/// Splits a tall image, e.g. a webtoon strip, into strips whose height is at most `max_aspect`
+/// times their width, from top to bottom.
+///
+/// Images within the allowed aspect ratio are returned unchanged, as the only strip.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{split_vertical, SplitStrategy};
+///
+/// let strips = split_vertical(&GrayImage::new(100, 700), 3.0, SplitStrategy::FromTop);
+///
+/// assert_eq!(strips.len(), 3);
+/// assert_eq!(strips[2].dimensions(), (100, 100));
+/// ```
+pub fn split_vertical(
+    image: &GrayImage,
+    max_aspect: f32,
+    strategy: SplitStrategy,
+) -> Vec<GrayImage> {
+    let (width, height) = image.dimensions();
+    let max_height = ((width as f32 * max_aspect).floor() as u32).max(1);
+    if height <= max_height {
+        return vec![image.clone()];
+    }
+    let count = height.div_ceil(max_height);
+    (0..count)
+        .map(|i| {
+            let (top, bottom) = match strategy {
+                SplitStrategy::Equal => (i * height / count, (i + 1) * height / count),
+                SplitStrategy::FromTop => (i * max_height, ((i + 1) * max_height).min(height)),
+            };
+            imageops::crop_imm(image, 0, top, width, bottom - top).to_image()
+        })
+        .collect()
+}
+
+/// Packs images into a single atlas texture at most `max_dim` pixels wide and tall.
+///
+/// Images are placed left to right in the order they were given, starting a new shelf below the
+/// tallest image of the current one when a row is full. The atlas is cropped to the area actually
+/// used. Returns the atlas and the placement of each image. If the images don't all fit,
+/// [`Error::AtlasFull`] is returned with the index of the first image which didn't, so that one and
+/// the remaining images can be packed into another atlas.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::atlas;
+///
+/// let pages = vec![GrayImage::new(30, 40), GrayImage::new(30, 40)];
+/// let (texture, rects) = atlas(&pages, 64).unwrap();
+///
+/// assert_eq!(texture.dimensions(), (60, 40));
+/// assert_eq!(rects[1].x, 30);
+/// ```
+pub fn atlas(images: &[GrayImage], max_dim: u32) -> Result<(GrayImage, Vec<Rect>), Error> {
+    let mut rects = Vec::with_capacity(images.len());
+    let (mut x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+    let (mut atlas_width, mut atlas_height) = (0, 0);
+    for (index, image) in images.iter().enumerate() {
+        let (width, height) = image.dimensions();
+        if x + width > max_dim {
+            x = 0;
+            shelf_y += shelf_height;
+            shelf_height = 0;
+        }
+        if x + width > max_dim || shelf_y + height > max_dim {
+            return Err(Error::AtlasFull { index });
+        }
+        rects.push(Rect::new(x, shelf_y, width, height));
+        x += width;
+        shelf_height = shelf_height.max(height);
+        atlas_width = atlas_width.max(x);
+        atlas_height = atlas_height.max(shelf_y + height);
+    }
+
+    let mut texture = GrayImage::new(atlas_width, atlas_height);
+    for (image, rect) in images.iter().zip(&rects) {
+        imageops::replace(&mut texture, image, rect.x, rect.y);
+    }
+    Ok((texture, rects))
+}
+
+/// Centers an image on a canvas of the given dimensions filled with `background`, e.g. to
+/// letterbox pages to the exact screen size of a device.
+///
+/// When the leftover margin along an axis is odd, the extra pixel goes to the right or bottom
+/// margin, matching [`center_square()`]. Images larger than the canvas along an axis are cropped
+/// around their center by the same rule.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::pad_to;
+///
+/// let padded = pad_to(&GrayImage::from_pixel(2, 1, Luma([0])), 5, 1, 255);
+///
+/// assert_eq!(padded.into_raw(), vec![255, 0, 0, 255, 255]);
+/// ```
+pub fn pad_to(image: &GrayImage, width: u32, height: u32, background: u8) -> GrayImage {
+    // Returns the offset into the canvas and into the image along an axis.
+    let offsets = |size: u32, target: u32| {
+        if size <= target {
+            ((target - size) / 2, 0)
+        } else {
+            (0, (size - target) / 2)
+        }
+    };
+    let (dst_x, src_x) = offsets(image.width(), width);
+    let (dst_y, src_y) = offsets(image.height(), height);
+    let visible = imageops::crop_imm(
+        image,
+        src_x,
+        src_y,
+        image.width().min(width),
+        image.height().min(height),
+    );
+    let mut canvas = GrayImage::from_pixel(width, height, Luma([background]));
+    imageops::replace(&mut canvas, &visible, dst_x, dst_y);
+    canvas
+}
+
+/// Appends a blank page if the number of pages is odd, e.g. for readers which lay pages out in
+/// spreads.
+///
+/// The blank page has the dimensions and background color of the last page, where the
+/// background is the most common value along its edges. Empty and even batches are left
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::pad_to_even;
+///
+/// let mut pages = vec![GrayImage::from_pixel(30, 40, Luma([255]))];
+/// pad_to_even(&mut pages);
+///
+/// assert_eq!(pages.len(), 2);
+/// assert_eq!(pages[1], pages[0]);
+/// ```
+pub fn pad_to_even(pages: &mut Vec<GrayImage>) {
+    if pages.len().is_multiple_of(2) {
+        return;
+    }
+    if let Some(blank) = pages.last().map(blank_page) {
+        pages.push(blank);
+    }
+}
+
+/// Creates a blank page with the dimensions and background color of `page`.
+pub(crate) fn blank_page(page: &GrayImage) -> GrayImage {
+    let (width, height) = page.dimensions();
+    let mut counts = [0_u32; 256];
+    for (x, y, p) in page.enumerate_pixels() {
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            counts[p[0] as usize] += 1;
+        }
+    }
+    // Prefer lighter values on ties, as pages are usually white.
+    let background = (0..=255_u8)
+        .max_by_key(|&v| counts[v as usize])
+        .unwrap_or(255);
+    GrayImage::from_pixel(width, height, Luma([background]))
+}
+
+#[cfg(test)]
+mod tests {
+    use imageproc::assert_pixels_eq;
+
+    use super::*;
+
+    fn coordinates(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| Luma([(x + y * 2) as u8]))
+    }
+
+    #[test]
+    fn pad_to_odd_margin() {
+        let image = GrayImage::from_pixel(99, 99, Luma([0]));
+
+        let padded = pad_to(&image, 100, 100, 255);
+
+        // The extra pixel goes to the right and bottom.
+        assert_pixels_eq!(
+            padded,
+            GrayImage::from_fn(100, 100, |x, y| Luma([if x == 99 || y == 99 {
+                255
+            } else {
+                0
+            }]))
+        );
+        let padded = pad_to(&image, 102, 98, 255);
+        assert_eq!(padded.dimensions(), (102, 98));
+        let row: Vec<u8> = (0..102).map(|x| padded[(x, 50)][0]).collect();
+        assert_eq!(row.iter().position(|&p| p == 0), Some(1));
+        assert_eq!(row.iter().rposition(|&p| p == 0), Some(99));
+    }
+
+    #[test]
+    fn pad_to_crops_larger_images() {
+        let image = coordinates(5, 3);
+
+        let cropped = pad_to(&image, 2, 3, 255);
+
+        assert_pixels_eq!(cropped, GrayImage::from_fn(2, 3, |x, y| image[(x + 1, y)]));
+    }
+
+    #[test]
+    fn pad_to_even_adds_blank_page() {
+        // A dark page with a white border.
+        let page = GrayImage::from_fn(20, 30, |x, y| {
+            let border = x < 2 || y < 2 || x >= 18 || y >= 28;
+            Luma([if border { 250 } else { 10 }])
+        });
+        let mut pages = vec![page.clone(), page.clone(), page];
+
+        pad_to_even(&mut pages);
+
+        assert_eq!(pages.len(), 4);
+        assert_pixels_eq!(pages[3], GrayImage::from_pixel(20, 30, Luma([250])));
+
+        pad_to_even(&mut pages);
+        assert_eq!(pages.len(), 4);
+
+        let mut empty = Vec::new();
+        pad_to_even(&mut empty);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn center_square_landscape() {
+        let image = coordinates(100, 60);
+
+        let square = center_square(&image);
+
+        assert_pixels_eq!(
+            square,
+            GrayImage::from_fn(60, 60, |x, y| image[(x + 20, y)])
+        );
+    }
+
+    #[test]
+    fn center_square_portrait() {
+        let image = coordinates(60, 100);
+
+        let square = center_square(&image);
+
+        assert_pixels_eq!(
+            square,
+            GrayImage::from_fn(60, 60, |x, y| image[(x, y + 20)])
+        );
+    }
+
+    #[test]
+    fn center_square_square() {
+        let image = coordinates(30, 30);
+
+        assert_pixels_eq!(center_square(&image), image);
+    }
+
+    #[test]
+    fn center_square_extreme() {
+        let image = coordinates(1, 255);
+
+        assert_eq!(
+            center_square(&image),
+            GrayImage::from_fn(1, 1, |_, _| image[(0, 127)])
+        );
+    }
+
+    #[test]
+    fn atlas_packs_without_overlap() {
+        let images = vec![
+            GrayImage::from_pixel(30, 20, Luma([1])),
+            GrayImage::from_pixel(25, 35, Luma([2])),
+            GrayImage::from_pixel(40, 10, Luma([3])),
+        ];
+
+        let (texture, rects) = atlas(&images, 64).unwrap();
+
+        assert_eq!(rects.len(), 3);
+        for (i, (a, image)) in rects.iter().zip(&images).enumerate() {
+            assert_eq!((a.width, a.height), image.dimensions());
+            assert!(a.x + a.width <= texture.width() && a.y + a.height <= texture.height());
+            assert!(texture.width() <= 64 && texture.height() <= 64);
+            assert_eq!(texture.get_pixel(a.x, a.y), image.get_pixel(0, 0));
+            for b in &rects[i + 1..] {
+                let disjoint = a.x + a.width <= b.x
+                    || b.x + b.width <= a.x
+                    || a.y + a.height <= b.y
+                    || b.y + b.height <= a.y;
+                assert!(disjoint, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn atlas_full() {
+        let images = vec![GrayImage::new(40, 40), GrayImage::new(40, 40)];
+
+        match atlas(&images, 64) {
+            Err(Error::AtlasFull { index }) => assert_eq!(index, 1),
+            other => panic!("unexpected result: {:?}", other.map(|(_, rects)| rects)),
+        }
+    }
+
+    #[test]
+    fn split_spread_orders_pages() {
+        let spread = GrayImage::from_fn(5, 2, |x, _| Luma([x as u8]));
+
+        let ltr = split_spread(&spread, ReadingDirection::LeftToRight);
+        let rtl = split_spread(&spread, ReadingDirection::RightToLeft);
+
+        assert_pixels_eq!(ltr[0], GrayImage::from_fn(2, 2, |x, _| Luma([x as u8])));
+        assert_pixels_eq!(ltr[1], GrayImage::from_fn(3, 2, |x, _| Luma([x as u8 + 2])));
+        assert_eq!(rtl, vec![ltr[1].clone(), ltr[0].clone()]);
+    }
+
+    #[test]
+    fn split_spread_keeps_single_pages() {
+        let page = GrayImage::new(4, 6);
+
+        assert_eq!(
+            split_spread(&page, ReadingDirection::RightToLeft),
+            vec![page]
+        );
+    }
+
+    #[test]
+    fn split_vertical_strips() {
+        let strip = GrayImage::from_fn(100, 1000, |_, y| Luma([(y / 4) as u8]));
+
+        let equal = split_vertical(&strip, 3.0, SplitStrategy::Equal);
+        let from_top = split_vertical(&strip, 3.0, SplitStrategy::FromTop);
+
+        let heights = |strips: &[GrayImage]| strips.iter().map(|s| s.height()).collect::<Vec<_>>();
+        assert_eq!(heights(&equal), vec![250, 250, 250, 250]);
+        assert_eq!(heights(&from_top), vec![300, 300, 300, 100]);
+        assert_eq!(equal[1].get_pixel(0, 0)[0], 62);
+        assert_eq!(from_top[3].get_pixel(0, 99)[0], 249);
+    }
+
+    #[test]
+    fn split_vertical_keeps_normal_pages() {
+        let page = GrayImage::new(600, 800);
+
+        assert_eq!(split_vertical(&page, 3.0, SplitStrategy::Equal), vec![page]);
+    }
+}
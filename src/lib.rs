@@ -1,3 +1,56 @@
+mod analysis;
+mod batch;
+#[cfg(feature = "cbz")]
+pub mod cbz;
+mod color;
 mod convert;
+#[cfg(feature = "devices")]
+mod device;
+mod dither;
+mod error;
+mod filter;
+mod geometry;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+mod hdr;
+mod icc;
+mod integral;
+pub mod io;
+mod layout;
+mod local;
+mod overlay;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+mod resample;
+mod scan_lines;
+mod speckle;
 
-pub use convert::{convert, ConvertParams, ConvertParamsBuilder};
+pub use analysis::{estimate_cutoff, estimate_sharpness, is_text_page, match_gamma, quality_score};
+#[cfg(feature = "rayon")]
+pub use batch::convert_batch_parallel;
+pub use batch::{
+    convert_batch, convert_dir, BatchError, BatchParams, BatchParamsBuilder, BatchSummary, OnError,
+};
+pub use color::{is_color, to_gray, LumaWeights};
+pub use convert::{
+    apply_lut16, binding_constraint, clip_map, compute_bounds_batch, convert, convert_and_diff,
+    convert_frames, convert_if_changed, convert_into, convert_raw, convert_region, convert_source,
+    convert_tinted, convert_with_bounds, convert_with_mask, convert_with_progress,
+    convert_with_report, convert_with_stats, estimate_memory, export_lut_image, looks_converted,
+    process, resize_only, tone_only, Constraint, ContrastBounds, ContrastMethod, ConvertParams,
+    ConvertParamsBuilder, ConvertReport, ConvertStats, Dimension, Rounding, Source, Stage, Step,
+    ToneCurve,
+};
+#[cfg(feature = "devices")]
+pub use device::DeviceProfile;
+pub use dither::DitherMethod;
+pub use error::{BuildError, Error};
+pub use filter::convolve;
+pub use geometry::Rect;
+pub use hdr::{convert_f32, ToneMap};
+pub use layout::{
+    atlas, center_square, pad_to, pad_to_even, split_spread, split_vertical, ReadingDirection,
+    SplitStrategy,
+};
+pub use overlay::{overlay, preview_with_checkerboard, stamp_page_number, Corner};
+pub use resample::{filter_name, parse_filter, CustomFilter, EdgeMode, UpscaleMethod};
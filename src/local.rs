@@ -0,0 +1,69 @@
+use image::GrayImage;
+
+/// Remaps an image using a separate lookup table for each of `tiles` tiles.
+///
+/// `lut` builds the lookup table of a tile from its histogram. Each pixel is mapped through the
+/// tables of the four nearest tile centers and the results are blended bilinearly, so there are no
+/// visible seams at tile borders.
+pub(crate) fn local_stretch<L>(image: &mut GrayImage, tiles: (u32, u32), lut: L)
+where
+    L: Fn(&[u64; 256]) -> [u8; 256],
+{
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let tiles_x = tiles.0.clamp(1, width);
+    let tiles_y = tiles.1.clamp(1, height);
+    let tile_width = width as f32 / tiles_x as f32;
+    let tile_height = height as f32 / tiles_y as f32;
+
+    let tile_x = |x: u32| ((x as f32 / tile_width) as u32).min(tiles_x - 1);
+    let tile_y = |y: u32| ((y as f32 / tile_height) as u32).min(tiles_y - 1);
+
+    let mut histograms = vec![[0_u64; 256]; (tiles_x * tiles_y) as usize];
+    for (x, y, p) in image.enumerate_pixels() {
+        histograms[(tile_y(y) * tiles_x + tile_x(x)) as usize][p[0] as usize] += 1;
+    }
+    let luts: Vec<[u8; 256]> = histograms.iter().map(lut).collect();
+
+    // Returns the two nearest tile centers along an axis and the weight of the second one.
+    let neighbors = |pos: u32, size: f32, count: u32| {
+        let t = (pos as f32 + 0.5) / size - 0.5;
+        let first = t.floor().clamp(0.0, (count - 1) as f32);
+        let second = (first + 1.0).min((count - 1) as f32);
+        (first as u32, second as u32, (t - first).clamp(0.0, 1.0))
+    };
+
+    for y in 0..height {
+        let (y0, y1, wy) = neighbors(y, tile_height, tiles_y);
+        for x in 0..width {
+            let (x0, x1, wx) = neighbors(x, tile_width, tiles_x);
+            let p = image.get_pixel_mut(x, y);
+            let value =
+                |tx: u32, ty: u32| f32::from(luts[(ty * tiles_x + tx) as usize][p[0] as usize]);
+            let top = value(x0, y0) * (1.0 - wx) + value(x1, y0) * wx;
+            let bottom = value(x0, y1) * (1.0 - wx) + value(x1, y1) * wx;
+            p[0] = (top * (1.0 - wy) + bottom * wy).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_stretch_single_tile_matches_lut() {
+        let mut image = GrayImage::from_fn(10, 10, |x, y| image::Luma([(x * 10 + y) as u8]));
+        let want = GrayImage::from_fn(10, 10, |x, y| image::Luma([255 - (x * 10 + y) as u8]));
+
+        let mut invert = [0; 256];
+        for (i, v) in invert.iter_mut().enumerate() {
+            *v = 255 - i as u8;
+        }
+        local_stretch(&mut image, (1, 1), |_| invert);
+
+        assert_eq!(image, want);
+    }
+}
@@ -0,0 +1,283 @@
+use image::{GrayImage, Luma, Pixel, RgbaImage};
+use imageproc::drawing::draw_filled_rect_mut;
+use imageproc::rect::Rect as DrawRect;
+
+/// Identifies a corner of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    /// The top left corner.
+    TopLeft,
+    /// The top right corner.
+    TopRight,
+    /// The bottom left corner.
+    BottomLeft,
+    /// The bottom right corner.
+    BottomRight,
+}
+
+/// 3x5 pixel glyphs for digits 0-9 followed by a slash. Each row is stored in the lowest 3 bits,
+/// most significant bit on the left.
+const GLYPHS: [[u8; 5]; 11] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+    [0b001, 0b001, 0b010, 0b100, 0b100],
+];
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Draws a page number in `"page/total"` format into a corner of an image.
+///
+/// `index` is zero-based, so the first page is displayed as 1. Digits are drawn in black on a
+/// white box, scaled with the size of the image. If the image is too small to fit the text, it's
+/// left unchanged and `false` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::{stamp_page_number, Corner};
+///
+/// let mut page = GrayImage::new(600, 800);
+///
+/// assert!(stamp_page_number(&mut page, 0, 12, Corner::BottomRight));
+/// ```
+pub fn stamp_page_number(
+    image: &mut GrayImage,
+    index: usize,
+    total: usize,
+    position: Corner,
+) -> bool {
+    let text = format!("{}/{}", index + 1, total);
+    let scale = (image.width().min(image.height()) / 200).max(1);
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    let padding = 2 * scale;
+    let box_width = text.len() as u32 * advance - scale + 2 * padding;
+    let box_height = GLYPH_HEIGHT * scale + 2 * padding;
+    if box_width + padding > image.width() || box_height + padding > image.height() {
+        return false;
+    }
+
+    let (left, top) = match position {
+        Corner::TopLeft => (padding, padding),
+        Corner::TopRight => (image.width() - padding - box_width, padding),
+        Corner::BottomLeft => (padding, image.height() - padding - box_height),
+        Corner::BottomRight => (
+            image.width() - padding - box_width,
+            image.height() - padding - box_height,
+        ),
+    };
+    draw_filled_rect_mut(
+        image,
+        DrawRect::at(left as i32, top as i32).of_size(box_width, box_height),
+        Luma([255]),
+    );
+
+    for (i, c) in text.chars().enumerate() {
+        let glyph = match c.to_digit(10) {
+            Some(d) => GLYPHS[d as usize],
+            None => GLYPHS[10],
+        };
+        let x0 = left + padding + i as u32 * advance;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    let x = x0 + col * scale;
+                    let y = top + padding + row as u32 * scale;
+                    draw_filled_rect_mut(
+                        image,
+                        DrawRect::at(x as i32, y as i32).of_size(scale, scale),
+                        Luma([0]),
+                    );
+                }
+            }
+        }
+    }
+    true
+}
+
+/// Blends a stamp image, e.g. a watermark, into a corner of an image.
+///
+/// Each pixel becomes `base * (1 - opacity) + stamp * opacity`, with `opacity` clamped to `0..=1`.
+/// The stamp is placed flush with the corner. Parts of a stamp larger than the image are clipped.
+///
+/// # Examples
+///
+/// ```
+/// use image::{GrayImage, Luma};
+/// use maco::{overlay, Corner};
+///
+/// let mut page = GrayImage::from_pixel(100, 100, Luma([200]));
+/// overlay(&mut page, &GrayImage::new(10, 10), Corner::BottomRight, 0.25);
+///
+/// assert_eq!(page.get_pixel(99, 99)[0], 150);
+/// assert_eq!(page.get_pixel(89, 89)[0], 200);
+/// ```
+pub fn overlay(base: &mut GrayImage, stamp: &GrayImage, position: Corner, opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    let width = stamp.width().min(base.width());
+    let height = stamp.height().min(base.height());
+    let (x0, y0) = match position {
+        Corner::TopLeft => (0, 0),
+        Corner::TopRight => (base.width() - width, 0),
+        Corner::BottomLeft => (0, base.height() - height),
+        Corner::BottomRight => (base.width() - width, base.height() - height),
+    };
+    // Clip the side of the stamp facing away from the corner.
+    let (sx0, sy0) = match position {
+        Corner::TopLeft => (0, 0),
+        Corner::TopRight => (stamp.width() - width, 0),
+        Corner::BottomLeft => (0, stamp.height() - height),
+        Corner::BottomRight => (stamp.width() - width, stamp.height() - height),
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let s = f32::from(stamp.get_pixel(sx0 + x, sy0 + y)[0]);
+            let p = base.get_pixel_mut(x0 + x, y0 + y);
+            p[0] = (f32::from(p[0]) * (1.0 - opacity) + s * opacity).round() as u8;
+        }
+    }
+}
+
+/// Size of a single checkerboard square in pixels.
+const CHECKER_SIZE: u32 = 8;
+/// Light and dark checkerboard values.
+const CHECKER_VALUES: [u8; 2] = [204, 153];
+
+/// Converts an image to grayscale, compositing it over a checkerboard.
+///
+/// Useful for previewing which regions of an image are transparent before deciding what color to
+/// flatten them to. The checkerboard starts with a light square in the top left corner.
+///
+/// # Examples
+///
+/// ```
+/// use image::{Rgba, RgbaImage};
+/// use maco::preview_with_checkerboard;
+///
+/// let image = RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 0]));
+/// let preview = preview_with_checkerboard(&image);
+///
+/// assert_eq!(preview.get_pixel(0, 0)[0], 204);
+/// assert_eq!(preview.get_pixel(8, 0)[0], 153);
+/// ```
+pub fn preview_with_checkerboard(image: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = image.get_pixel(x, y);
+        let value = f32::from(pixel.to_luma()[0]);
+        let alpha = f32::from(pixel[3]) / 255.0;
+        let background =
+            f32::from(CHECKER_VALUES[((x / CHECKER_SIZE + y / CHECKER_SIZE) % 2) as usize]);
+        Luma([(value * alpha + background * (1.0 - alpha)).round() as u8])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the bounding box of pixels which differ between two images.
+    fn changed_area(a: &GrayImage, b: &GrayImage) -> Option<(u32, u32, u32, u32)> {
+        let mut area: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, p) in a.enumerate_pixels() {
+            if p != b.get_pixel(x, y) {
+                area = Some(match area {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+        area
+    }
+
+    #[test]
+    fn stamp_page_number_corners() {
+        let original = GrayImage::from_pixel(100, 80, Luma([128]));
+
+        for &corner in &[
+            Corner::TopLeft,
+            Corner::TopRight,
+            Corner::BottomLeft,
+            Corner::BottomRight,
+        ] {
+            let mut image = original.clone();
+            assert!(stamp_page_number(&mut image, 2, 10, corner));
+
+            let (x0, y0, x1, y1) = changed_area(&original, &image).unwrap();
+            let (left, top) = (x1 < 50, y1 < 40);
+            let (right, bottom) = (x0 >= 50, y0 >= 40);
+            match corner {
+                Corner::TopLeft => assert!(left && top),
+                Corner::TopRight => assert!(right && top),
+                Corner::BottomLeft => assert!(left && bottom),
+                Corner::BottomRight => assert!(right && bottom),
+            }
+            assert!(image.pixels().any(|p| p[0] == 0));
+        }
+    }
+
+    #[test]
+    fn stamp_page_number_too_small() {
+        let original = GrayImage::from_pixel(10, 10, Luma([128]));
+        let mut image = original.clone();
+
+        assert!(!stamp_page_number(&mut image, 99, 100, Corner::TopLeft));
+        assert_eq!(image, original);
+    }
+
+    #[test]
+    fn preview_with_checkerboard_composites() {
+        let image = RgbaImage::from_fn(32, 16, |x, _| {
+            if x < 16 {
+                image::Rgba([0, 0, 0, 0])
+            } else {
+                image::Rgba([10, 10, 10, 255])
+            }
+        });
+
+        let preview = preview_with_checkerboard(&image);
+
+        for (x, y, p) in preview.enumerate_pixels() {
+            let want = if x >= 16 {
+                10
+            } else if (x / 8 + y / 8) % 2 == 0 {
+                204
+            } else {
+                153
+            };
+            assert_eq!(p[0], want, "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn overlay_blends_stamp() {
+        let mut base = GrayImage::from_pixel(30, 20, Luma([100]));
+        let stamp = GrayImage::from_fn(10, 10, |x, _| Luma([if x < 5 { 0 } else { 255 }]));
+
+        overlay(&mut base, &stamp, Corner::TopLeft, 0.5);
+
+        assert_eq!(base.get_pixel(0, 0)[0], 50);
+        assert_eq!(base.get_pixel(9, 9)[0], 178);
+        assert_eq!(base.get_pixel(10, 0)[0], 100);
+        assert_eq!(base.get_pixel(0, 10)[0], 100);
+    }
+
+    #[test]
+    fn overlay_clips_and_clamps() {
+        let mut base = GrayImage::from_pixel(4, 4, Luma([100]));
+        let stamp = GrayImage::from_fn(6, 6, |x, y| Luma([(x + y * 6) as u8]));
+
+        overlay(&mut base, &stamp, Corner::BottomRight, 2.0);
+
+        // Fully opaque, showing the bottom right 4x4 of the stamp.
+        assert_eq!(base.get_pixel(0, 0)[0], 2 + 2 * 6);
+        assert_eq!(base.get_pixel(3, 3)[0], 5 + 5 * 6);
+    }
+}
@@ -0,0 +1,176 @@
+//! Writing PDF documents.
+
+use std::io::Write;
+
+use image::GrayImage;
+
+use crate::Error;
+
+/// Resolution pages are laid out at, in dots per inch. PDF units are 1/72 inch.
+const PDF_DPI: f64 = 150.0;
+
+/// Writes images as the pages of a PDF document, one image per page.
+///
+/// Each page is sized to its image at 150 dpi, so pages keep the aspect ratio of their images.
+/// Images are embedded losslessly as compressed 8-bit grayscale and should be converted
+/// beforehand, e.g. with [`convert_batch()`](crate::convert_batch).
+///
+/// # Examples
+///
+/// ```
+/// use image::GrayImage;
+/// use maco::pdf::batch_to_pdf;
+///
+/// let mut pdf = Vec::new();
+/// batch_to_pdf(&[GrayImage::new(150, 300)], &mut pdf).unwrap();
+///
+/// assert!(pdf.starts_with(b"%PDF-1.4"));
+/// ```
+pub fn batch_to_pdf(images: &[GrayImage], mut out: impl Write) -> Result<(), Error> {
+    let mut pdf = PdfWriter::default();
+    pdf.write(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+
+    // Objects 1 and 2 are the catalog and the page tree, followed by three objects per page: the
+    // page itself, its content stream and its image.
+    let page_id = |i: usize| 3 + 3 * i;
+    pdf.object(1, b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<String> = (0..images.len())
+        .map(|i| format!("{} 0 R", page_id(i)))
+        .collect();
+    pdf.object(
+        2,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            images.len()
+        )
+        .as_bytes(),
+    );
+
+    for (i, image) in images.iter().enumerate() {
+        let id = page_id(i);
+        let points = |pixels: u32| f64::from(pixels) * 72.0 / PDF_DPI;
+        let (width, height) = (points(image.width()), points(image.height()));
+        pdf.object(
+            id,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                width,
+                height,
+                id + 2,
+                id + 1
+            )
+            .as_bytes(),
+        );
+        let content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q", width, height);
+        pdf.stream(id + 1, "", content.as_bytes());
+        let data = deflate::deflate_bytes_zlib(image);
+        let dict = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray \
+             /BitsPerComponent 8 /Filter /FlateDecode ",
+            image.width(),
+            image.height()
+        );
+        pdf.stream(id + 2, &dict, &data);
+    }
+
+    pdf.finish();
+    out.write_all(&pdf.buffer)?;
+    Ok(())
+}
+
+/// Accumulates a PDF document, keeping track of object offsets for the cross-reference table.
+#[derive(Default)]
+struct PdfWriter {
+    buffer: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn write(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Writes an object. Objects are expected in order of their ids, starting at 1.
+    fn object(&mut self, id: usize, body: &[u8]) {
+        debug_assert_eq!(id, self.offsets.len() + 1);
+        self.offsets.push(self.buffer.len());
+        self.write(format!("{} 0 obj\n", id).as_bytes());
+        self.write(body);
+        self.write(b"\nendobj\n");
+    }
+
+    /// Writes a stream object with the given dictionary entries, which have to end in a space.
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        let mut body = format!("<< {}/Length {} >>\nstream\n", dict, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.object(id, &body);
+    }
+
+    /// Writes the cross-reference table and the trailer.
+    fn finish(&mut self) {
+        let start = self.buffer.len();
+        let size = self.offsets.len() + 1;
+        self.write(format!("xref\n0 {}\n0000000000 65535 f \n", size).as_bytes());
+        for offset in self.offsets.clone() {
+            self.write(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        self.write(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                size, start
+            )
+            .as_bytes(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    /// Returns the position of `needle` in `haystack` at or after `from`.
+    fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+        haystack[from..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|i| i + from)
+    }
+
+    #[test]
+    fn batch_to_pdf_writes_pages() {
+        let images = vec![
+            GrayImage::from_fn(30, 60, |x, y| Luma([(x + y) as u8])),
+            GrayImage::from_pixel(90, 45, Luma([200])),
+        ];
+        let mut pdf = Vec::new();
+
+        batch_to_pdf(&images, &mut pdf).unwrap();
+
+        // Follow the cross-reference table to every object.
+        let trailer = std::str::from_utf8(&pdf[pdf.len() - 32..]).unwrap_or_default();
+        let start: usize = trailer
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|s| s.lines().next())
+            .and_then(|s| s.parse().ok())
+            .unwrap();
+        let xref = std::str::from_utf8(&pdf[start..]).unwrap();
+        assert!(xref.starts_with("xref\n0 9\n"));
+        for (i, entry) in xref.lines().skip(3).take(8).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+
+        assert!(find(&pdf, b"/Type /Pages /Kids [3 0 R 6 0 R] /Count 2", 0).is_some());
+        assert!(find(&pdf, b"/MediaBox [0 0 14.40 28.80]", 0).is_some());
+        assert!(find(&pdf, b"/MediaBox [0 0 43.20 21.60]", 0).is_some());
+        assert!(find(&pdf, b"/Type /Page ", 0)
+            .and_then(|i| find(&pdf, b"/Type /Page ", i + 1))
+            .is_some());
+    }
+}
@@ -0,0 +1,636 @@
+use image::imageops::{self, FilterType};
+use image::{GenericImageView, GrayImage, ImageBuffer, Luma};
+
+use crate::geometry::Rect;
+use crate::integral::IntegralImage;
+
+/// Parses the name of a resampling filter, as returned by [`filter_name()`].
+///
+/// Matching is case-insensitive and accepts common aliases: `bilinear` for
+/// [`FilterType::Triangle`], `bicubic` for [`FilterType::CatmullRom`] and `lanczos` for
+/// [`FilterType::Lanczos3`].
+///
+/// # Examples
+///
+/// ```
+/// use image::imageops::FilterType;
+/// use maco::parse_filter;
+///
+/// assert_eq!(parse_filter("Bicubic"), Some(FilterType::CatmullRom));
+/// assert_eq!(parse_filter("sharp"), None);
+/// ```
+pub fn parse_filter(s: &str) -> Option<FilterType> {
+    match s.to_ascii_lowercase().as_str() {
+        "nearest" => Some(FilterType::Nearest),
+        "triangle" | "bilinear" => Some(FilterType::Triangle),
+        "catmull-rom" | "catmullrom" | "bicubic" => Some(FilterType::CatmullRom),
+        "gaussian" => Some(FilterType::Gaussian),
+        "lanczos3" | "lanczos" => Some(FilterType::Lanczos3),
+        _ => None,
+    }
+}
+
+/// Returns the canonical name of a resampling filter, which [`parse_filter()`] parses back.
+pub fn filter_name(filter: FilterType) -> &'static str {
+    match filter {
+        FilterType::Nearest => "nearest",
+        FilterType::Triangle => "triangle",
+        FilterType::CatmullRom => "catmull-rom",
+        FilterType::Gaussian => "gaussian",
+        FilterType::Lanczos3 => "lanczos3",
+    }
+}
+
+/// Defines how images are enlarged when upscaling is enabled.
+///
+/// See also: [`ConvertParamsBuilder::upscale_method()`](crate::ConvertParamsBuilder::upscale_method).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpscaleMethod {
+    /// Interpolates using the configured resampling filter.
+    Interpolate,
+    /// Interpolates bilinearly, but sharpens the transition across strong edges. Keeps line art
+    /// crisp at the cost of slightly jagged diagonals.
+    EdgeDirected,
+}
+
+/// Defines how the resampling filter treats pixels beyond the edges of the image.
+///
+/// See also: [`ConvertParamsBuilder::resize_edge()`](crate::ConvertParamsBuilder::resize_edge).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Drops the part of the kernel outside the image, as the image crate does. Content touching
+    /// an edge can come out lighter or darker than it should.
+    Truncate,
+    /// Repeats the outermost pixels.
+    Replicate,
+    /// Mirrors the image at its edges, without repeating the outermost pixels.
+    Reflect,
+}
+
+/// Defines a tunable resampling filter, overriding the fixed [`FilterType`](image::imageops::FilterType)
+/// kernels.
+///
+/// See also: [`ConvertParamsBuilder::custom_filter()`](crate::ConvertParamsBuilder::custom_filter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomFilter {
+    /// Lanczos windowed sinc with `a` lobes on each side. Lower values reduce ringing around sharp
+    /// edges, higher values preserve more detail. `a = 3` is equivalent to
+    /// [`FilterType::Lanczos3`](image::imageops::FilterType::Lanczos3). `a = 0` is treated as 1.
+    Lanczos {
+        /// Number of lobes of the kernel.
+        a: u32,
+    },
+}
+
+impl CustomFilter {
+    /// Returns the radius of the kernel in source pixels, before scaling.
+    pub(crate) fn support(self) -> f32 {
+        match self {
+            CustomFilter::Lanczos { a } => a.max(1) as f32,
+        }
+    }
+
+    /// Evaluates the kernel at `x`.
+    fn kernel(self, x: f32) -> f32 {
+        match self {
+            CustomFilter::Lanczos { a } => {
+                let a = a.max(1) as f32;
+                if x.abs() < a {
+                    sinc(x) * sinc(x / a)
+                } else {
+                    0_f32
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0_f32 {
+        1_f32
+    } else {
+        let x = x * std::f32::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Resizes an image into `out` using one of the fixed filters, vertically first.
+///
+/// The result is identical to [`imageops::resize()`], which always allocates a new output buffer.
+pub(crate) fn resize_fixed(image: &GrayImage, out: &mut GrayImage, filter: FilterType) {
+    let (src_width, src_height) = image.dimensions();
+    let (width, height) = out.dimensions();
+
+    // Vertical pass, src_width x src_height -> src_width x height, rounded like the image crate.
+    let row_len = src_width as usize;
+    let mut vertical = GrayImage::new(src_width, height);
+    let mut sums = vec![0_f32; row_len];
+    for (y, row) in vertical.chunks_exact_mut(row_len).enumerate() {
+        let (start, weights, total) = fixed_weights(y as u32, src_height, height, filter);
+        sums.iter_mut().for_each(|s| *s = 0_f32);
+        for (i, w) in weights.iter().enumerate() {
+            let src_row = &image.as_raw()[(start as usize + i) * row_len..][..row_len];
+            for (s, &p) in sums.iter_mut().zip(src_row) {
+                *s += f32::from(p) * w;
+            }
+        }
+        for (p, s) in row.iter_mut().zip(&sums) {
+            *p = round_sample(s / total);
+        }
+    }
+
+    // Horizontal pass, src_width x height -> width x height.
+    for x in 0..width {
+        let (start, weights, total) = fixed_weights(x, src_width, width, filter);
+        for y in 0..height {
+            let mut sum = 0_f32;
+            for (i, w) in weights.iter().enumerate() {
+                sum += f32::from(vertical.get_pixel(start + i as u32, y)[0]) * w;
+            }
+            out.put_pixel(x, y, Luma([round_sample(sum / total)]));
+        }
+    }
+}
+
+/// Returns the first source index, weights and the sum of weights contributing to output index
+/// `i` when resizing with a fixed filter, as computed by the image crate.
+fn fixed_weights(i: u32, src_len: u32, len: u32, filter: FilterType) -> (u32, Vec<f32>, f32) {
+    let (kernel, support): (fn(f32) -> f32, f32) = match filter {
+        FilterType::Nearest => (|_| 1_f32, 0_f32),
+        FilterType::Triangle => (|x| (1_f32 - x.abs()).max(0_f32), 1_f32),
+        FilterType::CatmullRom => (catmull_rom, 2_f32),
+        FilterType::Gaussian => (gaussian, 3_f32),
+        FilterType::Lanczos3 => (|x| CustomFilter::Lanczos { a: 3 }.kernel(x), 3_f32),
+    };
+    let ratio = src_len as f32 / len as f32;
+    let scale = ratio.max(1_f32);
+    let src_support = support * scale;
+    let center = (i as f32 + 0.5) * ratio;
+
+    let start = ((center - src_support).floor() as i64).clamp(0, i64::from(src_len) - 1);
+    let end = ((center + src_support).ceil() as i64).clamp(start + 1, i64::from(src_len));
+    let center = center - 0.5;
+    let weights: Vec<f32> = (start..end)
+        .map(|j| kernel((j as f32 - center) / scale))
+        .collect();
+    let mut total = 0_f32;
+    for w in &weights {
+        total += w;
+    }
+    (start as u32, weights, total)
+}
+
+/// Rounds a resampled value to the nearest 8-bit value.
+fn round_sample(v: f32) -> u8 {
+    v.clamp(0_f32, 255_f32).round() as u8
+}
+
+/// Catmull-Rom spline, written out like the image crate does to produce identical weights.
+fn catmull_rom(x: f32) -> f32 {
+    let (b, c) = (0_f32, 0.5_f32);
+    let a = x.abs();
+    let k = if a < 1_f32 {
+        (12_f32 - 9_f32 * b - 6_f32 * c) * a.powi(3)
+            + (-18_f32 + 12_f32 * b + 6_f32 * c) * a.powi(2)
+            + (6_f32 - 2_f32 * b)
+    } else if a < 2_f32 {
+        (-b - 6_f32 * c) * a.powi(3)
+            + (6_f32 * b + 30_f32 * c) * a.powi(2)
+            + (-12_f32 * b - 48_f32 * c) * a
+            + (8_f32 * b + 24_f32 * c)
+    } else {
+        0_f32
+    };
+    k / 6_f32
+}
+
+/// Gaussian with a standard deviation of 0.5, as used by the image crate.
+fn gaussian(x: f32) -> f32 {
+    let r = 0.5_f32;
+    ((2_f32 * std::f32::consts::PI).sqrt() * r).recip() * (-x.powi(2) / (2_f32 * r.powi(2))).exp()
+}
+
+/// Resizes an image into `out` using a custom filter, one axis at a time.
+pub(crate) fn resize_custom(image: &GrayImage, out: &mut GrayImage, filter: CustomFilter) {
+    let (src_width, src_height) = image.dimensions();
+    let (width, height) = out.dimensions();
+    let src: Vec<f32> = image.iter().map(|&p| f32::from(p)).collect();
+
+    // Horizontal pass, src_width x src_height -> width x src_height.
+    let mut horizontal = vec![0_f32; (width * src_height) as usize];
+    for x in 0..width {
+        let (start, weights) = axis_weights(x, src_width, width, filter);
+        for y in 0..src_height {
+            let row = (y * src_width) as usize;
+            let value: f32 = weights
+                .iter()
+                .enumerate()
+                .map(|(i, w)| src[row + start + i] * w)
+                .sum();
+            horizontal[(y * width + x) as usize] = value;
+        }
+    }
+
+    // Vertical pass, width x src_height -> width x height.
+    for y in 0..height {
+        let (start, weights) = axis_weights(y, src_height, height, filter);
+        for x in 0..width {
+            let value: f32 = weights
+                .iter()
+                .enumerate()
+                .map(|(i, w)| horizontal[((start + i) as u32 * width + x) as usize] * w)
+                .sum();
+            out.put_pixel(x, y, Luma([value.round().clamp(0_f32, 255_f32) as u8]));
+        }
+    }
+}
+
+/// Returns the first source index and normalized filter weights contributing to output index `i`.
+fn axis_weights(i: u32, src_len: u32, len: u32, filter: CustomFilter) -> (usize, Vec<f32>) {
+    let ratio = src_len as f32 / len as f32;
+    // When downscaling, stretch the kernel to cover all contributing source pixels.
+    let scale = ratio.max(1_f32);
+    let support = filter.support() * scale;
+    let center = (i as f32 + 0.5) * ratio;
+
+    let start = ((center - support).floor() as i64).max(0) as usize;
+    let end = ((center + support).ceil() as i64).min(i64::from(src_len)) as usize;
+    let mut weights: Vec<f32> = (start..end)
+        .map(|j| filter.kernel((j as f32 + 0.5 - center) / scale))
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    if sum != 0_f32 {
+        for w in &mut weights {
+            *w /= sum;
+        }
+    }
+    (start, weights)
+}
+
+/// Resizes an image into `out` in linear light: values are decoded from sRGB, resized with
+/// `filter` at 16-bit precision and encoded back.
+pub(crate) fn resize_linear(image: &GrayImage, out: &mut GrayImage, filter: FilterType) {
+    let resized = resize_linear16(image, out.width(), out.height(), filter);
+    for (o, &v) in out.iter_mut().zip(resized.iter()) {
+        *o = encode_linear(f32::from(v) / 65535.0);
+    }
+}
+
+/// Like [`resize_linear()`], but maps the encoded values through the lookup table returned by
+/// `lut` for their histogram while encoding them back.
+///
+/// The result is identical to mapping the output of [`resize_linear()`] through the table, while
+/// saving a pass over the image and the intermediate histogram pass.
+pub(crate) fn resize_linear_with<L>(
+    image: &GrayImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+    lut: L,
+) -> GrayImage
+where
+    L: FnOnce(&[u64; 256]) -> [u8; 256],
+{
+    let resized = resize_linear16(image, width, height, filter);
+    let encode: Vec<u8> = (0..=u16::MAX)
+        .map(|v| encode_linear(f32::from(v) / 65535.0))
+        .collect();
+    let mut hist = [0_u64; 256];
+    for &v in resized.iter() {
+        hist[usize::from(encode[usize::from(v)])] += 1;
+    }
+    let lut = lut(&hist);
+    let output = resized
+        .iter()
+        .map(|&v| lut[usize::from(encode[usize::from(v)])])
+        .collect();
+    GrayImage::from_raw(width, height, output).expect("buffer matches dimensions")
+}
+
+/// Decodes an image from sRGB into 16-bit linear light and resizes it.
+fn resize_linear16(
+    image: &GrayImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+    let mut decode = [0_u16; 256];
+    for (i, d) in decode.iter_mut().enumerate() {
+        *d = (srgb_to_linear(i as f32 / 255.0) * 65535.0).round() as u16;
+    }
+    let linear: ImageBuffer<Luma<u16>, Vec<u16>> =
+        ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            Luma([decode[usize::from(image.get_pixel(x, y)[0])]])
+        });
+    imageops::resize(&linear, width, height, filter)
+}
+
+/// Encodes a linear light value in range `0..=1` into an 8-bit sRGB value.
+fn encode_linear(v: f32) -> u8 {
+    (linear_to_srgb(v) * 255.0).round() as u8
+}
+
+/// Decodes an sRGB value in range `0..=1` into linear light.
+pub(crate) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear light value in range `0..=1` into sRGB.
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Extends an image by `border` pixels on every side, filling the new pixels according to `mode`.
+/// [`EdgeMode::Truncate`] is treated as [`EdgeMode::Replicate`].
+pub(crate) fn pad_edges(image: &GrayImage, border: u32, mode: EdgeMode) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let source = |i: u32, len: u32| {
+        let i = i64::from(i) - i64::from(border);
+        let last = i64::from(len) - 1;
+        let i = match mode {
+            EdgeMode::Truncate | EdgeMode::Replicate => i,
+            EdgeMode::Reflect => {
+                let i = i.abs();
+                if i > last {
+                    2 * last - i
+                } else {
+                    i
+                }
+            }
+        };
+        i.clamp(0, last) as u32
+    };
+    GrayImage::from_fn(width + 2 * border, height + 2 * border, |x, y| {
+        *image.get_pixel(source(x, width), source(y, height))
+    })
+}
+
+/// Downscales an image by an integer factor, averaging each `factor` x `factor` block of pixels.
+/// Blocks at the right and bottom edges may be smaller.
+pub(crate) fn box_downscale(image: &GrayImage, factor: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let factor = factor.max(1);
+    let integral = IntegralImage::new(image);
+    GrayImage::from_fn(width.div_ceil(factor), height.div_ceil(factor), |x, y| {
+        Luma([integral.mean(Rect::new(x * factor, y * factor, factor, factor))])
+    })
+}
+
+/// Like [`box_downscale()`], but averages the blocks in linear light.
+pub(crate) fn box_downscale_linear(image: &GrayImage, factor: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let factor = factor.max(1);
+    let mut decode = [0_f32; 256];
+    for (i, d) in decode.iter_mut().enumerate() {
+        *d = srgb_to_linear(i as f32 / 255.0);
+    }
+    GrayImage::from_fn(width.div_ceil(factor), height.div_ceil(factor), |x, y| {
+        let block = Rect::new(x * factor, y * factor, factor, factor).clamp_to(width, height);
+        let block = imageops::crop_imm(image, block.x, block.y, block.width, block.height);
+        let sum: f32 = block
+            .pixels()
+            .map(|(_, _, p)| decode[usize::from(p[0])])
+            .sum();
+        let count = block.width() * block.height();
+        Luma([encode_linear(sum / count as f32)])
+    })
+}
+
+/// Difference between neighboring pixels above which they're considered to be on an edge.
+const EDGE_THRESHOLD: u8 = 32;
+
+/// How much steeper the transition across an edge is compared to plain bilinear interpolation.
+const EDGE_SHARPNESS: f32 = 4.0;
+
+/// Resizes an image using edge-adaptive bilinear interpolation.
+///
+/// For each output pixel, the 4 surrounding source pixels are examined. If neighbors differ by more
+/// than [`EDGE_THRESHOLD`] along an axis, interpolation weights along that axis are sharpened so
+/// the transition happens over a fraction of the output pixels. The output size is given by `out`.
+pub(crate) fn edge_directed(image: &GrayImage, out: &mut GrayImage) {
+    let (src_width, src_height) = image.dimensions();
+    let x_ratio = src_width as f32 / out.width() as f32;
+    let y_ratio = src_height as f32 / out.height() as f32;
+
+    for (x, y, p) in out.enumerate_pixels_mut() {
+        let (x0, x1, fx) = sample_position(x, x_ratio, src_width);
+        let (y0, y1, fy) = sample_position(y, y_ratio, src_height);
+
+        let a = image.get_pixel(x0, y0)[0];
+        let b = image.get_pixel(x1, y0)[0];
+        let c = image.get_pixel(x0, y1)[0];
+        let d = image.get_pixel(x1, y1)[0];
+
+        let fx = if a.abs_diff(b).max(c.abs_diff(d)) > EDGE_THRESHOLD {
+            sharpen_weight(fx)
+        } else {
+            fx
+        };
+        let fy = if a.abs_diff(c).max(b.abs_diff(d)) > EDGE_THRESHOLD {
+            sharpen_weight(fy)
+        } else {
+            fy
+        };
+
+        let top = lerp(f32::from(a), f32::from(b), fx);
+        let bottom = lerp(f32::from(c), f32::from(d), fx);
+        *p = Luma([lerp(top, bottom, fy).round() as u8]);
+    }
+}
+
+/// Maps an output coordinate onto the source image, returning the two neighboring source
+/// coordinates and the fractional position between them.
+fn sample_position(i: u32, ratio: f32, len: u32) -> (u32, u32, f32) {
+    let pos = ((i as f32 + 0.5) * ratio - 0.5).max(0_f32);
+    let i0 = (pos.floor() as u32).min(len - 1);
+    let i1 = (i0 + 1).min(len - 1);
+    (i0, i1, pos - i0 as f32)
+}
+
+/// Steepens an interpolation weight around its midpoint.
+fn sharpen_weight(f: f32) -> f32 {
+    ((f - 0.5) * EDGE_SHARPNESS + 0.5).clamp(0_f32, 1_f32)
+}
+
+fn lerp(a: f32, b: f32, f: f32) -> f32 {
+    a + (b - a) * f
+}
+
+#[cfg(test)]
+mod tests {
+    use image::imageops::resize;
+
+    use super::*;
+
+    /// Counts pixels which are neither close to black nor to white.
+    fn transition_pixels(image: &GrayImage) -> usize {
+        image.pixels().filter(|p| p[0] > 16 && p[0] < 239).count()
+    }
+
+    #[test]
+    fn edge_directed_is_sharper_than_bicubic() {
+        let image = GrayImage::from_fn(8, 8, |x, y| Luma([if x + y < 8 { 0 } else { 255 }]));
+
+        let bicubic = resize(&image, 32, 32, FilterType::CatmullRom);
+        let mut edge = GrayImage::new(32, 32);
+        edge_directed(&image, &mut edge);
+
+        assert!(transition_pixels(&edge) < transition_pixels(&bicubic));
+    }
+
+    #[test]
+    fn filter_name_round_trip() {
+        let filters = [
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::CatmullRom,
+            FilterType::Gaussian,
+            FilterType::Lanczos3,
+        ];
+        for &filter in filters.iter() {
+            assert_eq!(parse_filter(filter_name(filter)), Some(filter));
+            assert_eq!(
+                parse_filter(&filter_name(filter).to_uppercase()),
+                Some(filter)
+            );
+        }
+        assert_eq!(parse_filter("bilinear"), Some(FilterType::Triangle));
+        assert_eq!(parse_filter(""), None);
+    }
+
+    #[test]
+    fn lanczos_lobes_change_ringing() {
+        let image = GrayImage::from_fn(64, 1, |x, _| Luma([if x < 32 { 32 } else { 224 }]));
+
+        let (mut a2, mut a3) = (GrayImage::new(24, 1), GrayImage::new(24, 1));
+        resize_custom(&image, &mut a2, CustomFilter::Lanczos { a: 2 });
+        resize_custom(&image, &mut a3, CustomFilter::Lanczos { a: 3 });
+        // Overshoot around the edge, beyond the original values.
+        let ringing = |image: &GrayImage| {
+            image
+                .pixels()
+                .map(|p| {
+                    32_i32
+                        .saturating_sub(p[0].into())
+                        .max(i32::from(p[0]) - 224)
+                })
+                .max()
+                .unwrap()
+        };
+
+        assert_ne!(a2, a3);
+        assert!(ringing(&a2) < ringing(&a3));
+        // Both keep the flat areas intact.
+        assert_eq!(a2.get_pixel(0, 0)[0], 32);
+        assert_eq!(a3.get_pixel(23, 0)[0], 224);
+    }
+
+    #[test]
+    fn lanczos3_matches_image() {
+        let image = GrayImage::from_fn(40, 30, |x, y| Luma([(x * 4 + y * 3) as u8]));
+
+        let want = resize(&image, 15, 11, FilterType::Lanczos3);
+        let mut got = GrayImage::new(15, 11);
+        resize_custom(&image, &mut got, CustomFilter::Lanczos { a: 3 });
+
+        let max_diff = want
+            .iter()
+            .zip(got.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max();
+        assert!(max_diff.unwrap() <= 2, "{:?}", max_diff);
+    }
+
+    #[test]
+    fn resize_fixed_matches_image() {
+        let image = GrayImage::from_fn(37, 23, |x, y| Luma([((x * 41 + y * 17) % 256) as u8]));
+        let filters = [
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::CatmullRom,
+            FilterType::Gaussian,
+            FilterType::Lanczos3,
+        ];
+        for &filter in filters.iter() {
+            for &(width, height) in [(12, 9), (37, 23), (80, 50), (20, 61), (1, 1)].iter() {
+                let mut got = GrayImage::new(width, height);
+                resize_fixed(&image, &mut got, filter);
+                assert_eq!(got, resize(&image, width, height, filter), "{:?}", filter);
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=255_u8 {
+            let v = f32::from(i) / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn box_downscale_averages_blocks() {
+        let image = GrayImage::from_fn(5, 3, |x, y| Luma([(x * 10 + y * 100) as u8]));
+
+        let small = box_downscale(&image, 2);
+
+        assert_eq!(small.dimensions(), (3, 2));
+        // (0 + 10 + 100 + 110) / 4
+        assert_eq!(small.get_pixel(0, 0)[0], 55);
+        // (40 + 140) / 2
+        assert_eq!(small.get_pixel(2, 0)[0], 90);
+        assert_eq!(small.get_pixel(2, 1)[0], 240);
+    }
+
+    #[test]
+    fn box_downscale_linear_averages_light() {
+        let image = GrayImage::from_fn(4, 2, |x, _| Luma([if x % 2 == 0 { 0 } else { 255 }]));
+
+        let small = box_downscale_linear(&image, 2);
+
+        assert_eq!(small.dimensions(), (2, 1));
+        // Half of full intensity encoded back to sRGB, rather than the plain average of 128.
+        assert_eq!(small.get_pixel(0, 0)[0], 188);
+        assert_eq!(box_downscale_linear(&image, 1), image);
+    }
+
+    #[test]
+    fn pad_edges_replicates_or_reflects() {
+        let image = GrayImage::from_fn(3, 1, |x, _| Luma([x as u8 * 10 + 10]));
+        let row = |mode| {
+            let padded = pad_edges(&image, 2, mode);
+            assert_eq!(padded.dimensions(), (7, 5));
+            padded
+                .rows()
+                .nth(4)
+                .unwrap()
+                .map(|p| p[0])
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(row(EdgeMode::Replicate), [10, 10, 10, 20, 30, 30, 30]);
+        assert_eq!(row(EdgeMode::Reflect), [30, 20, 10, 20, 30, 20, 10]);
+    }
+
+    #[test]
+    fn edge_directed_keeps_gradients() {
+        let image = GrayImage::from_fn(4, 1, |x, _| Luma([x as u8 * 16]));
+
+        let mut edge = GrayImage::new(16, 1);
+        edge_directed(&image, &mut edge);
+        let mut values: Vec<u8> = edge.pixels().map(|p| p[0]).collect();
+
+        assert!(values.windows(2).all(|w| w[0] <= w[1]));
+        // Smooth gradients are interpolated bilinearly, yielding more distinct values than input.
+        values.dedup();
+        assert!(values.len() > 4);
+    }
+}
@@ -0,0 +1,162 @@
+use image::GrayImage;
+
+/// Largest standard deviation of a row or column considered uniform by [`remove_scan_lines()`].
+const MAX_LINE_DEVIATION: f64 = 2.0;
+
+/// Smallest standard deviation both neighbors of a uniform line need for it to stand out.
+const MIN_NEIGHBOR_DEVIATION: f64 = 8.0;
+
+/// Smallest difference between the mean of a uniform line and its neighbors for it to stand out
+/// on an otherwise uniform background.
+const MIN_MEAN_DIFFERENCE: f64 = 16.0;
+
+/// Replaces full-length rows and columns left by stuck scanner sensor elements, interpolating
+/// between the nearest lines on either side which aren't stuck.
+///
+/// A line is considered stuck if it's uniform along its whole length while its neighbors aren't,
+/// or if its brightness differs from both of them. Returns the number of lines replaced.
+pub(crate) fn remove_scan_lines(image: &mut GrayImage) -> usize {
+    let (width, height) = image.dimensions();
+    let columns = stuck_lines(width, |x| (0..height).map(move |y| (x, y)), image);
+    for &x in &columns {
+        let (before, after) = nearest_intact(x, &columns);
+        for y in 0..height {
+            let value = inpaint(image, x, before, after, |x| (x, y));
+            image.get_pixel_mut(x, y)[0] = value;
+        }
+    }
+    let rows = stuck_lines(height, |y| (0..width).map(move |x| (x, y)), image);
+    for &y in &rows {
+        let (before, after) = nearest_intact(y, &rows);
+        for x in 0..width {
+            let value = inpaint(image, y, before, after, |y| (x, y));
+            image.get_pixel_mut(x, y)[0] = value;
+        }
+    }
+    columns.len() + rows.len()
+}
+
+/// Returns the indices of stuck lines among `count` lines, where `line` yields the coordinates of
+/// the pixels of a line.
+fn stuck_lines<L, I>(count: u32, line: L, image: &GrayImage) -> Vec<u32>
+where
+    L: Fn(u32) -> I,
+    I: Iterator<Item = (u32, u32)>,
+{
+    if count < 3 {
+        return Vec::new();
+    }
+    let stats: Vec<(f64, f64)> = (0..count)
+        .map(|i| mean_deviation(line(i).map(|(x, y)| image[(x, y)][0])))
+        .collect();
+    (1..count - 1)
+        .filter(|&i| {
+            let (mean, deviation) = stats[i as usize];
+            let (before, after) = (stats[i as usize - 1], stats[i as usize + 1]);
+            deviation < MAX_LINE_DEVIATION
+                && (before.1.min(after.1) > MIN_NEIGHBOR_DEVIATION
+                    || (mean - before.0).abs().min((mean - after.0).abs()) > MIN_MEAN_DIFFERENCE)
+        })
+        .collect()
+}
+
+/// Returns the mean and standard deviation of the given values.
+fn mean_deviation(values: impl Iterator<Item = u8>) -> (f64, f64) {
+    let (mut n, mut sum, mut squares) = (0_f64, 0_f64, 0_f64);
+    for v in values {
+        let v = f64::from(v);
+        n += 1.0;
+        sum += v;
+        squares += v * v;
+    }
+    let mean = sum / n;
+    (mean, (squares / n - mean * mean).max(0.0).sqrt())
+}
+
+/// Returns the nearest lines before and after stuck line `i` which aren't among the sorted
+/// `stuck` lines. As returned by [`stuck_lines()`], the first and last lines are never stuck.
+fn nearest_intact(i: u32, stuck: &[u32]) -> (u32, u32) {
+    let mut before = i - 1;
+    while stuck.binary_search(&before).is_ok() {
+        before -= 1;
+    }
+    let mut after = i + 1;
+    while stuck.binary_search(&after).is_ok() {
+        after += 1;
+    }
+    (before, after)
+}
+
+/// Returns the value of line `i` interpolated between lines `before` and `after`, where `pixel`
+/// returns the coordinates of the pixel in a given line.
+fn inpaint(
+    image: &GrayImage,
+    i: u32,
+    before: u32,
+    after: u32,
+    pixel: impl Fn(u32) -> (u32, u32),
+) -> u8 {
+    let distance = after - before;
+    let weighted = u32::from(image[pixel(before)][0]) * (after - i)
+        + u32::from(image[pixel(after)][0]) * (i - before);
+    ((weighted + distance / 2) / distance) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn remove_scan_lines_removes_stuck_column() {
+        let content =
+            GrayImage::from_fn(40, 30, |x, y| Luma([((x * 7 + y * 13) % 50 + 100) as u8]));
+        let mut image = content.clone();
+        for y in 0..30 {
+            image.put_pixel(20, y, Luma([255]));
+        }
+
+        assert_eq!(remove_scan_lines(&mut image), 1);
+
+        for y in 0..30 {
+            let want = (u16::from(content[(19, y)][0]) + u16::from(content[(21, y)][0])) / 2;
+            assert!((i32::from(image[(20, y)][0]) - i32::from(want)).abs() <= 1);
+        }
+        for (x, y, p) in content.enumerate_pixels().filter(|&(x, _, _)| x != 20) {
+            assert_eq!(image[(x, y)], *p);
+        }
+
+        let mut clean = content.clone();
+        assert_eq!(remove_scan_lines(&mut clean), 0);
+    }
+
+    #[test]
+    fn remove_scan_lines_removes_adjacent_columns() {
+        let content =
+            GrayImage::from_fn(40, 30, |x, y| Luma([((x * 7 + y * 13) % 50 + 100) as u8]));
+        let mut image = content.clone();
+        for y in 0..30 {
+            image.put_pixel(20, y, Luma([255]));
+            image.put_pixel(21, y, Luma([0]));
+        }
+
+        assert_eq!(remove_scan_lines(&mut image), 2);
+
+        for y in 0..30 {
+            let (before, after) = (
+                i32::from(content[(19, y)][0]),
+                i32::from(content[(22, y)][0]),
+            );
+            let want = [(2 * before + after) / 3, (before + 2 * after) / 3];
+            for (x, want) in (20..22).zip(want.iter()) {
+                assert!(
+                    (i32::from(image[(x, y)][0]) - want).abs() <= 1,
+                    "{}, {}",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+}
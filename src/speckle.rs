@@ -0,0 +1,101 @@
+use image::GrayImage;
+
+/// Removes isolated specks of fewer than `max_size` pixels, such as scan dust.
+///
+/// Pixels are split into dark and light ones at the mean brightness of the image. Dark specks on a
+/// light background are removed first, then light specks on a dark background. Each removed speck
+/// is filled with the mean of the pixels surrounding it. Components are 8-connected, so thin
+/// diagonal lines count as a single component and are kept.
+pub(crate) fn despeckle(image: &mut GrayImage, max_size: u32) {
+    let (width, height) = image.dimensions();
+    if max_size <= 1 || width == 0 || height == 0 {
+        return;
+    }
+    let total: u64 = image.iter().map(|&p| u64::from(p)).sum();
+    let mean = (total / (u64::from(width) * u64::from(height))) as u8;
+    remove_specks(image, max_size, |p| p < mean);
+    remove_specks(image, max_size, |p| p >= mean);
+}
+
+/// Fills components of pixels matching `class` which are smaller than `max_size`.
+fn remove_specks<C>(image: &mut GrayImage, max_size: u32, class: C)
+where
+    C: Fn(u8) -> bool,
+{
+    let (width, height) = image.dimensions();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut visited = vec![false; (width * height) as usize];
+    let mut component = Vec::new();
+    let mut stack = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            if visited[index(x, y)] || !class(image.get_pixel(x, y)[0]) {
+                continue;
+            }
+            component.clear();
+            visited[index(x, y)] = true;
+            stack.push((x, y));
+            // Sum and count of the bordering pixels, which don't match the class.
+            let (mut sum, mut count) = (0_u64, 0_u64);
+            while let Some((x, y)) = stack.pop() {
+                component.push((x, y));
+                for (nx, ny) in neighbors(x, y, width, height) {
+                    let p = image.get_pixel(nx, ny)[0];
+                    if !class(p) {
+                        sum += u64::from(p);
+                        count += 1;
+                    } else if !visited[index(nx, ny)] {
+                        visited[index(nx, ny)] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            if component.len() >= max_size as usize || count == 0 {
+                continue;
+            }
+            let fill = ((sum + count / 2) / count) as u8;
+            for &(x, y) in &component {
+                image.get_pixel_mut(x, y)[0] = fill;
+            }
+        }
+    }
+}
+
+/// Returns the coordinates of the up to 8 pixels neighboring `(x, y)`.
+fn neighbors(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    let xs = x.saturating_sub(1)..=(x + 1).min(width - 1);
+    let ys = y.saturating_sub(1)..=(y + 1).min(height - 1);
+    ys.flat_map(move |ny| xs.clone().map(move |nx| (nx, ny)))
+        .filter(move |&n| n != (x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn despeckle_removes_specks_and_keeps_regions() {
+        let mut image = GrayImage::from_pixel(40, 40, Luma([220]));
+        image.put_pixel(5, 5, Luma([10]));
+        for y in 20..24 {
+            for x in 20..25 {
+                image.put_pixel(x, y, Luma([10]));
+            }
+        }
+        // A light speck inside a dark area.
+        for y in 30..36 {
+            for x in 30..36 {
+                image.put_pixel(x, y, Luma([10]));
+            }
+        }
+        image.put_pixel(32, 32, Luma([220]));
+
+        despeckle(&mut image, 10);
+
+        assert_eq!(image.get_pixel(5, 5)[0], 220);
+        assert!((20..24).all(|y| (20..25).all(|x| image.get_pixel(x, y)[0] == 10)));
+        assert_eq!(image.get_pixel(32, 32)[0], 10);
+    }
+}
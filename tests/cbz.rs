@@ -0,0 +1,132 @@
+//! Test conversion of CBZ archives.
+#![cfg(feature = "cbz")]
+
+use std::io::{Cursor, Read, Write};
+
+use image::{ColorType, DynamicImage, GrayImage, ImageOutputFormat, Luma, Rgb, RgbImage};
+use maco::cbz::{convert_cbz, CbzParams};
+use maco::{ConvertParams, LumaWeights};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+fn png(image: DynamicImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut bytes, ImageOutputFormat::Png).unwrap();
+    bytes
+}
+
+/// Builds a CBZ archive with a color cover and a grayscale interior page, converts it and returns
+/// the decoded output pages.
+fn convert_pages(cbz_params: &CbzParams) -> Vec<DynamicImage> {
+    let cover = RgbImage::from_fn(40, 60, |x, _| {
+        Rgb(if x < 20 { [220, 40, 40] } else { [40, 40, 220] })
+    });
+    let interior = GrayImage::from_fn(40, 60, |x, y| Luma([(x * 2 + y * 3) as u8]));
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, image) in [
+        ("01.png", DynamicImage::ImageRgb8(cover)),
+        ("02.png", DynamicImage::ImageLuma8(interior)),
+    ] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(&png(image)).unwrap();
+    }
+    let input = zip.finish().unwrap();
+
+    let params = ConvertParams::builder().width(20).height(30).build();
+    let mut output = Cursor::new(Vec::new());
+    convert_cbz(
+        Cursor::new(input.into_inner()),
+        &mut output,
+        &params,
+        cbz_params,
+    )
+    .unwrap();
+
+    let mut archive = ZipArchive::new(output).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+    names.sort();
+    names
+        .iter()
+        .map(|name| {
+            let mut bytes = Vec::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut bytes)
+                .unwrap();
+            image::load_from_memory(&bytes).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn convert_cbz_color_cover() {
+    let pages = convert_pages(&CbzParams::builder().color_cover(true).build());
+
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0].color(), ColorType::Rgb8);
+    let cover = pages[0].to_rgb8();
+    assert_eq!(cover.dimensions(), (20, 30));
+    assert!(cover[(2, 15)][0] > cover[(2, 15)][2]);
+    assert!(cover[(17, 15)][0] < cover[(17, 15)][2]);
+    assert_eq!(pages[1].color(), ColorType::L8);
+    assert_eq!(pages[1].to_luma8().dimensions(), (20, 30));
+}
+
+#[test]
+fn convert_cbz_pads_color_cover() {
+    let cover = RgbImage::from_fn(40, 60, |x, y| {
+        Rgb(if (10..30).contains(&x) && (10..50).contains(&y) {
+            [220, 40, 40]
+        } else {
+            [255, 255, 255]
+        })
+    });
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("01.png", FileOptions::default()).unwrap();
+    zip.write_all(&png(DynamicImage::ImageRgb8(cover))).unwrap();
+    let input = zip.finish().unwrap();
+
+    let params = ConvertParams::builder().width(20).height(30).build();
+    let cbz_params = CbzParams::builder()
+        .color_cover(true)
+        .even_pages(true)
+        .build();
+    let mut output = Cursor::new(Vec::new());
+    convert_cbz(
+        Cursor::new(input.into_inner()),
+        &mut output,
+        &params,
+        &cbz_params,
+    )
+    .unwrap();
+
+    let mut archive = ZipArchive::new(output).unwrap();
+    assert_eq!(archive.len(), 2);
+    let mut bytes = Vec::new();
+    archive
+        .by_name("0002.png")
+        .unwrap()
+        .read_to_end(&mut bytes)
+        .unwrap();
+    let blank = image::load_from_memory(&bytes).unwrap();
+    assert_eq!(blank.color(), ColorType::L8);
+    assert_eq!(blank.to_luma8(), GrayImage::from_pixel(20, 30, Luma([255])));
+}
+
+#[test]
+fn convert_cbz_gray_cover() {
+    let rec709 = convert_pages(&CbzParams::default());
+    let rec601 = convert_pages(
+        &CbzParams::builder()
+            .luma_weights(LumaWeights::Rec601)
+            .build(),
+    );
+
+    for pages in [&rec709, &rec601] {
+        assert!(pages.iter().all(|page| page.color() == ColorType::L8));
+    }
+    // Red and blue are weighted differently, which changes the cover but not the interior.
+    assert_ne!(rec709[0].to_luma8(), rec601[0].to_luma8());
+    assert_eq!(rec709[1].to_luma8(), rec601[1].to_luma8());
+}
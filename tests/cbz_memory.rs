@@ -0,0 +1,65 @@
+//! Test the memory footprint of streaming CBZ conversion.
+#![cfg(feature = "cbz")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{Cursor, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use image::{GrayImage, Luma};
+use maco::cbz::{convert_cbz_streaming, CbzParams};
+use maco::io::{encode, SaveParams};
+use maco::ConvertParams;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Tracks the current and peak number of allocated bytes.
+struct Tracking;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Tracking {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Tracking = Tracking;
+
+#[test]
+fn convert_cbz_streaming_holds_one_page() {
+    const SIZE: u32 = 600;
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..12 {
+        let page = GrayImage::from_fn(SIZE, SIZE, |x, y| Luma([((x ^ y) + i) as u8]));
+        let mut bytes = Vec::new();
+        encode(&page, &mut bytes, &SaveParams::default()).unwrap();
+        zip.start_file(format!("{:02}.png", i), FileOptions::default())
+            .unwrap();
+        zip.write_all(&bytes).unwrap();
+    }
+    let input = zip.finish().unwrap().into_inner();
+    let output = tempfile::tempfile().unwrap();
+    let params = ConvertParams::builder().width(300).height(300).build();
+
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    convert_cbz_streaming(input.as_slice(), output, &params, &CbzParams::default()).unwrap();
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+
+    // Decoding, converting and encoding a page takes a few copies of it, holding every page would
+    // take at least twelve.
+    let page = (SIZE * SIZE) as usize;
+    assert!(peak < 4 * page, "peak {} bytes, page {} bytes", peak, page);
+}
@@ -2,9 +2,15 @@
 
 use std::path::PathBuf;
 
-use image::open;
+use image::imageops::FilterType;
+use image::{imageops, open, GrayImage, Luma};
 use imageproc::assert_pixels_eq;
-use maco::{convert, ConvertParams};
+use imageproc::contrast::stretch_contrast;
+use maco::{
+    compute_bounds_batch, convert, convert_and_diff, convert_frames, convert_into, convert_region,
+    convert_source, convert_with_bounds, convert_with_report, resize_only, tone_only,
+    ConvertParams, Rect, Source, ToneCurve,
+};
 
 const BASE_PATH: [&str; 3] = [".", "tests", "images"];
 
@@ -12,14 +18,12 @@ const BASE_PATH: [&str; 3] = [".", "tests", "images"];
 fn convert_sample() {
     let path: PathBuf = BASE_PATH.iter().collect();
 
-    let want = open(&path.join("wikipe-tan-want.png"))
-        .unwrap()
-        .into_luma8();
+    let want = open(path.join("wikipe-tan-want.png")).unwrap().into_luma8();
 
     // TODO: For some reason reading the non-grayscale image and converting to grayscale produces a
     // different result than converting to grayscale using python's pillow (current test baseline).
     // This needs some more investigating. Ideally, we want to open "wikipe-tan.png" here instead.
-    let got = open(&path.join("wikipe-tan-grayscale.png"))
+    let got = open(path.join("wikipe-tan-grayscale.png"))
         .unwrap()
         .into_luma8();
 
@@ -28,3 +32,194 @@ fn convert_sample() {
 
     assert_pixels_eq!(got, want);
 }
+
+#[test]
+fn convert_into_reuses_dst() {
+    let path: PathBuf = BASE_PATH.iter().collect();
+    let large = open(path.join("wikipe-tan-grayscale.png"))
+        .unwrap()
+        .into_luma8();
+    let small = GrayImage::from_fn(40, 30, |x, y| Luma([(x * 5 + y * 3) as u8]));
+
+    let params = ConvertParams::builder().width(100).height(100).build();
+    let want_large = convert(large.clone(), &params);
+    let want_small = convert(small.clone(), &params);
+    let mut dst = GrayImage::new(0, 0);
+
+    convert_into(&large, &mut dst, &params);
+    assert_pixels_eq!(dst, want_large);
+
+    // The resizer writes into the existing buffer.
+    let buffer = dst.as_raw().as_ptr();
+    convert_into(&large, &mut dst, &params);
+    assert_pixels_eq!(dst, want_large);
+    assert_eq!(dst.as_raw().as_ptr(), buffer);
+
+    convert_into(&small, &mut dst, &params);
+    assert_pixels_eq!(dst, want_small);
+
+    // Same dimensions, buffer is reused.
+    let buffer = dst.as_raw().as_ptr();
+    convert_into(&small, &mut dst, &params);
+    assert_pixels_eq!(dst, want_small);
+    assert_eq!(dst.as_raw().as_ptr(), buffer);
+
+    // Also when the source is modified before resizing, if it isn't resized.
+    let params = ConvertParams::builder().auto_invert(true).build();
+    convert_into(&small, &mut dst, &params);
+    assert_pixels_eq!(dst, convert(small.clone(), &params));
+    assert_eq!(dst.as_raw().as_ptr(), buffer);
+}
+
+#[test]
+fn convert_frames_shares_bounds() {
+    // Both frames contain value 100, dark frame at (19, 19), bright frame at (0, 0).
+    let dark = GrayImage::from_fn(20, 20, |x, y| Luma([(x + y) as u8 * 2 + 24]));
+    let bright = GrayImage::from_fn(20, 20, |x, y| Luma([(x + y) as u8 * 2 + 100]));
+    let params = ConvertParams::builder().cutoff(5).gamma(1.0).build();
+
+    let frames = convert_frames(vec![dark.clone(), bright.clone()], &params);
+    assert_eq!(frames[0].get_pixel(19, 19), frames[1].get_pixel(0, 0));
+
+    let dark = convert(dark, &params);
+    let bright = convert(bright, &params);
+    assert_ne!(dark.get_pixel(19, 19), bright.get_pixel(0, 0));
+}
+
+#[test]
+fn convert_region_matches_crop() {
+    let path: PathBuf = BASE_PATH.iter().collect();
+    let image = open(path.join("wikipe-tan-grayscale.png"))
+        .unwrap()
+        .into_luma8();
+    let region = Rect {
+        x: 40,
+        y: 60,
+        width: 80,
+        height: 100,
+    };
+    let params = ConvertParams::builder().width(40).height(40).build();
+
+    let want = convert(
+        imageops::crop_imm(&image, 40, 60, 80, 100).to_image(),
+        &params,
+    );
+
+    assert_pixels_eq!(convert_region(&image, region, &params, None), want);
+}
+
+#[test]
+fn convert_region_empty() {
+    let image = GrayImage::from_pixel(20, 10, Luma([128]));
+    let params = ConvertParams::builder().width(8).height(8).build();
+
+    let disjoint = convert_region(&image, Rect::new(30, 0, 10, 10), &params, None);
+    assert_eq!(disjoint.dimensions(), (0, 0));
+    let zero = convert_region(&image, Rect::new(5, 5, 0, 3), &params, None);
+    assert_eq!(zero.dimensions(), (0, 0));
+}
+
+#[test]
+fn convert_and_diff_identity() {
+    let image = GrayImage::from_fn(300, 200, |x, y| Luma([(x + y) as u8]));
+    let params = ConvertParams::builder()
+        .width(150)
+        .height(150)
+        .cutoff(0)
+        .gamma(1.0)
+        .build();
+
+    let (converted, diff) = convert_and_diff(image, &params);
+
+    assert_eq!(converted.dimensions(), (150, 100));
+    assert_eq!(diff.dimensions(), (150, 100));
+    assert!(diff.pixels().all(|p| p[0] == 0));
+}
+
+#[test]
+fn convert_with_report_matches_transformation() {
+    let path: PathBuf = BASE_PATH.iter().collect();
+    let image = open(path.join("wikipe-tan-grayscale.png"))
+        .unwrap()
+        .into_luma8();
+    let params = ConvertParams::builder()
+        .width(100)
+        .height(100)
+        .cutoff(5)
+        .gamma(1.0)
+        .build();
+
+    let (got, report) = convert_with_report(image.clone(), &params);
+
+    assert_eq!(report.input_dimensions, (195, 239));
+    assert_eq!(report.output_dimensions, got.dimensions());
+    assert_eq!(report.tone_curve, None);
+    let (width, height) = report.output_dimensions;
+    let (lower, upper) = report.stretch_bounds.unwrap();
+    let resized = imageops::resize(&image, width, height, FilterType::CatmullRom);
+    let want = stretch_contrast(&resized, lower, upper);
+    assert_pixels_eq!(got, want);
+
+    let params = ConvertParams::builder()
+        .gamma(0.75)
+        .gamma_protect(10, 240)
+        .build();
+    let (_, report) = convert_with_report(image, &params);
+    assert_eq!(report.output_dimensions, (195, 239));
+    assert_eq!(report.tone_curve, Some(ToneCurve::Gamma(0.75)));
+    assert_eq!(report.gamma_protect, Some((10, 240)));
+    assert!(!report.dithered);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn convert_report_serde_roundtrip() {
+    let image = GrayImage::from_fn(40, 30, |x, y| Luma([(x * 5 + y * 3) as u8]));
+    let params = ConvertParams::builder().build();
+    let (_, report) = convert_with_report(image, &params);
+
+    let json = serde_json::to_string(&report).unwrap();
+    assert_eq!(
+        serde_json::from_str::<maco::ConvertReport>(&json).unwrap(),
+        report
+    );
+}
+
+#[test]
+fn convert_source_kinds() {
+    let path: PathBuf = BASE_PATH.iter().collect();
+    let image = open(path.join("wikipe-tan-grayscale.png"))
+        .unwrap()
+        .into_luma8();
+    let (width, height) = image.dimensions();
+    let params = ConvertParams::builder()
+        .width(width / 2)
+        .upscale(true)
+        .build();
+
+    let full = convert_source(Source::Full(image.clone()), &params);
+    assert_pixels_eq!(full, convert(image.clone(), &params));
+    assert_eq!(full.width(), width / 2);
+    assert!(full.height() < height);
+
+    // A pre-resized image only has its tones adjusted, even if it's smaller than the target.
+    let preview = resize_only(image, &ConvertParams::builder().width(width / 4).build());
+    let sized = convert_source(Source::Sized(preview.clone()), &params);
+    assert_eq!(sized.dimensions(), preview.dimensions());
+    let toned = tone_only(preview, &params);
+    assert_pixels_eq!(sized, toned);
+}
+
+#[test]
+fn convert_with_shared_bounds() {
+    let dark = GrayImage::from_fn(20, 20, |x, y| Luma([(x + y) as u8 * 2 + 24]));
+    let bright = GrayImage::from_fn(20, 20, |x, y| Luma([(x + y) as u8 * 2 + 100]));
+    let params = ConvertParams::builder().cutoff(5).build();
+    let bounds = compute_bounds_batch(&[dark.clone(), bright.clone()], 5);
+
+    // Value 100 is at (19, 19) in the dark page and (0, 0) in the bright one.
+    let dark = convert_with_bounds(dark, &params, bounds);
+    let bright = convert_with_bounds(bright, &params, bounds);
+
+    assert_eq!(dark.get_pixel(19, 19), bright.get_pixel(0, 0));
+}
@@ -0,0 +1,45 @@
+//! Test log messages emitted during conversion.
+
+use std::sync::Mutex;
+
+use image::GrayImage;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use maco::{convert, ConvertParams};
+
+/// Collects log messages emitted by this crate.
+struct Capture(Mutex<Vec<String>>);
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Trace
+    }
+
+    fn log(&self, record: &Record) {
+        if record.target().starts_with("maco") {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+
+#[test]
+fn convert_logs_stages() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let image = GrayImage::from_fn(200, 100, |x, y| image::Luma([(x / 2 + y) as u8]));
+    let params = ConvertParams::builder().width(100).height(100).build();
+    convert(image, &params);
+
+    let messages = LOGGER.0.lock().unwrap();
+    assert!(messages.iter().any(|m| m == "resizing 200x100 to 100x50"));
+    assert!(messages
+        .iter()
+        .any(|m| m.starts_with("percentiles 1 and 99:")));
+    assert!(messages
+        .iter()
+        .any(|m| m.starts_with("applying Gamma(0.75)")));
+}